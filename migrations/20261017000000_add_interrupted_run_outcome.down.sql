@@ -0,0 +1,12 @@
+DROP INDEX IF EXISTS idx_runs_scope_outcome;
+
+UPDATE runs
+SET outcome = 'failed'
+WHERE outcome = 'interrupted';
+
+ALTER TABLE runs
+    DROP CONSTRAINT runs_outcome_check;
+
+ALTER TABLE runs
+    ADD CONSTRAINT runs_outcome_check
+        CHECK (outcome IN ('running', 'completed', 'failed'));
@@ -0,0 +1,12 @@
+-- Interrupted harvests (Ctrl-C mid-run) get their own outcome: incremental
+-- harvesting takes its `from` datestamp from the last *completed* run, and a
+-- partial listing must not count as one.
+ALTER TABLE runs
+    DROP CONSTRAINT runs_outcome_check;
+
+ALTER TABLE runs
+    ADD CONSTRAINT runs_outcome_check
+        CHECK (outcome IN ('running', 'completed', 'failed', 'interrupted'));
+
+CREATE INDEX IF NOT EXISTS idx_runs_scope_outcome
+    ON runs(kind, endpoint, metadata_prefix, outcome, started_at);
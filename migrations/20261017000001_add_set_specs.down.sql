@@ -0,0 +1,7 @@
+ALTER TABLE runs
+    DROP COLUMN set_spec;
+
+DROP INDEX IF EXISTS idx_oai_records_set_specs;
+
+ALTER TABLE oai_records
+    DROP COLUMN set_specs;
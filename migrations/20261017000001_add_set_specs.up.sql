@@ -0,0 +1,13 @@
+-- OAI set membership, as listed in each record's header <setSpec> elements.
+-- Sets narrow a harvest or index run; records stay keyed by
+-- (endpoint, metadata_prefix, identifier) regardless of how many sets they
+-- belong to.
+ALTER TABLE oai_records
+    ADD COLUMN set_specs TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX IF NOT EXISTS idx_oai_records_set_specs ON oai_records USING GIN (set_specs);
+
+-- The set a run was scoped to ('' = the whole endpoint), so incremental
+-- harvests of a set only follow runs of that same set.
+ALTER TABLE runs
+    ADD COLUMN set_spec TEXT NOT NULL DEFAULT '';
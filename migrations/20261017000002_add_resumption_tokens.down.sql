@@ -0,0 +1,4 @@
+ALTER TABLE runs
+    DROP COLUMN listing_started_at;
+
+DROP TABLE resumption_tokens;
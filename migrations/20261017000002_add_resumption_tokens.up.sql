@@ -0,0 +1,21 @@
+-- The resumption token of an in-progress listing, one per scope. Saved after
+-- each page is imported and removed when the listing completes, so a harvest
+-- cut short (Ctrl-C, a page timeout, a crash) picks up where it stopped.
+CREATE TABLE resumption_tokens (
+    endpoint TEXT NOT NULL,
+    metadata_prefix TEXT NOT NULL,
+    set_spec TEXT NOT NULL DEFAULT '',
+    verb TEXT NOT NULL,
+    token TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NULL,
+    listing_from TEXT NULL,
+    listing_started_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (endpoint, metadata_prefix, set_spec)
+);
+
+-- When the listing a harvest completed actually began. Differs from
+-- started_at for runs that resumed an earlier run's listing; incremental
+-- harvests must count from the earlier time.
+ALTER TABLE runs
+    ADD COLUMN listing_started_at TIMESTAMPTZ NULL;
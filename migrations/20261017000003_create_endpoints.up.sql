@@ -0,0 +1,21 @@
+-- What each endpoint says about itself (Identify, ListMetadataFormats,
+-- ListSets), keyed by the base URL records are harvested from
+-- (oai_records.endpoint). Refreshed by the discovery subcommands; Identify is
+-- also recorded by every harvest.
+CREATE TABLE endpoints (
+    endpoint TEXT PRIMARY KEY,
+    repository_name TEXT NOT NULL DEFAULT '',
+    base_url TEXT NOT NULL DEFAULT '',
+    protocol_version TEXT NOT NULL DEFAULT '',
+    admin_emails TEXT[] NOT NULL DEFAULT '{}',
+    earliest_datestamp TEXT NOT NULL DEFAULT '',
+    deleted_record TEXT NOT NULL DEFAULT '',
+    granularity TEXT NOT NULL DEFAULT '',
+    -- [{"metadata_prefix", "schema", "metadata_namespace"}]
+    metadata_formats JSONB NOT NULL DEFAULT '[]',
+    -- [{"set_spec", "set_name"}]
+    sets JSONB NOT NULL DEFAULT '[]',
+    identified_at TIMESTAMPTZ NULL,
+    formats_listed_at TIMESTAMPTZ NULL,
+    sets_listed_at TIMESTAMPTZ NULL
+);
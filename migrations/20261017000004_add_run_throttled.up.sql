@@ -0,0 +1,4 @@
+-- Responses the endpoint throttled (503/429) during a run. Waited out and
+-- retried rather than failed, so they show up here instead of in `failed`.
+ALTER TABLE runs
+    ADD COLUMN throttled INTEGER NOT NULL DEFAULT 0;
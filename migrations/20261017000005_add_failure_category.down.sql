@@ -0,0 +1,2 @@
+ALTER TABLE oai_records
+    DROP COLUMN failure_category;
@@ -0,0 +1,6 @@
+-- Classification of a failed download: 'transient' (network, HTTP 5xx,
+-- malformed response) or 'permanent' (an OAI error such as idDoesNotExist).
+-- NULL for records that have not failed, and for metadata failures.
+ALTER TABLE oai_records
+    ADD COLUMN failure_category TEXT
+    CHECK (failure_category IN ('transient', 'permanent'));
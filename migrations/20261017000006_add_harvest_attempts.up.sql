@@ -0,0 +1,5 @@
+-- Failed harvest attempts (download or metadata) since the record last
+-- completed the pipeline or changed upstream. `harvest --retry` skips records
+-- at/above its attempts budget, quarantining them.
+ALTER TABLE oai_records
+    ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
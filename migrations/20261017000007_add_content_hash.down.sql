@@ -0,0 +1,27 @@
+CREATE OR REPLACE FUNCTION check_status_transition() RETURNS trigger AS $$
+BEGIN
+    IF OLD.status = NEW.status THEN
+        RETURN NEW;  -- no-op transitions are always allowed
+    END IF;
+
+    IF NOT (
+        -- Harvest lifecycle transitions
+        (OLD.status = 'pending'   AND NEW.status IN ('available', 'failed'))
+        OR (OLD.status = 'available' AND NEW.status IN ('parsed', 'failed'))
+        OR (OLD.status = 'failed'    AND NEW.status = 'pending')
+        -- Import upsert / retry can reset to pending or deleted from any state
+        OR (NEW.status IN ('pending', 'deleted'))
+    ) THEN
+        RAISE EXCEPTION 'illegal status transition: % -> %', OLD.status, NEW.status;
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE runs
+    DROP COLUMN unchanged;
+
+ALTER TABLE oai_records
+    DROP COLUMN prior_status,
+    DROP COLUMN content_hash;
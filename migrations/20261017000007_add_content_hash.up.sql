@@ -0,0 +1,35 @@
+-- SHA-256 (hex) of the last stored metadata payload, and the status a record
+-- had before an import requeued it. A re-download whose payload hashes the
+-- same returns the record to that status (available or parsed) instead of
+-- reparsing and reindexing it.
+ALTER TABLE oai_records
+    ADD COLUMN content_hash TEXT,
+    ADD COLUMN prior_status TEXT;
+
+-- Records restored unchanged, per run.
+ALTER TABLE runs
+    ADD COLUMN unchanged INTEGER NOT NULL DEFAULT 0;
+
+-- Allow pending -> parsed, only to restore a parsed record whose re-downloaded
+-- payload was unchanged.
+CREATE OR REPLACE FUNCTION check_status_transition() RETURNS trigger AS $$
+BEGIN
+    IF OLD.status = NEW.status THEN
+        RETURN NEW;  -- no-op transitions are always allowed
+    END IF;
+
+    IF NOT (
+        -- Harvest lifecycle transitions
+        (OLD.status = 'pending'   AND NEW.status IN ('available', 'failed'))
+        OR (OLD.status = 'pending' AND NEW.status = 'parsed' AND OLD.prior_status IS NOT DISTINCT FROM 'parsed')
+        OR (OLD.status = 'available' AND NEW.status IN ('parsed', 'failed'))
+        OR (OLD.status = 'failed'    AND NEW.status = 'pending')
+        -- Import upsert / retry can reset to pending or deleted from any state
+        OR (NEW.status IN ('pending', 'deleted'))
+    ) THEN
+        RAISE EXCEPTION 'illegal status transition: % -> %', OLD.status, NEW.status;
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
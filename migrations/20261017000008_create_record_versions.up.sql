@@ -0,0 +1,13 @@
+-- Stored payload versions of a record, keyed by `oai_records.version` at the
+-- time the payload was downloaded. The payload itself is kept on disk next to
+-- the current one (`<fingerprint>.v<version>.xml`); `metadata` is filled in
+-- when that version is parsed. Older rows are pruned to the retention setting.
+CREATE TABLE record_versions (
+    record_id BIGINT NOT NULL REFERENCES oai_records(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    datestamp TEXT NOT NULL,
+    content_hash TEXT NOT NULL,
+    metadata JSONB,
+    stored_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (record_id, version)
+);
@@ -0,0 +1,5 @@
+ALTER TABLE record_versions
+    DROP COLUMN payload_format;
+
+ALTER TABLE oai_records
+    DROP COLUMN payload_format;
@@ -0,0 +1,9 @@
+-- How each stored payload is encoded on disk: 'xml' (raw), 'gzip' or 'zstd'.
+-- The file extension follows it (.xml, .xml.gz, .xml.zst).
+ALTER TABLE oai_records
+    ADD COLUMN payload_format TEXT NOT NULL DEFAULT 'xml'
+    CHECK (payload_format IN ('xml', 'gzip', 'zstd'));
+
+ALTER TABLE record_versions
+    ADD COLUMN payload_format TEXT NOT NULL DEFAULT 'xml'
+    CHECK (payload_format IN ('xml', 'gzip', 'zstd'));
@@ -0,0 +1 @@
+DROP INDEX idx_oai_records_fingerprint;
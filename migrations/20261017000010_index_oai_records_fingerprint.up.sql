@@ -0,0 +1,2 @@
+-- Payload files are named by fingerprint; `gc` looks records up by it.
+CREATE INDEX IF NOT EXISTS idx_oai_records_fingerprint ON oai_records(fingerprint);
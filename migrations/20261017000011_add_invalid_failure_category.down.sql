@@ -0,0 +1,10 @@
+UPDATE oai_records
+SET failure_category = 'permanent'
+WHERE failure_category = 'invalid';
+
+ALTER TABLE oai_records
+    DROP CONSTRAINT oai_records_failure_category_check;
+
+ALTER TABLE oai_records
+    ADD CONSTRAINT oai_records_failure_category_check
+    CHECK (failure_category IN ('transient', 'permanent'));
@@ -0,0 +1,8 @@
+-- 'invalid': the payload arrived but is not well-formed XML or fails schema
+-- validation.
+ALTER TABLE oai_records
+    DROP CONSTRAINT oai_records_failure_category_check;
+
+ALTER TABLE oai_records
+    ADD CONSTRAINT oai_records_failure_category_check
+    CHECK (failure_category IN ('transient', 'permanent', 'invalid'));
@@ -0,0 +1,4 @@
+ALTER TABLE oai_records
+    DROP COLUMN last_seen_response_date,
+    DROP COLUMN about,
+    DROP COLUMN header_status;
@@ -0,0 +1,12 @@
+-- Provenance kept alongside each record:
+-- * header_status: the header's status attribute as sent (NULL when absent);
+--   `status` only distinguishes deleted records from the rest.
+-- * about: the inner XML of each <about> container delivered with the stored
+--   payload (rights statements, provenance), in document order.
+-- * last_seen_response_date: the responseDate of the listing page the record
+--   was last seen in, as reported by the provider (unlike last_seen_at, which
+--   is our clock).
+ALTER TABLE oai_records
+    ADD COLUMN header_status TEXT,
+    ADD COLUMN about TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN last_seen_response_date TEXT;
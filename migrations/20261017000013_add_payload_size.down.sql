@@ -0,0 +1,16 @@
+UPDATE oai_records
+SET failure_category = 'permanent'
+WHERE failure_category = 'oversize';
+
+ALTER TABLE oai_records
+    DROP CONSTRAINT oai_records_failure_category_check;
+
+ALTER TABLE oai_records
+    ADD CONSTRAINT oai_records_failure_category_check
+    CHECK (failure_category IN ('transient', 'permanent', 'invalid'));
+
+DROP INDEX IF EXISTS idx_oai_records_payload_bytes;
+
+ALTER TABLE oai_records
+    DROP COLUMN component_count,
+    DROP COLUMN payload_bytes;
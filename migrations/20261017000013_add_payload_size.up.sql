@@ -0,0 +1,19 @@
+-- Size of the stored payload, recorded on download:
+-- * payload_bytes: the payload's length in bytes (before compression).
+-- * component_count: its EAD components (<c>, <c01>..<c12>).
+-- Both are NULL for records downloaded before they were tracked.
+ALTER TABLE oai_records
+    ADD COLUMN payload_bytes BIGINT,
+    ADD COLUMN component_count INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_oai_records_payload_bytes
+    ON oai_records (endpoint, metadata_prefix, payload_bytes DESC)
+    WHERE payload_bytes IS NOT NULL;
+
+-- 'oversize': the payload arrived but is larger than --max-payload-bytes.
+ALTER TABLE oai_records
+    DROP CONSTRAINT oai_records_failure_category_check;
+
+ALTER TABLE oai_records
+    ADD CONSTRAINT oai_records_failure_category_check
+    CHECK (failure_category IN ('transient', 'permanent', 'invalid', 'oversize'));
@@ -0,0 +1,163 @@
+//! `compress`: rewrite the payloads already in storage in another
+//! `PayloadFormat`, in place.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::Args;
+use sqlx::{Pool, Postgres};
+use tracing::{info, warn};
+
+use crate::db::{self, history};
+use crate::expand_path;
+use crate::oai::{OaiRecord, PayloadFormat};
+use crate::payload;
+use crate::storage::{Storage, StorageArgs};
+
+#[derive(Debug, Args)]
+pub struct CompressArgs {
+    /// Base directory for downloads
+    #[arg(short, long, default_value = "data", env = "DATA_DIR")]
+    pub dir: PathBuf,
+
+    #[command(flatten)]
+    pub storage: StorageArgs,
+
+    /// Format to rewrite stored payloads in (`xml` decompresses)
+    #[arg(long, default_value_t = PayloadFormat::Zstd)]
+    pub format: PayloadFormat,
+}
+
+#[derive(Default)]
+struct CompressStats {
+    payloads: usize,
+    versions: usize,
+    missing: usize,
+    failed: usize,
+}
+
+/// Rewrite every stored payload (current and versions) not yet in the target
+/// format. Each payload is written alongside the old one, recorded, then the
+/// old one is removed, so an interrupted run can simply be started again. Run
+/// it while no harvest is writing to the same storage.
+pub async fn compress(
+    cfg: CompressArgs,
+    pool: Pool<Postgres>,
+    shutdown: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    let storage = cfg.storage.config(expand_path(&cfg.dir))?.open();
+    let storage = storage.as_ref();
+    let mut stats = CompressStats::default();
+    let mut last_id = 0;
+
+    while !shutdown.load(Ordering::Relaxed) {
+        let batch = db::payload::fetch_for_conversion(&pool, cfg.format, last_id).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        last_id = last.id;
+
+        for record in &batch {
+            if record.payload_format != cfg.format {
+                let from = record.path();
+                let to = record.path_as(cfg.format);
+                match convert(storage, &from, record.payload_format, &to, cfg.format).await {
+                    Ok(true) => {
+                        let rows = db::payload::set_payload_format(
+                            &pool,
+                            record.id,
+                            record.payload_format,
+                            cfg.format,
+                        )
+                        .await?;
+                        if rows > 0 {
+                            remove_replaced(storage, &from).await;
+                            stats.payloads += 1;
+                        }
+                    }
+                    Ok(false) => stats.missing += 1,
+                    Err(e) => {
+                        warn!("Failed to convert {}: {e}", storage.describe(&from));
+                        stats.failed += 1;
+                    }
+                }
+            }
+            convert_versions(&pool, storage, record, cfg.format, &mut stats).await?;
+        }
+    }
+
+    info!(
+        "Converted {} payload(s) and {} version(s) to {} (missing: {}, failed: {})",
+        stats.payloads, stats.versions, cfg.format, stats.missing, stats.failed
+    );
+    if stats.failed > 0 {
+        anyhow::bail!("{} payload(s) could not be converted", stats.failed);
+    }
+    Ok(())
+}
+
+async fn convert_versions(
+    pool: &Pool<Postgres>,
+    storage: &dyn Storage,
+    record: &OaiRecord,
+    format: PayloadFormat,
+    stats: &mut CompressStats,
+) -> anyhow::Result<()> {
+    for version in history::versions(pool, record.id).await? {
+        if version.payload_format == format {
+            continue;
+        }
+        let from = record.version_path(version.version, version.payload_format);
+        let to = record.version_path(version.version, format);
+        match convert(storage, &from, version.payload_format, &to, format).await {
+            Ok(true) => {
+                let rows = db::payload::set_version_format(
+                    pool,
+                    record.id,
+                    version.version,
+                    version.payload_format,
+                    format,
+                )
+                .await?;
+                if rows > 0 {
+                    remove_replaced(storage, &from).await;
+                    stats.versions += 1;
+                }
+            }
+            Ok(false) => stats.missing += 1,
+            Err(e) => {
+                warn!("Failed to convert {}: {e}", storage.describe(&from));
+                stats.failed += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Store the payload at `from` under `to` in the new format. Returns false
+/// when there is nothing at `from` (e.g. a record not downloaded yet).
+async fn convert(
+    storage: &dyn Storage,
+    from: &Path,
+    from_format: PayloadFormat,
+    to: &Path,
+    to_format: PayloadFormat,
+) -> anyhow::Result<bool> {
+    let Some(stored) = storage.get(from).await? else {
+        return Ok(false);
+    };
+    let encoded = tokio::task::spawn_blocking(move || -> std::io::Result<Vec<u8>> {
+        let text = payload::decode(stored, from_format)?;
+        Ok(payload::encode(to_format, &text)?.into_owned())
+    })
+    .await??;
+    storage.put(to, encoded).await?;
+    Ok(true)
+}
+
+async fn remove_replaced(storage: &dyn Storage, key: &Path) {
+    if let Err(e) = storage.delete(key).await {
+        warn!("Failed to remove {}: {e}", storage.describe(key));
+    }
+}
@@ -0,0 +1,99 @@
+use oai_pmh::client::response::{Identify, MetadataFormat, Set};
+use serde_json::json;
+use sqlx::{Error, PgPool};
+
+/// Record an endpoint's `Identify` response.
+pub async fn upsert_identify(
+    pool: &PgPool,
+    endpoint: &str,
+    identify: &Identify,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO endpoints (
+            endpoint, repository_name, base_url, protocol_version, admin_emails,
+            earliest_datestamp, deleted_record, granularity, identified_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())
+        ON CONFLICT (endpoint) DO UPDATE SET
+            repository_name = EXCLUDED.repository_name,
+            base_url = EXCLUDED.base_url,
+            protocol_version = EXCLUDED.protocol_version,
+            admin_emails = EXCLUDED.admin_emails,
+            earliest_datestamp = EXCLUDED.earliest_datestamp,
+            deleted_record = EXCLUDED.deleted_record,
+            granularity = EXCLUDED.granularity,
+            identified_at = EXCLUDED.identified_at
+        "#,
+    )
+    .bind(endpoint)
+    .bind(&identify.repository_name)
+    .bind(&identify.base_url)
+    .bind(&identify.protocol_version)
+    .bind(&identify.admin_email)
+    .bind(&identify.earliest_datestamp)
+    .bind(&identify.deleted_record)
+    .bind(&identify.granularity)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record the metadata formats an endpoint disseminates.
+pub async fn update_metadata_formats(
+    pool: &PgPool,
+    endpoint: &str,
+    formats: &[MetadataFormat],
+) -> Result<(), Error> {
+    let formats: Vec<_> = formats
+        .iter()
+        .map(|format| {
+            json!({
+                "metadata_prefix": format.metadata_prefix,
+                "schema": format.schema,
+                "metadata_namespace": format.metadata_namespace,
+            })
+        })
+        .collect();
+
+    sqlx::query(
+        r#"
+        INSERT INTO endpoints (endpoint, metadata_formats, formats_listed_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (endpoint) DO UPDATE SET
+            metadata_formats = EXCLUDED.metadata_formats,
+            formats_listed_at = EXCLUDED.formats_listed_at
+        "#,
+    )
+    .bind(endpoint)
+    .bind(serde_json::Value::Array(formats))
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record an endpoint's sets (empty for endpoints without a set hierarchy).
+pub async fn update_sets(pool: &PgPool, endpoint: &str, sets: &[Set]) -> Result<(), Error> {
+    let sets: Vec<_> = sets
+        .iter()
+        .map(|set| json!({ "set_spec": set.set_spec, "set_name": set.set_name }))
+        .collect();
+
+    sqlx::query(
+        r#"
+        INSERT INTO endpoints (endpoint, sets, sets_listed_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (endpoint) DO UPDATE SET
+            sets = EXCLUDED.sets,
+            sets_listed_at = EXCLUDED.sets_listed_at
+        "#,
+    )
+    .bind(endpoint)
+    .bind(serde_json::Value::Array(sets))
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
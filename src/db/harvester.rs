@@ -1,62 +1,108 @@
+use chrono::{DateTime, Utc};
 use sqlx::postgres::PgQueryResult;
 use sqlx::{Error, PgPool, Postgres, Transaction};
 
-use crate::oai::{HarvestEvent, OaiHeader, OaiIndexStatus, OaiRecord, OaiRecordStatus, OaiScope};
+use crate::oai::{
+    FailureCategory, HarvestEvent, OaiHeader, OaiIndexStatus, OaiRecord, OaiRecordStatus, OaiScope,
+};
 
 #[derive(Default)]
 pub(crate) struct ImportStats {
     pub(crate) processed: usize,
     pub(crate) imported: usize,
     pub(crate) deleted: usize,
+    /// Changed records whose inline (`ListRecords`) payload could not be stored.
+    pub(crate) failed: usize,
+    /// Changed records whose inline payload hashed the same as the one
+    /// already stored, restored to their prior status.
+    pub(crate) unchanged: usize,
+    /// When the listing began, if it was resumed from an earlier run.
+    pub(crate) listing_started_at: Option<DateTime<Utc>>,
 }
 
 impl ImportStats {
+    /// Counts for the rows returned by `batch_upsert_records`.
+    pub(crate) fn tally(records: &[OaiRecord]) -> Self {
+        let deleted = records
+            .iter()
+            .filter(|r| r.status == OaiRecordStatus::Deleted)
+            .count();
+        Self {
+            processed: records.len(),
+            imported: records.len() - deleted,
+            deleted,
+            failed: 0,
+            unchanged: 0,
+            listing_started_at: None,
+        }
+    }
+
     pub(crate) fn accumulate(&mut self, other: &Self) {
         self.processed += other.processed;
         self.imported += other.imported;
         self.deleted += other.deleted;
+        self.failed += other.failed;
+        self.unchanged += other.unchanged;
     }
 }
 
+/// Upsert a page of listed headers, from a response dated `response_date`.
+/// Returns the rows that were inserted or changed (new datestamp) — unchanged
+/// and failed records are left alone.
 pub(crate) async fn batch_upsert_records(
     pool: &PgPool,
     scope: &OaiScope,
     records: &[OaiHeader],
-) -> anyhow::Result<ImportStats> {
+    response_date: &str,
+) -> anyhow::Result<Vec<OaiRecord>> {
     if records.is_empty() {
-        return Ok(ImportStats::default());
+        return Ok(Vec::new());
     }
 
     let identifiers: Vec<_> = records.iter().map(|r| r.identifier.as_str()).collect();
     let datestamps: Vec<_> = records.iter().map(|r| r.datestamp.as_str()).collect();
     let statuses: Vec<_> = records.iter().map(|r| r.status.as_str()).collect();
+    // Postgres arrays must be rectangular, so each record's sets travel as one
+    // space-separated string (the setSpec grammar excludes whitespace).
+    let set_specs: Vec<_> = records.iter().map(|r| r.set_specs.join(" ")).collect();
+    let header_statuses: Vec<_> = records.iter().map(|r| r.header_status.as_deref()).collect();
     let batch_len = records.len() as i32;
 
     let mut tx = pool.begin().await?;
 
-    let rows = sqlx::query_as::<_, (i64, String)>(
+    let rows = sqlx::query_as::<_, OaiRecord>(
         r#"
         INSERT INTO oai_records (
-            endpoint, metadata_prefix, identifier, datestamp, status, message, last_checked_at
+            endpoint, metadata_prefix, identifier, datestamp, status, message, last_checked_at,
+            set_specs
         )
-        SELECT * FROM UNNEST(
+        SELECT endpoint, metadata_prefix, identifier, datestamp, status, message, last_checked_at,
+               string_to_array(set_specs, ' ')
+        FROM UNNEST(
             ARRAY_FILL($1::text, ARRAY[$6]),
             ARRAY_FILL($2::text, ARRAY[$6]),
             $3::text[],
             $4::text[],
             $5::text[],
             ARRAY_FILL(''::text, ARRAY[$6]),
-            ARRAY_FILL(NOW(), ARRAY[$6])
-        )
+            ARRAY_FILL(NOW(), ARRAY[$6]),
+            $8::text[]
+        ) AS t(endpoint, metadata_prefix, identifier, datestamp, status, message, last_checked_at,
+               set_specs)
         ON CONFLICT (endpoint, metadata_prefix, identifier) DO UPDATE SET
             datestamp = EXCLUDED.datestamp,
             status = EXCLUDED.status,
             message = '',
+            attempts = 0,
+            prior_status = CASE
+                WHEN oai_records.status = $9 THEN oai_records.prior_status
+                ELSE oai_records.status
+            END,
             version = oai_records.version + 1,
             last_checked_at = EXCLUDED.last_checked_at
         WHERE oai_records.status != $7
         AND oai_records.datestamp != EXCLUDED.datestamp
-        RETURNING id, status
+        RETURNING id, identifier, fingerprint, status, payload_format
         "#,
     )
     .bind(&scope.endpoint)
@@ -66,13 +112,15 @@ pub(crate) async fn batch_upsert_records(
     .bind(&statuses)
     .bind(batch_len)
     .bind(OaiRecordStatus::Failed.as_str())
+    .bind(&set_specs)
+    .bind(OaiRecordStatus::Pending.as_str())
     .fetch_all(&mut *tx)
     .await?;
 
     let deleted_ids: Vec<i64> = rows
         .iter()
-        .filter(|(_, status)| status.as_str() == OaiRecordStatus::Deleted.as_str())
-        .map(|(id, _)| *id)
+        .filter(|r| r.status == OaiRecordStatus::Deleted)
+        .map(|r| r.id)
         .collect();
 
     if !deleted_ids.is_empty() {
@@ -81,33 +129,40 @@ pub(crate) async fn batch_upsert_records(
 
     // Every header in the batch was seen in the feed, including unchanged
     // records the upsert skipped — this is what makes orphan detection
-    // (records that silently vanish from the feed) possible.
+    // (records that silently vanish from the feed) possible. Set membership and
+    // the raw header status are refreshed alongside. A deleted header, or one
+    // listing no sets, keeps the membership already known: repositories often
+    // drop setSpec from deleted headers, and the record must stay selectable
+    // by `--set` so its deletion reaches the index.
     sqlx::query(
         r#"
-        UPDATE oai_records
-        SET last_seen_at = NOW()
-        WHERE endpoint = $1
-          AND metadata_prefix = $2
-          AND identifier = ANY($3::text[])
+        UPDATE oai_records r
+        SET last_seen_at = NOW(),
+            last_seen_response_date = $5,
+            set_specs = CASE
+                WHEN t.header_status = $7 OR t.set_specs = '' THEN r.set_specs
+                ELSE string_to_array(t.set_specs, ' ')
+            END,
+            header_status = t.header_status
+        FROM UNNEST($3::text[], $4::text[], $6::text[]) AS t(identifier, set_specs, header_status)
+        WHERE r.endpoint = $1
+          AND r.metadata_prefix = $2
+          AND r.identifier = t.identifier
         "#,
     )
     .bind(&scope.endpoint)
     .bind(&scope.metadata_prefix)
     .bind(&identifiers)
+    .bind(&set_specs)
+    .bind(response_date)
+    .bind(&header_statuses)
+    .bind(OaiRecordStatus::Deleted.as_str())
     .execute(&mut *tx)
     .await?;
 
     tx.commit().await?;
 
-    let deleted = deleted_ids.len();
-    let processed = rows.len();
-    let imported = processed.saturating_sub(deleted);
-
-    Ok(ImportStats {
-        processed,
-        imported,
-        deleted,
-    })
+    Ok(rows)
 }
 
 /// Requeue deleted records for purge: `* -> pending`. A partial reset —
@@ -142,12 +197,13 @@ pub(crate) async fn fetch(
 ) -> Result<Vec<OaiRecord>, Error> {
     sqlx::query_as::<_, OaiRecord>(
         r#"
-        SELECT id, identifier, fingerprint, status
+        SELECT id, identifier, fingerprint, status, payload_format
         FROM oai_records
         WHERE endpoint = $1
           AND metadata_prefix = $2
           AND status = $3
           AND ($4::TEXT IS NULL OR identifier > $4)
+          AND ($5::TEXT IS NULL OR $5 = ANY(set_specs))
         ORDER BY identifier
         LIMIT 100
         "#,
@@ -156,31 +212,85 @@ pub(crate) async fn fetch(
     .bind(&scope.metadata_prefix)
     .bind(status.as_str())
     .bind(last_identifier)
+    .bind(&scope.set)
     .fetch_all(pool)
     .await
 }
 
-/// Batch retry: reset all failed harvest records to pending.
+/// Which failed records a batch retry resets.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetryFilter {
+    /// Only failures classified as this category.
+    pub category: Option<FailureCategory>,
+    /// Skip records at/above this many failed attempts (`None` = unlimited).
+    pub max_attempts: Option<i32>,
+}
+
+/// Batch retry: reset failed harvest records matching `filter` to pending.
+/// `attempts` is kept, so a record that keeps failing reaches the budget.
+/// `prior_status` is cleared, so the re-download is parsed afresh even if it
+/// hashes the same as the stored payload.
 ///
 /// Transition: `failed -> pending`.
-pub async fn retry(pool: &PgPool, scope: &OaiScope) -> Result<PgQueryResult, Error> {
+pub async fn retry(
+    pool: &PgPool,
+    scope: &OaiScope,
+    filter: RetryFilter,
+) -> Result<PgQueryResult, Error> {
     sqlx::query(
         r#"
         UPDATE oai_records
-        SET status = $3, message = '', last_checked_at = NOW()
+        SET status = $3,
+            message = '',
+            failure_category = NULL,
+            prior_status = NULL,
+            last_checked_at = NOW()
         WHERE endpoint = $1
           AND metadata_prefix = $2
           AND status = $4
+          AND ($5::TEXT IS NULL OR $5 = ANY(set_specs))
+          AND ($6::TEXT IS NULL OR failure_category = $6)
+          AND ($7::INT IS NULL OR attempts < $7)
         "#,
     )
     .bind(&scope.endpoint)
     .bind(&scope.metadata_prefix)
     .bind(OaiRecordStatus::Pending.as_str())
     .bind(OaiRecordStatus::Failed.as_str())
+    .bind(&scope.set)
+    .bind(filter.category.map(|category| category.as_str()))
+    .bind(filter.max_attempts)
     .execute(pool)
     .await
 }
 
+/// Failed records at/above `max_attempts`: those a retry with that budget
+/// leaves quarantined.
+pub async fn count_quarantined(
+    pool: &PgPool,
+    scope: &OaiScope,
+    max_attempts: i32,
+) -> Result<i64, Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)
+        FROM oai_records
+        WHERE endpoint = $1
+          AND metadata_prefix = $2
+          AND status = $3
+          AND ($4::TEXT IS NULL OR $4 = ANY(set_specs))
+          AND attempts >= $5
+        "#,
+    )
+    .bind(&scope.endpoint)
+    .bind(&scope.metadata_prefix)
+    .bind(OaiRecordStatus::Failed.as_str())
+    .bind(&scope.set)
+    .bind(max_attempts)
+    .fetch_one(pool)
+    .await
+}
+
 /// Apply a harvest event for a single record. Returns the number of affected
 /// `oai_records` rows (0 or 1).
 pub async fn transition(
@@ -190,11 +300,27 @@ pub async fn transition(
     event: &HarvestEvent<'_>,
 ) -> Result<u64, Error> {
     match event {
-        // pending -> available
-        HarvestEvent::DownloadSucceeded => sqlx::query(
+        // pending -> available. `prior_status` is cleared: this payload has
+        // not been parsed, so a later unchanged download must not skip back
+        // past its parse. `attempts` is kept until the record is parsed, so a
+        // record whose metadata keeps failing still reaches the budget.
+        HarvestEvent::DownloadSucceeded {
+            content_hash,
+            payload_format,
+            about,
+            size,
+        } => sqlx::query(
             r#"
             UPDATE oai_records
-            SET status = $4, message = '', last_checked_at = NOW()
+            SET status = $4,
+                message = '',
+                prior_status = NULL,
+                content_hash = $6,
+                payload_format = $7,
+                about = $8,
+                payload_bytes = $9,
+                component_count = $10,
+                last_checked_at = NOW()
             WHERE endpoint = $1
               AND metadata_prefix = $2
               AND identifier = $3
@@ -206,21 +332,74 @@ pub async fn transition(
         .bind(identifier)
         .bind(OaiRecordStatus::Available.as_str())
         .bind(OaiRecordStatus::Pending.as_str())
+        .bind(content_hash)
+        .bind(payload_format.as_str())
+        .bind(about)
+        .bind(size.bytes)
+        .bind(size.components)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected()),
+
+        // pending -> available|parsed (the prior status), leaving
+        // `indexer_records` alone. Affects no rows unless the stored hash
+        // matches and the record was available or parsed before it was
+        // requeued.
+        HarvestEvent::DownloadUnchanged {
+            content_hash,
+            payload_format,
+            about,
+            size,
+        } => sqlx::query(
+            r#"
+            UPDATE oai_records
+            SET status = prior_status,
+                message = '',
+                payload_format = $8,
+                about = $9,
+                payload_bytes = $10,
+                component_count = $11,
+                last_checked_at = NOW()
+            WHERE endpoint = $1
+              AND metadata_prefix = $2
+              AND identifier = $3
+              AND status = $4
+              AND content_hash = $5
+              AND prior_status IN ($6, $7)
+            "#,
+        )
+        .bind(&scope.endpoint)
+        .bind(&scope.metadata_prefix)
+        .bind(identifier)
+        .bind(OaiRecordStatus::Pending.as_str())
+        .bind(content_hash)
+        .bind(OaiRecordStatus::Available.as_str())
+        .bind(OaiRecordStatus::Parsed.as_str())
+        .bind(payload_format.as_str())
+        .bind(about)
+        .bind(size.bytes)
+        .bind(size.components)
         .execute(pool)
         .await
         .map(|result| result.rows_affected()),
 
         // pending -> failed (download) or available -> failed (metadata)
-        HarvestEvent::DownloadFailed { message } | HarvestEvent::MetadataFailed { message } => {
-            let from = match event {
-                HarvestEvent::DownloadFailed { .. } => OaiRecordStatus::Pending,
-                HarvestEvent::MetadataFailed { .. } => OaiRecordStatus::Available,
+        HarvestEvent::DownloadFailed { message, .. } | HarvestEvent::MetadataFailed { message } => {
+            let (from, category) = match event {
+                HarvestEvent::DownloadFailed { category, .. } => {
+                    (OaiRecordStatus::Pending, Some(category.as_str()))
+                }
+                HarvestEvent::MetadataFailed { .. } => (OaiRecordStatus::Available, None),
                 _ => unreachable!(),
             };
             sqlx::query(
                 r#"
                 UPDATE oai_records
-                SET status = $4, message = $5, last_checked_at = NOW()
+                SET status = $4,
+                    message = $5,
+                    failure_category = $7,
+                    attempts = attempts + 1,
+                    last_checked_at = NOW()
                 WHERE endpoint = $1
                   AND metadata_prefix = $2
                   AND identifier = $3
@@ -233,6 +412,7 @@ pub async fn transition(
             .bind(OaiRecordStatus::Failed.as_str())
             .bind(message)
             .bind(from.as_str())
+            .bind(category)
             .execute(pool)
             .await
             .map(|result| result.rows_affected())
@@ -248,6 +428,7 @@ pub async fn transition(
             SET status = $4,
                 metadata = $5,
                 message = '',
+                attempts = 0,
                 last_checked_at = NOW()
             WHERE endpoint = $1
               AND metadata_prefix = $2
@@ -285,6 +466,23 @@ pub async fn transition(
                 .bind(OaiIndexStatus::Pending.as_str())
                 .execute(&mut *tx)
                 .await?;
+
+                // Keep the fields alongside the newest stored payload version,
+                // for `record diff`.
+                sqlx::query(
+                    r#"
+                    UPDATE record_versions
+                    SET metadata = $2
+                    WHERE record_id = $1
+                      AND version = (
+                          SELECT MAX(version) FROM record_versions WHERE record_id = $1
+                      )
+                    "#,
+                )
+                .bind(record_id)
+                .bind(metadata)
+                .execute(&mut *tx)
+                .await?;
             }
 
             tx.commit().await?;
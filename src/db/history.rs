@@ -0,0 +1,122 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Error, PgPool};
+
+use crate::oai::{OaiRecord, PayloadFormat};
+
+/// A record located by identifier alone, with the scope it lives under.
+#[derive(sqlx::FromRow)]
+pub struct ScopedRecord {
+    pub endpoint: String,
+    pub metadata_prefix: String,
+    #[sqlx(flatten)]
+    pub record: OaiRecord,
+}
+
+/// One stored payload version of a record.
+#[derive(Debug, sqlx::FromRow)]
+pub struct RecordVersion {
+    pub version: i32,
+    pub datestamp: String,
+    pub content_hash: String,
+    pub payload_format: PayloadFormat,
+    /// Extracted metadata, once this version has been parsed.
+    pub metadata: Option<serde_json::Value>,
+    pub stored_at: DateTime<Utc>,
+}
+
+/// Record the payload just stored for `record_id` as its current version.
+/// Returns that version (`oai_records.version`).
+pub(crate) async fn store_version(
+    pool: &PgPool,
+    record_id: i64,
+    content_hash: &str,
+    payload_format: PayloadFormat,
+) -> Result<i32, Error> {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO record_versions (record_id, version, datestamp, content_hash, payload_format)
+        SELECT id, version, datestamp, $2, $3
+        FROM oai_records
+        WHERE id = $1
+        ON CONFLICT (record_id, version) DO UPDATE SET
+            datestamp = EXCLUDED.datestamp,
+            content_hash = EXCLUDED.content_hash,
+            payload_format = EXCLUDED.payload_format,
+            metadata = NULL,
+            stored_at = NOW()
+        RETURNING version
+        "#,
+    )
+    .bind(record_id)
+    .bind(content_hash)
+    .bind(payload_format.as_str())
+    .fetch_one(pool)
+    .await
+}
+
+/// Drop all but the newest `keep` versions of a record. Returns the dropped
+/// versions (with their formats), whose payload files can then be removed.
+pub(crate) async fn prune(
+    pool: &PgPool,
+    record_id: i64,
+    keep: usize,
+) -> Result<Vec<(i32, PayloadFormat)>, Error> {
+    sqlx::query_as(
+        r#"
+        DELETE FROM record_versions
+        WHERE record_id = $1
+          AND version NOT IN (
+              SELECT version
+              FROM record_versions
+              WHERE record_id = $1
+              ORDER BY version DESC
+              LIMIT $2
+          )
+        RETURNING version, payload_format
+        "#,
+    )
+    .bind(record_id)
+    .bind(keep as i64)
+    .fetch_all(pool)
+    .await
+}
+
+/// Records with this identifier, optionally narrowed to an endpoint and/or
+/// metadata prefix.
+pub async fn find_records(
+    pool: &PgPool,
+    identifier: &str,
+    endpoint: Option<&str>,
+    metadata_prefix: Option<&str>,
+) -> Result<Vec<ScopedRecord>, Error> {
+    sqlx::query_as::<_, ScopedRecord>(
+        r#"
+        SELECT endpoint, metadata_prefix, id, identifier, fingerprint, status, payload_format
+        FROM oai_records
+        WHERE identifier = $1
+          AND ($2::TEXT IS NULL OR endpoint = $2)
+          AND ($3::TEXT IS NULL OR metadata_prefix = $3)
+        ORDER BY endpoint, metadata_prefix
+        "#,
+    )
+    .bind(identifier)
+    .bind(endpoint)
+    .bind(metadata_prefix)
+    .fetch_all(pool)
+    .await
+}
+
+/// Stored versions of a record, oldest first.
+pub async fn versions(pool: &PgPool, record_id: i64) -> Result<Vec<RecordVersion>, Error> {
+    sqlx::query_as::<_, RecordVersion>(
+        r#"
+        SELECT version, datestamp, content_hash, payload_format, metadata, stored_at
+        FROM record_versions
+        WHERE record_id = $1
+        ORDER BY version
+        "#,
+    )
+    .bind(record_id)
+    .fetch_all(pool)
+    .await
+}
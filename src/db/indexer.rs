@@ -25,7 +25,7 @@ pub async fn fetch(
         IndexSelectionMode::Standard => {
             sqlx::query_as::<_, OaiRecord>(
                 r#"
-                SELECT r.id, r.identifier, r.fingerprint, r.status
+                SELECT r.id, r.identifier, r.fingerprint, r.status, r.payload_format, r.payload_bytes
                 FROM oai_records r
                 JOIN indexer_records i ON i.record_id = r.id
                 WHERE r.endpoint = $1
@@ -37,6 +37,7 @@ pub async fn fetch(
                   AND r.metadata->'repository' ? $8
                   AND ($10::TEXT IS NULL OR r.identifier > $10)
                   AND ($11::TEXT IS NULL OR i.message ILIKE ('%' || $11 || '%'))
+                  AND ($12::TEXT IS NULL OR $12 = ANY(r.set_specs))
                 ORDER BY r.identifier
                 LIMIT 100
                 "#,
@@ -52,6 +53,7 @@ pub async fn fetch(
             .bind(params.max_attempts)
             .bind(params.last_identifier)
             .bind(params.message_filter)
+            .bind(&params.scope.set)
             .fetch_all(pool)
             .await
         }
@@ -61,7 +63,7 @@ pub async fn fetch(
         IndexSelectionMode::FailedOnly => {
             sqlx::query_as::<_, OaiRecord>(
                 r#"
-                SELECT r.id, r.identifier, r.fingerprint, r.status
+                SELECT r.id, r.identifier, r.fingerprint, r.status, r.payload_format, r.payload_bytes
                 FROM oai_records r
                 JOIN indexer_records i ON i.record_id = r.id
                 WHERE r.endpoint = $1
@@ -74,6 +76,7 @@ pub async fn fetch(
                   AND ($8::INT IS NULL OR i.attempts < $8)
                   AND ($9::TEXT IS NULL OR r.identifier > $9)
                   AND ($10::TEXT IS NULL OR i.message ILIKE ('%' || $10 || '%'))
+                  AND ($11::TEXT IS NULL OR $11 = ANY(r.set_specs))
                 ORDER BY r.identifier
                 LIMIT 100
                 "#,
@@ -88,6 +91,7 @@ pub async fn fetch(
             .bind(params.max_attempts)
             .bind(params.last_identifier)
             .bind(params.message_filter)
+            .bind(&params.scope.set)
             .fetch_all(pool)
             .await
         }
@@ -95,7 +99,8 @@ pub async fn fetch(
 }
 
 /// Batch reindex: reset the index lifecycle to pending for all parsed/deleted
-/// records in the given source repository. Wildcard transition: any -> pending.
+/// records in the given source repository (and the scope's set, if any).
+/// Wildcard transition: any -> pending.
 pub async fn reindex(
     pool: &PgPool,
     scope: &OaiScope,
@@ -121,6 +126,7 @@ pub async fn reindex(
           AND r.metadata_prefix = $2
           AND r.status = ANY($4::text[])
           AND r.metadata->'repository' ? $5
+          AND ($6::TEXT IS NULL OR $6 = ANY(r.set_specs))
         "#,
     )
     .bind(&scope.endpoint)
@@ -128,6 +134,7 @@ pub async fn reindex(
     .bind(OaiIndexStatus::Pending.as_str())
     .bind(&eligible[..])
     .bind(source_repository)
+    .bind(&scope.set)
     .execute(pool)
     .await
 }
@@ -1,6 +1,10 @@
+pub mod endpoints;
 pub mod harvester;
+pub mod history;
 pub mod indexer;
+pub(crate) mod payload;
 pub mod report;
+pub mod resumption;
 pub mod runs;
 mod summarizer;
 
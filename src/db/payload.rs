@@ -0,0 +1,170 @@
+use sqlx::{Error, PgPool};
+
+use crate::oai::{OaiRecord, OaiRecordStatus, PayloadFormat};
+
+/// A record whose payload should be in storage, with the checksum it was
+/// stored with (`None` for payloads stored before checksums were kept).
+#[derive(sqlx::FromRow)]
+pub(crate) struct StoredPayload {
+    #[sqlx(flatten)]
+    pub(crate) record: OaiRecord,
+    pub(crate) content_hash: Option<String>,
+}
+
+/// The next available or parsed records (by id, after `after_id`): those
+/// whose payload should be in storage.
+pub(crate) async fn fetch_stored(
+    pool: &PgPool,
+    after_id: i64,
+) -> Result<Vec<StoredPayload>, Error> {
+    sqlx::query_as::<_, StoredPayload>(
+        r#"
+        SELECT id, identifier, fingerprint, status, payload_format, content_hash
+        FROM oai_records
+        WHERE id > $1
+          AND status IN ($2, $3)
+        ORDER BY id
+        LIMIT 100
+        "#,
+    )
+    .bind(after_id)
+    .bind(OaiRecordStatus::Available.as_str())
+    .bind(OaiRecordStatus::Parsed.as_str())
+    .fetch_all(pool)
+    .await
+}
+
+/// Requeue a record whose payload is missing or damaged for download:
+/// `status -> pending`. `prior_status` is cleared so the new payload is
+/// parsed and indexed afresh even if it hashes the same. Affects no rows if
+/// the record left `status` in the meantime.
+pub(crate) async fn requeue_for_download(
+    pool: &PgPool,
+    record_id: i64,
+    status: OaiRecordStatus,
+    message: &str,
+) -> Result<u64, Error> {
+    sqlx::query(
+        r#"
+        UPDATE oai_records
+        SET status = $3,
+            message = $4,
+            prior_status = NULL,
+            attempts = 0,
+            last_checked_at = NOW()
+        WHERE id = $1 AND status = $2
+        "#,
+    )
+    .bind(record_id)
+    .bind(status.as_str())
+    .bind(OaiRecordStatus::Pending.as_str())
+    .bind(message)
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected())
+}
+
+/// The next records (by id, after `after_id`) whose current payload, or any
+/// stored version, is not in `format`.
+pub(crate) async fn fetch_for_conversion(
+    pool: &PgPool,
+    format: PayloadFormat,
+    after_id: i64,
+) -> Result<Vec<OaiRecord>, Error> {
+    sqlx::query_as::<_, OaiRecord>(
+        r#"
+        SELECT id, identifier, fingerprint, status, payload_format
+        FROM oai_records r
+        WHERE id > $2
+          AND (
+              payload_format != $1
+              OR EXISTS (
+                  SELECT 1 FROM record_versions v
+                  WHERE v.record_id = r.id AND v.payload_format != $1
+              )
+          )
+        ORDER BY id
+        LIMIT 100
+        "#,
+    )
+    .bind(format.as_str())
+    .bind(after_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Record that a payload was rewritten from `from` to `to`. Affects no rows if
+/// the payload was replaced in the meantime.
+pub(crate) async fn set_payload_format(
+    pool: &PgPool,
+    record_id: i64,
+    from: PayloadFormat,
+    to: PayloadFormat,
+) -> Result<u64, Error> {
+    sqlx::query("UPDATE oai_records SET payload_format = $3 WHERE id = $1 AND payload_format = $2")
+        .bind(record_id)
+        .bind(from.as_str())
+        .bind(to.as_str())
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+/// As `set_payload_format`, for one stored version.
+pub(crate) async fn set_version_format(
+    pool: &PgPool,
+    record_id: i64,
+    version: i32,
+    from: PayloadFormat,
+    to: PayloadFormat,
+) -> Result<u64, Error> {
+    sqlx::query(
+        r#"
+        UPDATE record_versions
+        SET payload_format = $4
+        WHERE record_id = $1 AND version = $2 AND payload_format = $3
+        "#,
+    )
+    .bind(record_id)
+    .bind(version)
+    .bind(from.as_str())
+    .bind(to.as_str())
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected())
+}
+
+/// Status and current payload format of the records with these fingerprints.
+pub(crate) async fn records_by_fingerprint(
+    pool: &PgPool,
+    fingerprints: &[String],
+) -> Result<Vec<(String, OaiRecordStatus, PayloadFormat)>, Error> {
+    sqlx::query_as(
+        r#"
+        SELECT fingerprint, status, payload_format
+        FROM oai_records
+        WHERE fingerprint = ANY($1)
+        "#,
+    )
+    .bind(fingerprints)
+    .fetch_all(pool)
+    .await
+}
+
+/// Stored versions (version, format) of the records with these fingerprints.
+pub(crate) async fn versions_by_fingerprint(
+    pool: &PgPool,
+    fingerprints: &[String],
+) -> Result<Vec<(String, i32, PayloadFormat)>, Error> {
+    sqlx::query_as(
+        r#"
+        SELECT r.fingerprint, v.version, v.payload_format
+        FROM record_versions v
+        JOIN oai_records r ON r.id = v.record_id
+        WHERE r.fingerprint = ANY($1)
+        "#,
+    )
+    .bind(fingerprints)
+    .fetch_all(pool)
+    .await
+}
@@ -15,6 +15,7 @@ pub async fn stale_in_index(pool: &PgPool, scope: &OaiScope) -> Result<Vec<Strin
           AND r.metadata_prefix = $2
           AND i.status = $3
           AND r.status = $4
+          AND ($5::TEXT IS NULL OR $5 = ANY(r.set_specs))
         ORDER BY r.identifier
         "#,
     )
@@ -22,6 +23,7 @@ pub async fn stale_in_index(pool: &PgPool, scope: &OaiScope) -> Result<Vec<Strin
     .bind(&scope.metadata_prefix)
     .bind(OaiIndexStatus::Indexed.as_str())
     .bind(OaiRecordStatus::Failed.as_str())
+    .bind(&scope.set)
     .fetch_all(pool)
     .await
 }
@@ -44,6 +46,7 @@ pub async fn not_seen_since(
           AND status != $3
           AND last_seen_at IS NOT NULL
           AND last_seen_at < NOW() - ($4 * INTERVAL '1 day')
+          AND ($5::TEXT IS NULL OR $5 = ANY(set_specs))
         ORDER BY identifier
         "#,
     )
@@ -51,6 +54,45 @@ pub async fn not_seen_since(
     .bind(&scope.metadata_prefix)
     .bind(OaiRecordStatus::Deleted.as_str())
     .bind(days)
+    .bind(&scope.set)
+    .fetch_all(pool)
+    .await
+}
+
+/// A record's stored payload size, for the largest-records report.
+#[derive(Debug, PartialEq, Eq, sqlx::FromRow)]
+pub struct PayloadSizeRow {
+    pub identifier: String,
+    pub payload_bytes: i64,
+    pub component_count: Option<i32>,
+}
+
+/// The `limit` records with the largest stored payloads, largest first.
+/// Deleted records and those downloaded before sizes were recorded are
+/// excluded.
+pub async fn largest_payloads(
+    pool: &PgPool,
+    scope: &OaiScope,
+    limit: i64,
+) -> Result<Vec<PayloadSizeRow>, Error> {
+    sqlx::query_as::<_, PayloadSizeRow>(
+        r#"
+        SELECT identifier, payload_bytes, component_count
+        FROM oai_records
+        WHERE endpoint = $1
+          AND metadata_prefix = $2
+          AND status != $3
+          AND payload_bytes IS NOT NULL
+          AND ($4::TEXT IS NULL OR $4 = ANY(set_specs))
+        ORDER BY payload_bytes DESC, identifier
+        LIMIT $5
+        "#,
+    )
+    .bind(&scope.endpoint)
+    .bind(&scope.metadata_prefix)
+    .bind(OaiRecordStatus::Deleted.as_str())
+    .bind(&scope.set)
+    .bind(limit)
     .fetch_all(pool)
     .await
 }
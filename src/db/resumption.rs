@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Error, PgPool};
+
+use crate::oai::OaiScope;
+
+/// The saved position of an unfinished listing: the token for the next page
+/// that has not been imported yet.
+#[derive(Debug, sqlx::FromRow)]
+pub struct SavedListing {
+    pub verb: String,
+    pub token: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The `from` argument the listing was started with (`None` = complete).
+    pub listing_from: Option<String>,
+    pub listing_started_at: DateTime<Utc>,
+}
+
+pub async fn load(pool: &PgPool, scope: &OaiScope) -> Result<Option<SavedListing>, Error> {
+    sqlx::query_as::<_, SavedListing>(
+        r#"
+        SELECT verb, token, expires_at, listing_from, listing_started_at
+        FROM resumption_tokens
+        WHERE endpoint = $1
+          AND metadata_prefix = $2
+          AND set_spec = $3
+        "#,
+    )
+    .bind(&scope.endpoint)
+    .bind(&scope.metadata_prefix)
+    .bind(scope.set_spec())
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn save(pool: &PgPool, scope: &OaiScope, listing: &SavedListing) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO resumption_tokens (
+            endpoint, metadata_prefix, set_spec, verb, token, expires_at, listing_from,
+            listing_started_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (endpoint, metadata_prefix, set_spec) DO UPDATE SET
+            verb = EXCLUDED.verb,
+            token = EXCLUDED.token,
+            expires_at = EXCLUDED.expires_at,
+            listing_from = EXCLUDED.listing_from,
+            listing_started_at = EXCLUDED.listing_started_at,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(&scope.endpoint)
+    .bind(&scope.metadata_prefix)
+    .bind(scope.set_spec())
+    .bind(&listing.verb)
+    .bind(&listing.token)
+    .bind(listing.expires_at)
+    .bind(&listing.listing_from)
+    .bind(listing.listing_started_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn clear(pool: &PgPool, scope: &OaiScope) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        DELETE FROM resumption_tokens
+        WHERE endpoint = $1
+          AND metadata_prefix = $2
+          AND set_spec = $3
+        "#,
+    )
+    .bind(&scope.endpoint)
+    .bind(&scope.metadata_prefix)
+    .bind(scope.set_spec())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use sqlx::{Error, PgPool};
 
 use crate::oai::OaiScope;
@@ -7,6 +8,7 @@ pub const KIND_INDEX: &str = "index";
 
 pub const OUTCOME_COMPLETED: &str = "completed";
 pub const OUTCOME_FAILED: &str = "failed";
+pub const OUTCOME_INTERRUPTED: &str = "interrupted";
 
 /// Aggregate counters recorded on a finished run.
 #[derive(Default)]
@@ -15,6 +17,13 @@ pub struct RunStats {
     pub imported: usize,
     pub deleted: usize,
     pub failed: usize,
+    /// Re-downloaded records whose payload was unchanged, so were neither
+    /// reparsed nor reindexed.
+    pub unchanged: usize,
+    /// Requests the endpoint throttled (`503`/`429`) and we waited out.
+    pub throttled: usize,
+    /// Set when the run resumed a listing begun by an earlier run.
+    pub listing_started_at: Option<DateTime<Utc>>,
 }
 
 /// Open a run row (outcome `running`). Returns the run id for `finish`.
@@ -26,8 +35,8 @@ pub async fn start(
 ) -> Result<i64, Error> {
     sqlx::query_scalar::<_, i64>(
         r#"
-        INSERT INTO runs (kind, endpoint, metadata_prefix, source_repository)
-        VALUES ($1, $2, $3, $4)
+        INSERT INTO runs (kind, endpoint, metadata_prefix, source_repository, set_spec)
+        VALUES ($1, $2, $3, $4, $5)
         RETURNING id
         "#,
     )
@@ -35,6 +44,7 @@ pub async fn start(
     .bind(&scope.endpoint)
     .bind(&scope.metadata_prefix)
     .bind(source_repository)
+    .bind(scope.set_spec())
     .fetch_one(pool)
     .await
 }
@@ -55,7 +65,10 @@ pub async fn finish(
             imported = $4,
             deleted = $5,
             failed = $6,
-            error_sample = $7
+            error_sample = $7,
+            listing_started_at = $8,
+            throttled = $9,
+            unchanged = $10
         WHERE id = $1
         "#,
     )
@@ -66,8 +79,43 @@ pub async fn finish(
     .bind(stats.deleted as i32)
     .bind(stats.failed as i32)
     .bind(error_sample)
+    .bind(stats.listing_started_at)
+    .bind(stats.throttled as i32)
+    .bind(stats.unchanged as i32)
     .execute(pool)
     .await?;
 
     Ok(())
 }
+
+/// Start time of the most recent completed run of `kind` for the scope, if
+/// any — or of its listing, when the run resumed one begun earlier. Used as the
+/// lower bound for incremental harvesting: everything that changed after a
+/// completed listing started is picked up by the next one. A set-scoped lookup
+/// also accepts runs of the whole endpoint, which cover every set.
+pub async fn last_completed_started_at(
+    pool: &PgPool,
+    kind: &str,
+    scope: &OaiScope,
+) -> Result<Option<DateTime<Utc>>, Error> {
+    sqlx::query_scalar::<_, DateTime<Utc>>(
+        r#"
+        SELECT COALESCE(listing_started_at, started_at)
+        FROM runs
+        WHERE kind = $1
+          AND endpoint = $2
+          AND metadata_prefix = $3
+          AND (set_spec = $4 OR set_spec = '')
+          AND outcome = $5
+        ORDER BY started_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(kind)
+    .bind(&scope.endpoint)
+    .bind(&scope.metadata_prefix)
+    .bind(scope.set_spec())
+    .bind(OUTCOME_COMPLETED)
+    .fetch_optional(pool)
+    .await
+}
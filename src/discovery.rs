@@ -0,0 +1,132 @@
+//! Endpoint discovery: `Identify`, `ListMetadataFormats` and `ListSets`,
+//! printed and stored in the `endpoints` table.
+
+use std::time::Duration;
+
+use clap::Args;
+use oai_pmh::{Client, ListMetadataFormatsArgs};
+use sqlx::{Pool, Postgres};
+use tokio::time::timeout;
+use tracing::info;
+
+use crate::db;
+use crate::oai::ProtocolError;
+
+#[derive(Debug, Args)]
+pub struct DiscoveryArgs {
+    /// OAI endpoint url
+    pub endpoint: String,
+
+    /// Timeout for individual OAI operations (seconds)
+    #[arg(long, default_value_t = 120, env = "OAI_TIMEOUT")]
+    pub oai_timeout: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct ListFormatsArgs {
+    #[command(flatten)]
+    pub discovery: DiscoveryArgs,
+
+    /// Only list formats available for this record (not stored)
+    #[arg(long)]
+    pub identifier: Option<String>,
+}
+
+/// Print and store an endpoint's `Identify` response.
+pub async fn identify(cfg: DiscoveryArgs, pool: Pool<Postgres>) -> anyhow::Result<()> {
+    let client = Client::new(&cfg.endpoint)?;
+    let response = with_timeout(cfg.oai_timeout, "identify", client.identify()).await??;
+    if let Some(error) = &response.error {
+        return Err(ProtocolError::from(error).into());
+    }
+    let identify = response
+        .payload
+        .ok_or_else(|| anyhow::anyhow!("Identify response missing payload"))?;
+
+    info!("Repository name: {}", identify.repository_name);
+    info!("Base URL: {}", identify.base_url);
+    info!("Protocol version: {}", identify.protocol_version);
+    info!("Admin email: {}", identify.admin_email.join(", "));
+    info!("Earliest datestamp: {}", identify.earliest_datestamp);
+    info!("Deleted records: {}", identify.deleted_record);
+    info!("Granularity: {}", identify.granularity);
+
+    db::endpoints::upsert_identify(&pool, &cfg.endpoint, &identify).await?;
+    Ok(())
+}
+
+/// Print an endpoint's metadata formats, storing them unless the listing was
+/// limited to a single record.
+pub async fn list_formats(cfg: ListFormatsArgs, pool: Pool<Postgres>) -> anyhow::Result<()> {
+    let ListFormatsArgs {
+        discovery: cfg,
+        identifier,
+    } = cfg;
+    let client = Client::new(&cfg.endpoint)?;
+    let args = identifier.as_deref().map(ListMetadataFormatsArgs::new);
+    let response = with_timeout(
+        cfg.oai_timeout,
+        "list_metadata_formats",
+        client.list_metadata_formats(args),
+    )
+    .await??;
+    if let Some(error) = &response.error {
+        return Err(ProtocolError::from(error).into());
+    }
+    let formats = response
+        .payload
+        .map(|payload| payload.metadata_format)
+        .unwrap_or_default();
+
+    info!("Metadata formats: {}", formats.len());
+    for format in &formats {
+        info!(
+            "  {} ({}, {})",
+            format.metadata_prefix, format.metadata_namespace, format.schema
+        );
+    }
+
+    if identifier.is_none() {
+        db::endpoints::update_metadata_formats(&pool, &cfg.endpoint, &formats).await?;
+    }
+    Ok(())
+}
+
+/// Print and store an endpoint's sets.
+pub async fn list_sets(cfg: DiscoveryArgs, pool: Pool<Postgres>) -> anyhow::Result<()> {
+    let client = Client::new(&cfg.endpoint)?;
+    let mut stream = with_timeout(cfg.oai_timeout, "list_sets", client.list_sets()).await??;
+
+    let mut sets = Vec::new();
+    while let Some(response) =
+        with_timeout(cfg.oai_timeout, "list_sets page fetch", stream.try_next()).await??
+    {
+        if let Some(error) = &response.error {
+            match ProtocolError::from(error) {
+                ProtocolError::NoSetHierarchy(_) => break,
+                error => return Err(error.into()),
+            }
+        }
+        if let Some(payload) = response.payload {
+            sets.extend(payload.set);
+        }
+    }
+
+    info!("Sets: {}", sets.len());
+    for set in &sets {
+        info!("  {} ({})", set.set_spec, set.set_name);
+    }
+
+    db::endpoints::update_sets(&pool, &cfg.endpoint, &sets).await?;
+    Ok(())
+}
+
+async fn with_timeout<T>(
+    seconds: u64,
+    label: &str,
+    future: impl Future<Output = T>,
+) -> anyhow::Result<T> {
+    timeout(Duration::from_secs(seconds), future)
+        .await
+        .map_err(|_| anyhow::anyhow!("OAI {label} timed out after {seconds}s"))
+}
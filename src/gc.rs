@@ -0,0 +1,410 @@
+//! `gc`: find the files in a data dir that no record needs any more (payloads
+//! of deleted or vanished records, superseded formats and versions, partial
+//! writes), and remove them with `--apply`.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::Args;
+use sqlx::{Pool, Postgres};
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::db;
+use crate::expand_path;
+use crate::oai::{OaiRecordStatus, PayloadFormat};
+use crate::storage::{StorageArgs, is_temp_file};
+
+/// Length of `oai_records.fingerprint` (hex).
+const FINGERPRINT_LEN: usize = 24;
+
+#[derive(Debug, Args)]
+pub struct GcArgs {
+    /// Base directory for downloads
+    #[arg(short, long, default_value = "data", env = "DATA_DIR")]
+    pub dir: PathBuf,
+
+    #[command(flatten)]
+    pub storage: StorageArgs,
+
+    /// Remove the files found (default: only report them)
+    #[arg(long, default_value_t = false)]
+    pub apply: bool,
+}
+
+/// Why a file is no longer needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Garbage {
+    /// No `oai_records` row has its fingerprint (e.g. a removed endpoint).
+    Orphaned,
+    /// Its record is deleted.
+    Deleted,
+    /// Its record stores the payload (or this version) in another file, or no
+    /// longer keeps this version.
+    Unreferenced,
+    /// A temp file left by an interrupted write.
+    Partial,
+}
+
+impl Garbage {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Orphaned => "orphaned",
+            Self::Deleted => "deleted",
+            Self::Unreferenced => "unreferenced",
+            Self::Partial => "partial",
+        }
+    }
+}
+
+/// A file name in the fingerprint-sharded tree.
+#[derive(Debug, PartialEq)]
+enum PayloadFile {
+    /// `<fingerprint>.<ext>`
+    Current {
+        fingerprint: String,
+        format: PayloadFormat,
+    },
+    /// `<fingerprint>.v<version>.<ext>`
+    Version {
+        fingerprint: String,
+        version: i32,
+        format: PayloadFormat,
+    },
+    Temp,
+}
+
+impl PayloadFile {
+    fn parse(name: &str) -> Option<Self> {
+        if is_temp_file(name) {
+            return Some(Self::Temp);
+        }
+        let (stem, format) = [PayloadFormat::Xml, PayloadFormat::Gzip, PayloadFormat::Zstd]
+            .into_iter()
+            .find_map(|format| {
+                let stem = name.strip_suffix(format.extension())?.strip_suffix('.')?;
+                Some((stem, format))
+            })?;
+        let (fingerprint, version) = match stem.split_once(".v") {
+            Some((fingerprint, version)) => (fingerprint, Some(version.parse().ok()?)),
+            None => (stem, None),
+        };
+        if fingerprint.len() != FINGERPRINT_LEN
+            || !fingerprint
+                .bytes()
+                .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        {
+            return None;
+        }
+
+        let fingerprint = fingerprint.to_string();
+        Some(match version {
+            Some(version) => Self::Version {
+                fingerprint,
+                version,
+                format,
+            },
+            None => Self::Current {
+                fingerprint,
+                format,
+            },
+        })
+    }
+
+    fn fingerprint(&self) -> Option<&str> {
+        match self {
+            Self::Current { fingerprint, .. } | Self::Version { fingerprint, .. } => {
+                Some(fingerprint)
+            }
+            Self::Temp => None,
+        }
+    }
+}
+
+/// What the database knows about the fingerprints in one shard.
+#[derive(Default)]
+struct Known {
+    records: HashMap<String, (OaiRecordStatus, PayloadFormat)>,
+    versions: HashSet<(String, i32, PayloadFormat)>,
+}
+
+impl Known {
+    fn classify(&self, file: &PayloadFile) -> Option<Garbage> {
+        let (fingerprint, file_format) = match file {
+            PayloadFile::Temp => return Some(Garbage::Partial),
+            PayloadFile::Current {
+                fingerprint,
+                format,
+            }
+            | PayloadFile::Version {
+                fingerprint,
+                format,
+                ..
+            } => (fingerprint, format),
+        };
+        let Some((status, stored_format)) = self.records.get(fingerprint) else {
+            return Some(Garbage::Orphaned);
+        };
+        if *status == OaiRecordStatus::Deleted {
+            return Some(Garbage::Deleted);
+        }
+        let referenced = match file {
+            PayloadFile::Version { version, .. } => {
+                self.versions
+                    .contains(&(fingerprint.clone(), *version, *file_format))
+            }
+            _ => stored_format == file_format,
+        };
+        (!referenced).then_some(Garbage::Unreferenced)
+    }
+}
+
+#[derive(Default)]
+struct GcStats {
+    orphaned: usize,
+    deleted: usize,
+    unreferenced: usize,
+    partial: usize,
+    bytes: u64,
+    unrecognized: usize,
+    failed: usize,
+}
+
+impl GcStats {
+    fn add(&mut self, garbage: Garbage, bytes: u64) {
+        *match garbage {
+            Garbage::Orphaned => &mut self.orphaned,
+            Garbage::Deleted => &mut self.deleted,
+            Garbage::Unreferenced => &mut self.unreferenced,
+            Garbage::Partial => &mut self.partial,
+        } += 1;
+        self.bytes += bytes;
+    }
+
+    fn files(&self) -> usize {
+        self.orphaned + self.deleted + self.unreferenced + self.partial
+    }
+}
+
+/// Walk the data dir one top-level shard at a time, report each file no
+/// record needs, and remove them with `--apply`. Files that are not named like
+/// payloads are left alone. Run it while no harvest is writing to the data dir
+/// (a write in progress looks like a partial one). Payloads in object storage
+/// are not walked: gc bails rather than report a local dir no phase reads.
+pub async fn gc(
+    cfg: GcArgs,
+    pool: Pool<Postgres>,
+    shutdown: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    if let Some(url) = &cfg.storage.storage_url {
+        anyhow::bail!("gc only cleans a local data dir; payloads are stored in {url}");
+    }
+    let data_dir = expand_path(&cfg.dir);
+    if !data_dir.is_dir() {
+        anyhow::bail!("base directory was not found");
+    }
+
+    let mut stats = GcStats::default();
+    for shard in shard_dirs(&data_dir).await? {
+        if shutdown.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let mut files = Vec::new();
+        for dir in shard_dirs(&shard).await? {
+            for (path, bytes) in files_in(&dir).await? {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                match PayloadFile::parse(&name) {
+                    Some(file) if in_shard(&file, &path) => files.push((path, bytes, file)),
+                    _ => stats.unrecognized += 1,
+                }
+            }
+        }
+
+        let fingerprints: Vec<String> = files
+            .iter()
+            .filter_map(|(_, _, file)| file.fingerprint().map(str::to_string))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let known = Known {
+            records: db::payload::records_by_fingerprint(&pool, &fingerprints)
+                .await?
+                .into_iter()
+                .map(|(fingerprint, status, format)| (fingerprint, (status, format)))
+                .collect(),
+            versions: db::payload::versions_by_fingerprint(&pool, &fingerprints)
+                .await?
+                .into_iter()
+                .collect(),
+        };
+
+        for (path, bytes, file) in &files {
+            let Some(garbage) = known.classify(file) else {
+                continue;
+            };
+            info!("{}: {}", garbage.label(), path.display());
+            if cfg.apply
+                && let Err(e) = fs::remove_file(path).await
+                && e.kind() != io::ErrorKind::NotFound
+            {
+                warn!("Failed to remove {}: {e}", path.display());
+                stats.failed += 1;
+                continue;
+            }
+            stats.add(garbage, *bytes);
+        }
+
+        if cfg.apply {
+            // Only succeeds for directories the removals emptied.
+            for dir in shard_dirs(&shard).await? {
+                let _ = fs::remove_dir(dir).await;
+            }
+            let _ = fs::remove_dir(&shard).await;
+        }
+    }
+
+    info!(
+        "{} {} file(s), {} bytes (orphaned: {}, deleted: {}, unreferenced: {}, partial: {}); \
+         left {} unrecognized file(s) alone",
+        if cfg.apply { "Removed" } else { "Found" },
+        stats.files(),
+        stats.bytes,
+        stats.orphaned,
+        stats.deleted,
+        stats.unreferenced,
+        stats.partial,
+        stats.unrecognized
+    );
+    if !cfg.apply && stats.files() > 0 {
+        info!("Run again with --apply to remove them");
+    }
+    if stats.failed > 0 {
+        anyhow::bail!("{} file(s) could not be removed", stats.failed);
+    }
+    Ok(())
+}
+
+/// Subdirectories of `dir` named like a fingerprint shard (two hex digits),
+/// sorted.
+async fn shard_dirs(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if entry.file_type().await?.is_dir()
+            && name.len() == 2
+            && name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        {
+            dirs.push(entry.path());
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Regular files in `dir`, with their sizes.
+async fn files_in(dir: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
+    let mut files = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if metadata.is_file() {
+            files.push((entry.path(), metadata.len()));
+        }
+    }
+    Ok(files)
+}
+
+/// Whether `path` is where `file` would be stored (`ab/cd/abcd...`). Temp
+/// files are wherever their payload is.
+fn in_shard(file: &PayloadFile, path: &Path) -> bool {
+    let Some(fingerprint) = file.fingerprint() else {
+        return true;
+    };
+    let mut dirs = path
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.iter().rev().take(2));
+    dirs.next().is_some_and(|dir| *dir == fingerprint[2..4])
+        && dirs.next().is_some_and(|dir| *dir == fingerprint[0..2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FINGERPRINT: &str = "0123456789abcdef01234567";
+
+    #[test]
+    fn parses_payload_file_names() {
+        assert_eq!(
+            PayloadFile::parse(&format!("{FINGERPRINT}.xml.zst")),
+            Some(PayloadFile::Current {
+                fingerprint: FINGERPRINT.to_string(),
+                format: PayloadFormat::Zstd,
+            })
+        );
+        assert_eq!(
+            PayloadFile::parse(&format!("{FINGERPRINT}.v12.xml")),
+            Some(PayloadFile::Version {
+                fingerprint: FINGERPRINT.to_string(),
+                version: 12,
+                format: PayloadFormat::Xml,
+            })
+        );
+        assert_eq!(
+            PayloadFile::parse(&format!(".{FINGERPRINT}.xml.123-0.tmp")),
+            Some(PayloadFile::Temp)
+        );
+        assert_eq!(PayloadFile::parse("notes.xml"), None);
+        assert_eq!(PayloadFile::parse(&format!("{FINGERPRINT}.json")), None);
+        assert_eq!(PayloadFile::parse(&format!("{FINGERPRINT}.vx.xml")), None);
+        assert!(in_shard(
+            &PayloadFile::parse(&format!("{FINGERPRINT}.xml")).unwrap(),
+            Path::new(&format!("data/01/23/{FINGERPRINT}.xml"))
+        ));
+        assert!(!in_shard(
+            &PayloadFile::parse(&format!("{FINGERPRINT}.xml")).unwrap(),
+            Path::new(&format!("data/23/01/{FINGERPRINT}.xml"))
+        ));
+    }
+
+    #[test]
+    fn classifies_files_against_known_records() {
+        let mut known = Known::default();
+        known.records.insert(
+            FINGERPRINT.to_string(),
+            (OaiRecordStatus::Parsed, PayloadFormat::Zstd),
+        );
+        known
+            .versions
+            .insert((FINGERPRINT.to_string(), 2, PayloadFormat::Xml));
+        let classify = |name: String| known.classify(&PayloadFile::parse(&name).unwrap());
+
+        assert_eq!(classify(format!("{FINGERPRINT}.xml.zst")), None);
+        assert_eq!(classify(format!("{FINGERPRINT}.v2.xml")), None);
+        assert_eq!(
+            classify(format!("{FINGERPRINT}.xml")),
+            Some(Garbage::Unreferenced)
+        );
+        assert_eq!(
+            classify(format!("{FINGERPRINT}.v1.xml")),
+            Some(Garbage::Unreferenced)
+        );
+        assert_eq!(
+            classify("ffffffffffffffffffffffff.xml".to_string()),
+            Some(Garbage::Orphaned)
+        );
+
+        known.records.get_mut(FINGERPRINT).unwrap().0 = OaiRecordStatus::Deleted;
+        assert_eq!(
+            known.classify(&PayloadFile::parse(&format!("{FINGERPRINT}.xml.zst")).unwrap()),
+            Some(Garbage::Deleted)
+        );
+    }
+}
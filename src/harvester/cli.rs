@@ -1,33 +1,86 @@
 use std::{
     path::{self, PathBuf},
-    sync::{Arc, atomic::AtomicBool},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 
+use anyhow::Context;
 use clap::Args;
+use futures::stream::{self, StreamExt};
 use sqlx::{Pool, Postgres};
-use tracing::info;
+use tokio::sync::Semaphore;
+use tracing::{Instrument, error, info, info_span, warn};
 
-use super::{Harvester, perform};
-use crate::{OaiConfig, db, expand_path, oai::OaiScope};
+use super::http::{self, Limiters};
+use super::sources::{self, Source};
+use super::{
+    DEFAULT_CONCURRENT_DOWNLOADS, DEFAULT_KEEP_VERSIONS, DEFAULT_MAX_CONCURRENT_DOWNLOADS,
+    DEFAULT_MAX_HARVEST_ATTEMPTS, Harvester, perform,
+};
+use crate::{
+    OaiConfig, db,
+    db::harvester::RetryFilter,
+    db::runs::RunStats,
+    expand_path,
+    oai::{FailureCategory, HarvestMode, OaiScope, PayloadFormat},
+    storage::{StorageArgs, StorageConfig},
+};
 
 #[derive(Debug, Args)]
 pub struct HarvesterArgs {
     /// OAI endpoint url
-    pub endpoint: String,
+    #[arg(required_unless_present = "sources", conflicts_with = "sources")]
+    pub endpoint: Option<String>,
+
+    /// Harvest every source listed in this TOML file (one run per source)
+    #[arg(long)]
+    pub sources: Option<PathBuf>,
+
+    /// Maximum sources harvested at the same time (with --sources)
+    #[arg(long, default_value_t = 4, env = "MAX_CONCURRENT_SOURCES")]
+    pub max_concurrent_sources: usize,
+
+    /// Maximum records downloaded at the same time across all sources (with
+    /// --sources); each source's concurrency applies within it
+    #[arg(long, default_value_t = DEFAULT_MAX_CONCURRENT_DOWNLOADS, env = "MAX_CONCURRENT_DOWNLOADS")]
+    pub max_concurrent_downloads: usize,
 
     /// Base directory for downloads
     #[arg(short, long, default_value = "data", env = "DATA_DIR")]
     pub dir: PathBuf,
 
-    /// OAI metadata prefix
-    #[arg(short, long, env = "METADATA_PREFIX")]
-    pub metadata_prefix: String,
+    #[command(flatten)]
+    pub storage: StorageArgs,
+
+    /// OAI metadata prefix (the default for sources that do not set one)
+    #[arg(
+        short,
+        long,
+        env = "METADATA_PREFIX",
+        required_unless_present = "sources"
+    )]
+    pub metadata_prefix: Option<String>,
+
+    /// Only harvest records in this OAI set (setSpec)
+    #[arg(long, conflicts_with = "sources")]
+    pub set: Option<String>,
 
     /// Reset failed records to pending before harvesting
     #[arg(long, default_value_t = false)]
     pub retry: bool,
 
-    /// XML scanning rules file
+    /// With --retry, only reset failures of this category (transient,
+    /// permanent, invalid or oversize)
+    #[arg(long, requires = "retry")]
+    pub failure_category: Option<FailureCategory>,
+
+    /// With --retry, skip failed records at/above this attempt count
+    #[arg(long, requires = "retry", default_value_t = DEFAULT_MAX_HARVEST_ATTEMPTS)]
+    pub max_attempts: i32,
+
+    /// XML scanning rules file (CSV, or typed fields from a `.toml` file)
     #[arg(short, long, env = "RULES_FILE")]
     pub rules: Option<PathBuf>,
 
@@ -38,6 +91,53 @@ pub struct HarvesterArgs {
     /// Max retries for transient OAI failures (per record)
     #[arg(long, default_value_t = 0, env = "OAI_RETRIES")]
     pub oai_retries: u32,
+
+    /// Records downloaded concurrently (the ceiling with
+    /// --adaptive-concurrency)
+    #[arg(
+        long,
+        default_value_t = DEFAULT_CONCURRENT_DOWNLOADS,
+        env = "OAI_CONCURRENCY",
+        value_parser = parse_concurrency
+    )]
+    pub concurrency: usize,
+
+    /// Start at a quarter of --concurrency and adapt to the endpoint: one more
+    /// download per round of prompt responses, half as many after a timeout
+    /// or server error
+    #[arg(long, default_value_t = false, env = "OAI_ADAPTIVE_CONCURRENCY")]
+    pub adaptive_concurrency: bool,
+
+    /// Maximum OAI requests per second to an endpoint (default: unlimited)
+    #[arg(long, env = "OAI_REQUESTS_PER_SECOND", value_parser = parse_requests_per_second)]
+    pub requests_per_second: Option<f64>,
+
+    /// Payload versions kept per record for `record diff` (0 keeps no history)
+    #[arg(long, default_value_t = DEFAULT_KEEP_VERSIONS, env = "KEEP_VERSIONS")]
+    pub keep_versions: usize,
+
+    /// How downloaded payloads are written to disk (see `compress` for
+    /// existing ones)
+    #[arg(long, default_value_t = PayloadFormat::Xml, env = "PAYLOAD_FORMAT")]
+    pub payload_format: PayloadFormat,
+
+    /// Validate downloaded payloads against this XSD (e.g. EAD 2002 or EAD3)
+    /// with xmllint; invalid ones fail before they are stored
+    #[arg(long, env = "SCHEMA_FILE")]
+    pub schema: Option<PathBuf>,
+
+    /// Fail downloaded payloads larger than this many bytes (category
+    /// oversize) instead of storing them (default: no limit)
+    #[arg(long, env = "MAX_PAYLOAD_BYTES")]
+    pub max_payload_bytes: Option<u64>,
+
+    /// List all records, ignoring the last completed harvest (no `from`)
+    #[arg(long, default_value_t = false)]
+    pub full: bool,
+
+    /// How to fetch records: per-record GetRecord or paged ListRecords
+    #[arg(long, value_enum, default_value_t = HarvestMode::ListIdentifiers, env = "HARVEST_MODE")]
+    pub mode: HarvestMode,
 }
 
 pub async fn harvest(
@@ -45,26 +145,228 @@ pub async fn harvest(
     pool: Pool<Postgres>,
     shutdown: Arc<AtomicBool>,
 ) -> anyhow::Result<()> {
-    info!("Harvesting records from {}", cfg.endpoint);
+    let storage = cfg.storage.config(path::absolute(expand_path(&cfg.dir))?)?;
+
+    if let Some(path) = &cfg.sources {
+        return harvest_sources(&cfg, &expand_path(path), storage, pool, shutdown).await;
+    }
 
-    let scope = OaiScope::new(cfg.endpoint, cfg.metadata_prefix);
+    let endpoint = cfg.endpoint.clone().context("an endpoint is required")?;
+    let metadata_prefix = cfg
+        .metadata_prefix
+        .clone()
+        .context("a metadata prefix is required")?;
+    info!("Harvesting records from {endpoint}");
 
-    if cfg.retry {
-        let result = db::harvester::retry(&pool, &scope).await?;
+    let config = OaiConfig {
+        storage,
+        scope: OaiScope::new(endpoint, metadata_prefix).with_set(cfg.set.clone()),
+        oai_timeout: cfg.oai_timeout,
+        oai_retries: cfg.oai_retries,
+        full: cfg.full,
+        mode: cfg.mode,
+        concurrency: cfg.concurrency,
+        adaptive_concurrency: cfg.adaptive_concurrency,
+        requests_per_second: cfg.requests_per_second,
+        keep_versions: cfg.keep_versions,
+        payload_format: cfg.payload_format,
+        schema: cfg.schema.as_deref().map(expand_path),
+        max_payload_bytes: cfg.max_payload_bytes,
+    };
+    let retry = retry_filter(&cfg);
+    let rules = cfg.rules.map(|p| expand_path(&p));
+    harvest_scope(config, rules, retry, None, None, pool, shutdown).await?;
+    Ok(())
+}
+
+async fn harvest_scope(
+    config: OaiConfig,
+    rules: Option<PathBuf>,
+    retry: Option<RetryFilter>,
+    downloads: Option<Arc<Semaphore>>,
+    limiters: Option<&Limiters>,
+    pool: Pool<Postgres>,
+    shutdown: Arc<AtomicBool>,
+) -> anyhow::Result<RunStats> {
+    if let Some(filter) = retry {
+        let result = db::harvester::retry(&pool, &config.scope, filter).await?;
         info!(
             "Reset {} failed record(s) to pending",
             result.rows_affected()
         );
+        if let Some(max_attempts) = filter.max_attempts {
+            let quarantined =
+                db::harvester::count_quarantined(&pool, &config.scope, max_attempts).await?;
+            if quarantined > 0 {
+                warn!(
+                    "{quarantined} failed record(s) quarantined at/above {max_attempts} attempts \
+                     (raise --max-attempts to retry them)"
+                );
+            }
+        }
     }
 
-    let data_dir = path::absolute(expand_path(&cfg.dir))?;
+    let mut harvester = Harvester::new(config, pool, shutdown)?;
+    if let Some(downloads) = downloads {
+        harvester = harvester.with_download_limit(downloads);
+    }
+    if let Some(limiters) = limiters {
+        harvester = harvester.with_rate_limiters(limiters);
+    }
+    perform(&harvester, rules).await
+}
+
+/// Harvest one sources file entry, filling unset options from the command
+/// line.
+async fn harvest_source(
+    cfg: &HarvesterArgs,
+    source: Source,
+    storage: StorageConfig,
+    downloads: Arc<Semaphore>,
+    limiters: &Limiters,
+    pool: Pool<Postgres>,
+    shutdown: Arc<AtomicBool>,
+) -> anyhow::Result<RunStats> {
+    if shutdown.load(Ordering::Relaxed) {
+        anyhow::bail!("skipped after interrupt");
+    }
 
+    let metadata_prefix = source
+        .metadata_prefix
+        .or_else(|| cfg.metadata_prefix.clone())
+        .context("no metadata_prefix for source (or --metadata-prefix)")?;
     let config = OaiConfig {
-        data_dir,
-        scope,
-        oai_timeout: cfg.oai_timeout,
-        oai_retries: cfg.oai_retries,
+        storage,
+        scope: OaiScope::new(source.endpoint, metadata_prefix).with_set(source.set),
+        oai_timeout: source.oai_timeout.unwrap_or(cfg.oai_timeout),
+        oai_retries: source.oai_retries.unwrap_or(cfg.oai_retries),
+        full: cfg.full,
+        mode: source.mode.unwrap_or(cfg.mode),
+        concurrency: source.concurrency.unwrap_or(cfg.concurrency),
+        adaptive_concurrency: source
+            .adaptive_concurrency
+            .unwrap_or(cfg.adaptive_concurrency),
+        requests_per_second: source.requests_per_second.or(cfg.requests_per_second),
+        keep_versions: source.keep_versions.unwrap_or(cfg.keep_versions),
+        payload_format: source.payload_format.unwrap_or(cfg.payload_format),
+        schema: source
+            .schema
+            .or_else(|| cfg.schema.clone())
+            .map(|p| expand_path(&p)),
+        max_payload_bytes: source.max_payload_bytes.or(cfg.max_payload_bytes),
     };
-    let harvester = Harvester::new(config, pool, shutdown.clone());
-    perform(&harvester, cfg.rules.map(|p| expand_path(&p))).await
+    let rules = source
+        .rules
+        .or_else(|| cfg.rules.clone())
+        .map(|p| expand_path(&p));
+
+    info!("Harvesting records from {}", config.scope.endpoint);
+    harvest_scope(
+        config,
+        rules,
+        retry_filter(cfg),
+        Some(downloads),
+        Some(limiters),
+        pool,
+        shutdown,
+    )
+    .await
+}
+
+fn parse_concurrency(value: &str) -> Result<usize, String> {
+    match value.parse().map_err(|e| format!("{e}"))? {
+        0 => Err("must be at least 1".to_string()),
+        concurrency => Ok(concurrency),
+    }
+}
+
+fn parse_requests_per_second(value: &str) -> Result<f64, String> {
+    let rate = value.parse().map_err(|e| format!("{e}"))?;
+    http::request_interval(rate).map_err(|e| e.to_string())?;
+    Ok(rate)
+}
+
+/// The `--retry` reset to run before harvesting, if any.
+fn retry_filter(cfg: &HarvesterArgs) -> Option<RetryFilter> {
+    cfg.retry.then_some(RetryFilter {
+        category: cfg.failure_category,
+        max_attempts: Some(cfg.max_attempts),
+    })
+}
+
+/// Harvest every entry of a sources file, at most `max_concurrent_sources` at
+/// a time and `max_concurrent_downloads` records at a time between them;
+/// sources on the same endpoint share its request pacing. Each source is its
+/// own run; a failing source does not stop the others. Ends with a combined summary, and fails if any source failed.
+async fn harvest_sources(
+    cfg: &HarvesterArgs,
+    path: &path::Path,
+    storage: StorageConfig,
+    pool: Pool<Postgres>,
+    shutdown: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    let sources = sources::load(path)?;
+    info!(
+        "Harvesting {} source(s) from {}",
+        sources.len(),
+        path.display()
+    );
+
+    let downloads = Arc::new(Semaphore::new(cfg.max_concurrent_downloads.max(1)));
+    let limiters = Limiters::default();
+    let results: Vec<_> = stream::iter(sources)
+        .map(|source| {
+            let label = source.endpoint.clone();
+            let span = info_span!("source", endpoint = %source.endpoint);
+            let harvest = harvest_source(
+                cfg,
+                source,
+                storage.clone(),
+                downloads.clone(),
+                &limiters,
+                pool.clone(),
+                shutdown.clone(),
+            );
+            async move { (label, harvest.await) }.instrument(span)
+        })
+        .buffer_unordered(cfg.max_concurrent_sources.max(1))
+        .collect()
+        .await;
+
+    let mut total = RunStats::default();
+    let mut failed = 0;
+    for (label, result) in &results {
+        match result {
+            Ok(stats) => {
+                info!(
+                    "{label}: processed {}, imported {}, deleted {}, failed {}, throttled {}",
+                    stats.processed, stats.imported, stats.deleted, stats.failed, stats.throttled
+                );
+                total.processed += stats.processed;
+                total.imported += stats.imported;
+                total.deleted += stats.deleted;
+                total.failed += stats.failed;
+                total.throttled += stats.throttled;
+            }
+            Err(e) => {
+                error!("{label}: {e}");
+                failed += 1;
+            }
+        }
+    }
+    info!(
+        "Harvested {} of {} source(s): processed {}, imported {}, deleted {}, failed {}, throttled {}",
+        results.len() - failed,
+        results.len(),
+        total.processed,
+        total.imported,
+        total.deleted,
+        total.failed,
+        total.throttled
+    );
+
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} source(s) failed", results.len());
+    }
+    Ok(())
 }
@@ -0,0 +1,296 @@
+//! How many `GetRecord` downloads are in flight at once: a fixed number, or
+//! (adaptive) a limit that grows by one per round of healthy responses and
+//! halves when the endpoint times out or errors (AIMD), up to the configured
+//! ceiling.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// A response slower than this multiple of the latency baseline is not taken
+/// as a sign the endpoint can handle more.
+const LATENCY_TOLERANCE: u32 = 3;
+
+/// Recent responses the latency baseline is taken from.
+const LATENCY_WINDOW: usize = 32;
+
+/// The baseline is this percentile of the recent latencies: low enough to
+/// reflect a prompt endpoint, high enough that a few unusually fast (tiny or
+/// cached) responses do not set it.
+const BASELINE_PERCENTILE: usize = 10;
+
+/// How a request went, as far as the endpoint's load is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Outcome {
+    /// A response arrived (latency decides whether it counts as healthy).
+    Responded,
+    /// A timeout, `5xx`, throttle or connection failure.
+    Overloaded,
+}
+
+pub(super) struct Concurrency {
+    adaptive: bool,
+    ceiling: usize,
+    state: Mutex<State>,
+    released: Notify,
+}
+
+struct State {
+    limit: usize,
+    in_flight: usize,
+    /// Healthy responses since the limit last grew.
+    healthy: usize,
+    /// Bumped on every decrease. Requests started before it do not decrease
+    /// the limit again, so a burst of failures halves it once.
+    epoch: u64,
+    /// The latencies of the last `LATENCY_WINDOW` responses.
+    recent: VecDeque<Duration>,
+    lowest: usize,
+    highest: usize,
+}
+
+impl Concurrency {
+    /// `ceiling` downloads at once, or with `adaptive` a limit starting at a
+    /// quarter of it.
+    pub(super) fn new(ceiling: usize, adaptive: bool) -> Self {
+        let ceiling = ceiling.max(1);
+        let limit = if adaptive {
+            ceiling.div_ceil(4)
+        } else {
+            ceiling
+        };
+        Self {
+            adaptive,
+            ceiling,
+            state: Mutex::new(State {
+                limit,
+                in_flight: 0,
+                healthy: 0,
+                epoch: 0,
+                recent: VecDeque::with_capacity(LATENCY_WINDOW),
+                lowest: limit,
+                highest: limit,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Wait for a slot under the current limit.
+    pub(super) async fn acquire(&self) -> Permit<'_> {
+        loop {
+            // Registered before the check, so a release in between is not
+            // missed.
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit {
+                    state.in_flight += 1;
+                    return Permit {
+                        concurrency: self,
+                        epoch: state.epoch,
+                        started: Instant::now(),
+                    };
+                }
+            }
+            released.await;
+        }
+    }
+
+    pub(super) fn summary(&self) -> Summary {
+        let state = self.state.lock().unwrap();
+        Summary {
+            adaptive: self.adaptive,
+            ceiling: self.ceiling,
+            last: state.limit,
+            lowest: state.lowest,
+            highest: state.highest,
+        }
+    }
+
+    fn record(&self, epoch: u64, latency: Duration, outcome: Outcome) {
+        if !self.adaptive {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        match outcome {
+            Outcome::Overloaded => {
+                if epoch == state.epoch {
+                    state.limit = (state.limit / 2).max(1);
+                    state.lowest = state.lowest.min(state.limit);
+                    state.healthy = 0;
+                    state.epoch += 1;
+                }
+            }
+            Outcome::Responded => {
+                if state.recent.len() == LATENCY_WINDOW {
+                    state.recent.pop_front();
+                }
+                state.recent.push_back(latency);
+                if latency > state.baseline() * LATENCY_TOLERANCE {
+                    return;
+                }
+                state.healthy += 1;
+                if state.healthy >= state.limit && state.limit < self.ceiling {
+                    state.limit += 1;
+                    state.highest = state.highest.max(state.limit);
+                    state.healthy = 0;
+                    self.released.notify_waiters();
+                }
+            }
+        }
+    }
+}
+
+impl State {
+    /// The `BASELINE_PERCENTILE`th percentile of the recent latencies.
+    fn baseline(&self) -> Duration {
+        let mut recent: Vec<_> = self.recent.iter().copied().collect();
+        recent.sort_unstable();
+        recent
+            .get(recent.len() * BASELINE_PERCENTILE / 100)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// A slot for one request; dropping it frees the slot.
+pub(super) struct Permit<'a> {
+    concurrency: &'a Concurrency,
+    epoch: u64,
+    started: Instant,
+}
+
+impl Permit<'_> {
+    /// Feed the request's outcome (and latency) to the adaptive limit.
+    pub(super) fn finish(self, outcome: Outcome) {
+        self.concurrency
+            .record(self.epoch, self.started.elapsed(), outcome);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.concurrency.state.lock().unwrap().in_flight -= 1;
+        self.concurrency.released.notify_waiters();
+    }
+}
+
+/// Download concurrency over a run, for its summary.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Summary {
+    adaptive: bool,
+    ceiling: usize,
+    last: usize,
+    lowest: usize,
+    highest: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.adaptive {
+            return write!(f, "{}", self.ceiling);
+        }
+        write!(
+            f,
+            "adaptive, ended at {} (range {}-{}, ceiling {})",
+            self.last, self.lowest, self.highest, self.ceiling
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn respond(concurrency: &Concurrency, latency: Duration, outcome: Outcome) {
+        let epoch = concurrency.state.lock().unwrap().epoch;
+        concurrency.record(epoch, latency, outcome);
+    }
+
+    fn limit(concurrency: &Concurrency) -> usize {
+        concurrency.state.lock().unwrap().limit
+    }
+
+    #[test]
+    fn grows_by_one_per_round_of_healthy_responses_up_to_the_ceiling() {
+        let concurrency = Concurrency::new(8, true);
+        assert_eq!(limit(&concurrency), 2);
+        for _ in 0..2 {
+            respond(&concurrency, Duration::from_millis(100), Outcome::Responded);
+        }
+        assert_eq!(limit(&concurrency), 3);
+        // Slow responses hold the limit.
+        for _ in 0..10 {
+            respond(&concurrency, Duration::from_millis(400), Outcome::Responded);
+        }
+        assert_eq!(limit(&concurrency), 3);
+        for _ in 0..100 {
+            respond(&concurrency, Duration::from_millis(120), Outcome::Responded);
+        }
+        assert_eq!(limit(&concurrency), 8);
+    }
+
+    #[test]
+    fn an_outlier_fast_response_does_not_stall_growth() {
+        let concurrency = Concurrency::new(40, true);
+        // A tiny (or cached) record, then ordinary ones at 50-150x its time.
+        respond(&concurrency, Duration::from_millis(2), Outcome::Responded);
+        for i in 0..1000 {
+            let latency = Duration::from_millis(100 + (i % 5) * 50);
+            respond(&concurrency, latency, Outcome::Responded);
+        }
+        assert_eq!(limit(&concurrency), 40);
+    }
+
+    #[test]
+    fn halves_once_per_burst_of_failures() {
+        let concurrency = Concurrency::new(16, true);
+        for _ in 0..200 {
+            respond(&concurrency, Duration::from_millis(100), Outcome::Responded);
+        }
+        assert_eq!(limit(&concurrency), 16);
+
+        // Requests in flight when the endpoint started failing.
+        let epoch = concurrency.state.lock().unwrap().epoch;
+        for _ in 0..5 {
+            concurrency.record(epoch, Duration::from_secs(120), Outcome::Overloaded);
+        }
+        assert_eq!(limit(&concurrency), 8);
+        respond(&concurrency, Duration::from_secs(120), Outcome::Overloaded);
+        assert_eq!(limit(&concurrency), 4);
+
+        assert_eq!(
+            concurrency.summary().to_string(),
+            "adaptive, ended at 4 (range 4-16, ceiling 16)"
+        );
+    }
+
+    #[test]
+    fn fixed_concurrency_ignores_outcomes() {
+        let concurrency = Concurrency::new(10, false);
+        respond(&concurrency, Duration::from_secs(120), Outcome::Overloaded);
+        assert_eq!(limit(&concurrency), 10);
+        assert_eq!(concurrency.summary().to_string(), "10");
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_a_free_slot() {
+        let concurrency = Concurrency::new(1, false);
+        let permit = concurrency.acquire().await;
+        let waiting = concurrency.acquire();
+        tokio::pin!(waiting);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), &mut waiting)
+                .await
+                .is_err()
+        );
+        drop(permit);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("the slot is freed");
+    }
+}
@@ -1,108 +1,358 @@
-use std::path::PathBuf;
+use std::path::Path;
 use std::time::Duration;
 
 use futures::stream::{self, StreamExt};
-use oai_pmh::client::response::GetRecordResponse;
-use oai_pmh::{Client, GetRecordArgs};
-use tokio::fs;
-use tokio::time::timeout;
 use tracing::warn;
 
-use crate::harvester::{BatchStats, CONCURRENT_DOWNLOADS};
-use crate::oai::{HarvestEvent, OaiRecord, OaiRecordStatus};
+use crate::db::history;
+use crate::harvester::BatchStats;
+use crate::oai::{
+    FailureCategory, HarvestEvent, OaiRecord, OaiRecordStatus, PayloadFormat, PayloadSize,
+    ProtocolError,
+};
+use crate::payload;
 
 use super::Harvester;
+use super::concurrency::Outcome;
+use super::http::FetchError;
+use super::validate;
+
+/// Base wait before retrying a transient download failure.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest wait before a retry, however many attempts came before.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
 
 pub(super) async fn run(harvester: &Harvester) -> anyhow::Result<super::BatchStats> {
-    let client = Client::new(&harvester.config.scope.endpoint)?;
     harvester
         .batched(OaiRecordStatus::Pending, "Downloaded", async |batch| {
-            process_batch(&client, harvester, batch).await
+            process_batch(harvester, batch).await
         })
         .await
 }
 
-async fn process_batch(
-    client: &Client,
-    harvester: &Harvester,
-    records: &[OaiRecord],
-) -> BatchStats {
+async fn process_batch(harvester: &Harvester, records: &[OaiRecord]) -> BatchStats {
     let results: Vec<_> = stream::iter(records)
-        .map(|record| process_record(client, harvester, record))
-        .buffer_unordered(CONCURRENT_DOWNLOADS)
+        .map(|record| process_record(harvester, record))
+        .buffer_unordered(harvester.config.concurrency)
         .collect()
         .await;
 
-    BatchStats::from_results(results)
+    BatchStats::from_downloads(results)
+}
+
+/// How a pending record left the download step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Downloaded {
+    /// Its transition (stored or failed) was applied.
+    Applied,
+    /// The payload hashed the same as the stored one; the record went back to
+    /// its prior status.
+    Unchanged,
+    /// The record was no longer pending, or the transition errored.
+    NotApplied,
+}
+
+impl Downloaded {
+    fn from_applied(applied: bool) -> Self {
+        if applied {
+            Self::Applied
+        } else {
+            Self::NotApplied
+        }
+    }
+}
+
+/// A failed download, classified so transient failures can be retried.
+#[derive(Debug)]
+struct DownloadError {
+    category: FailureCategory,
+    message: String,
 }
 
-async fn process_record(
-    client: &Client,
+impl DownloadError {
+    fn transient(message: String) -> Self {
+        Self {
+            category: FailureCategory::Transient,
+            message,
+        }
+    }
+
+    fn permanent(message: String) -> Self {
+        Self {
+            category: FailureCategory::Permanent,
+            message,
+        }
+    }
+
+    fn invalid(message: String) -> Self {
+        Self {
+            category: FailureCategory::Invalid,
+            message,
+        }
+    }
+
+    fn oversize(message: String) -> Self {
+        Self {
+            category: FailureCategory::Oversize,
+            message,
+        }
+    }
+}
+
+impl From<FetchError> for DownloadError {
+    fn from(error: FetchError) -> Self {
+        Self {
+            category: error.category(),
+            message: format!("Failed to fetch OAI record: {error}"),
+        }
+    }
+}
+
+async fn process_record(harvester: &Harvester, record: &OaiRecord) -> anyhow::Result<Downloaded> {
+    match fetch_with_retries(harvester, record).await {
+        Ok((metadata, about)) => store(harvester, record, &metadata, &about).await,
+        Err(error) => fail(harvester, record, &error).await,
+    }
+}
+
+/// Validate a fetched payload, write it to storage (in the configured
+/// `payload_format`) and move the record on from pending: back to its prior
+/// status when the payload is unchanged, otherwise to available. `about` (the
+/// record's `<about>` containers) and the payload's size are saved on the
+/// record either way. An oversize or invalid payload fails the record and
+/// leaves the stored one as it was.
+pub(super) async fn store(
     harvester: &Harvester,
     record: &OaiRecord,
-) -> anyhow::Result<bool> {
-    let outcome = fetch_and_write(client, harvester, record).await;
-    let event = match &outcome {
-        Ok(()) => HarvestEvent::DownloadSucceeded,
-        Err(message) => HarvestEvent::DownloadFailed { message },
+    metadata: &str,
+    about: &[String],
+) -> anyhow::Result<Downloaded> {
+    let size = match validate(harvester, metadata).await {
+        Ok(size) => size,
+        Err(error) => return fail(harvester, record, &error).await,
+    };
+
+    let payload_format = harvester.config.payload_format;
+    let key = record.path_as(payload_format);
+    if let Err(e) = write_payload(harvester, &key, payload_format, metadata).await {
+        let error = DownloadError::transient(format!("Failed to write metadata file: {}", e));
+        return fail(harvester, record, &error).await;
+    }
+
+    let content_hash = payload::content_hash(metadata);
+    let content_hash = content_hash.as_str();
+    let unchanged = HarvestEvent::DownloadUnchanged {
+        content_hash,
+        payload_format,
+        about,
+        size,
+    };
+    let downloaded = if harvester.update(record, &unchanged).await? {
+        Downloaded::Unchanged
+    } else {
+        let succeeded = HarvestEvent::DownloadSucceeded {
+            content_hash,
+            payload_format,
+            about,
+            size,
+        };
+        let applied = harvester.update(record, &succeeded).await?;
+        if applied && harvester.config.keep_versions > 0 {
+            // History is best effort: the record is stored either way.
+            if let Err(e) = keep_version(harvester, record, metadata, content_hash).await {
+                warn!(
+                    "Failed to keep payload version of {}: {e}",
+                    record.identifier
+                );
+            }
+        }
+        Downloaded::from_applied(applied)
+    };
+
+    // The payload was stored in a different format before: drop the old file.
+    if downloaded != Downloaded::NotApplied && record.payload_format != payload_format {
+        remove_payload(harvester, &record.path()).await;
+    }
+    Ok(downloaded)
+}
+
+/// Check that a payload is within `max_payload_bytes`, well-formed and, with
+/// a configured schema, valid, and measure it.
+async fn validate(harvester: &Harvester, metadata: &str) -> Result<PayloadSize, DownloadError> {
+    let bytes = metadata.len() as u64;
+    if let Some(limit) = harvester.config.max_payload_bytes
+        && bytes > limit
+    {
+        return Err(DownloadError::oversize(format!(
+            "Payload is {bytes} bytes, over the --max-payload-bytes limit of {limit}"
+        )));
+    }
+    let components = validate::check_well_formed(metadata)
+        .map_err(|e| DownloadError::invalid(format!("Payload is not well-formed XML at {e}")))?;
+    let size = PayloadSize {
+        bytes: bytes as i64,
+        components: i32::try_from(components).unwrap_or(i32::MAX),
+    };
+    let Some(schema) = &harvester.config.schema else {
+        return Ok(size);
     };
-    harvester.update(record, &event).await
+    match validate::check_schema(schema, metadata).await {
+        Ok(Ok(())) => Ok(size),
+        Ok(Err(e)) => Err(DownloadError::invalid(format!(
+            "Payload is not valid against {} at {e}",
+            schema.display()
+        ))),
+        Err(e) => Err(DownloadError::transient(format!(
+            "Failed to validate payload: {e}"
+        ))),
+    }
 }
 
-async fn fetch_and_write(
-    client: &Client,
+/// Copy a newly stored payload into the record's version history, then drop
+/// versions beyond `keep_versions`.
+async fn keep_version(
     harvester: &Harvester,
     record: &OaiRecord,
-) -> Result<(), String> {
-    let response = fetch_with_retries(client, harvester, record).await?;
+    metadata: &str,
+    content_hash: &str,
+) -> anyhow::Result<()> {
+    let payload_format = harvester.config.payload_format;
+    let version =
+        history::store_version(&harvester.pool, record.id, content_hash, payload_format).await?;
+    let key = record.version_path(version, payload_format);
+    write_payload(harvester, &key, payload_format, metadata).await?;
 
-    let payload = response
-        .payload
-        .ok_or_else(|| "OAI response missing payload".to_string())?;
+    let pruned = history::prune(&harvester.pool, record.id, harvester.config.keep_versions).await?;
+    for (version, format) in pruned {
+        remove_payload(harvester, &record.version_path(version, format)).await;
+    }
+    Ok(())
+}
 
-    let path = harvester.config.data_dir.join(record.path());
-    write_metadata_to_file(path, &payload.record.metadata)
-        .await
-        .map_err(|e| format!("Failed to write metadata file: {}", e))
+/// Remove a payload that is no longer referenced. A missing payload is fine.
+async fn remove_payload(harvester: &Harvester, key: &Path) {
+    if let Err(e) = harvester.storage.delete(key).await {
+        warn!("Failed to remove {}: {e}", harvester.storage.describe(key));
+    }
+}
+
+async fn fail(
+    harvester: &Harvester,
+    record: &OaiRecord,
+    error: &DownloadError,
+) -> anyhow::Result<Downloaded> {
+    let event = HarvestEvent::DownloadFailed {
+        message: &error.message,
+        category: error.category,
+    };
+    Ok(Downloaded::from_applied(
+        harvester.update(record, &event).await?,
+    ))
 }
 
+/// Fetch a record's metadata and `<about>` containers, retrying transient
+/// failures up to `oai_retries` times with jittered exponential backoff.
+/// Throttling is waited out inside `OaiHttp` and does not count as an attempt.
 async fn fetch_with_retries(
-    client: &Client,
     harvester: &Harvester,
     record: &OaiRecord,
-) -> Result<GetRecordResponse, String> {
-    let duration = Duration::from_secs(harvester.config.oai_timeout);
+) -> Result<(String, Vec<String>), DownloadError> {
     let max_retries = harvester.config.oai_retries;
     let mut attempts = 0u32;
 
     loop {
-        let args = GetRecordArgs::new(&record.identifier, &harvester.config.scope.metadata_prefix);
-        match timeout(duration, client.get_record(args)).await {
-            Ok(Ok(response)) => return Ok(response),
-            Ok(Err(error)) => return Err(format!("Failed to fetch OAI record: {}", error)),
-            Err(_) if attempts < max_retries => {
-                attempts += 1;
-                warn!(
-                    "OAI get_record timed out for {}, retry {}/{}",
-                    record.identifier, attempts, max_retries
-                );
-                tokio::time::sleep(Duration::from_millis(500 * 2u64.pow(attempts - 1))).await;
-            }
-            Err(_) => {
-                return Err(format!(
-                    "OAI get_record timed out after {}s",
-                    harvester.config.oai_timeout
-                ));
-            }
+        let error = match fetch_metadata(harvester, record).await {
+            Ok(fetched) => return Ok(fetched),
+            Err(error) => error,
+        };
+        if error.category == FailureCategory::Permanent || attempts >= max_retries {
+            return Err(error);
         }
+        attempts += 1;
+        warn!(
+            "{} ({}), retry {}/{}",
+            error.message, record.identifier, attempts, max_retries
+        );
+        tokio::time::sleep(retry_backoff(attempts)).await;
     }
 }
 
-async fn write_metadata_to_file(path: PathBuf, metadata: &str) -> anyhow::Result<()> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).await?;
+async fn fetch_metadata(
+    harvester: &Harvester,
+    record: &OaiRecord,
+) -> Result<(String, Vec<String>), DownloadError> {
+    // The process-wide slot first, so waiting for it does not count as
+    // endpoint latency.
+    let _download = match &harvester.downloads {
+        Some(downloads) => Some(
+            downloads
+                .acquire()
+                .await
+                .expect("the download semaphore is never closed"),
+        ),
+        None => None,
+    };
+    let permit = harvester.concurrency.acquire().await;
+    let result = harvester
+        .http
+        .get_record(&record.identifier, &harvester.config.scope.metadata_prefix)
+        .await;
+    permit.finish(match &result {
+        Err(error) if error.is_overload() => Outcome::Overloaded,
+        _ => Outcome::Responded,
+    });
+    let (response, about) = result?;
+
+    if let Some(error) = &response.error {
+        return Err(DownloadError::permanent(
+            ProtocolError::from(error).to_string(),
+        ));
     }
+    let payload = response
+        .payload
+        .ok_or_else(|| DownloadError::transient("OAI response missing payload".to_string()))?;
+    Ok((payload.record.metadata, about))
+}
 
-    fs::write(&path, metadata).await?;
-    Ok(())
+/// `RETRY_BACKOFF * 2^(attempt - 1)`, scaled by a random factor in
+/// `[0.5, 1.5)` so concurrent downloads that failed together do not retry in
+/// lockstep, and capped at `MAX_RETRY_BACKOFF`.
+fn retry_backoff(attempt: u32) -> Duration {
+    RETRY_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(MAX_RETRY_BACKOFF)
+        .mul_f64(rand::random_range(0.5..1.5))
+        .min(MAX_RETRY_BACKOFF)
+}
+
+async fn write_payload(
+    harvester: &Harvester,
+    key: &Path,
+    format: PayloadFormat,
+    metadata: &str,
+) -> anyhow::Result<()> {
+    let bytes = payload::encode(format, metadata)?.into_owned();
+    harvester.storage.put(key, bytes).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_backoff_doubles_with_jitter() {
+        for attempt in 1..=4 {
+            let base = RETRY_BACKOFF * 2u32.pow(attempt - 1);
+            let wait = retry_backoff(attempt);
+            assert!(
+                wait >= base / 2 && wait < base * 3 / 2,
+                "{wait:?} for {attempt}"
+            );
+        }
+        for attempt in [8, 32, u32::MAX] {
+            assert!(retry_backoff(attempt) <= MAX_RETRY_BACKOFF);
+        }
+        assert!(retry_backoff(u32::MAX) >= MAX_RETRY_BACKOFF / 2);
+    }
 }
@@ -0,0 +1,447 @@
+//! Structured rules files (`--rules rules.toml`): typed fields, each single-
+//! or multi-valued, with an optional default and description, so extracted
+//! metadata has the same shape for every record.
+//!
+//! ```toml
+//! skip = ["dsc"]
+//!
+//! [namespaces]
+//! xlink = "http://www.w3.org/1999/xlink"
+//!
+//! [fields.title]
+//! path = "/ead/archdesc/did/unittitle"
+//! required = true
+//! description = "Collection title"
+//!
+//! [fields.dates]
+//! path = ["unitdate@normal", "unitdate"]
+//! type = "date"
+//! multiple = true
+//!
+//! [fields.subjects]
+//! path = "controlaccess/*"
+//! multiple = true
+//! properties = { type = "name()", value = "text()", source = "@source" }
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use anyhow::Context;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::rules::{self, Rule, RuleSet, Selector};
+use super::transform::{self, Transform};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    /// Replaces the default `dsc`, like `@skip`.
+    skip: Option<HashSet<String>>,
+    /// Prefixes the paths use, like `@ns`.
+    #[serde(default)]
+    namespaces: HashMap<String, String>,
+    #[serde(default)]
+    fields: BTreeMap<String, FieldSpec>,
+}
+
+/// One `[fields.<name>]` entry.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldSpec {
+    path: Paths,
+    #[serde(rename = "type", default)]
+    kind: FieldType,
+    #[serde(default)]
+    multiple: bool,
+    #[serde(default)]
+    required: bool,
+    default: Option<toml::Value>,
+    description: Option<String>,
+    #[serde(default)]
+    transforms: Vec<String>,
+    /// For grouped fields: property name -> `text()`, `name()` or `@attr`.
+    properties: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Paths {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// What a field's values are, in the extracted metadata.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    #[default]
+    String,
+    /// A whole number; values that do not parse are dropped.
+    Integer,
+    /// An ISO 8601 date or `start/end` range, as the `date` transform makes
+    /// them; values that are not recognizable dates are dropped.
+    Date,
+    /// `true`/`yes`/`y`/`1` or `false`/`no`/`n`/`0`; other values are
+    /// dropped.
+    Boolean,
+}
+
+impl FieldType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Date => "date",
+            Self::Boolean => "boolean",
+        }
+    }
+
+    /// An extracted value as this type, if it is one.
+    pub fn convert(self, value: &str) -> Option<Value> {
+        match self {
+            Self::String => Some(Value::String(value.to_string())),
+            Self::Integer => value.trim().parse::<i64>().ok().map(Value::from),
+            Self::Date => transform::iso_date(value).map(Value::String),
+            Self::Boolean => match value.trim().to_lowercase().as_str() {
+                "true" | "yes" | "y" | "1" => Some(Value::Bool(true)),
+                "false" | "no" | "n" | "0" => Some(Value::Bool(false)),
+                _ => None,
+            },
+        }
+    }
+
+    /// A default value from the rules file as this type, if it is one.
+    fn convert_default(self, value: toml::Value) -> Option<Value> {
+        match (self, value) {
+            (Self::String, toml::Value::String(value)) => Some(Value::String(value)),
+            (Self::Integer, toml::Value::Integer(value)) => Some(Value::from(value)),
+            (Self::Boolean, toml::Value::Boolean(value)) => Some(Value::Bool(value)),
+            (Self::Date, toml::Value::String(value)) => self.convert(&value),
+            (Self::Date, toml::Value::Datetime(value)) => self.convert(&value.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A declared field: always present in the extracted metadata, as one value
+/// (or `null`) or an array of them.
+#[derive(Debug)]
+pub struct Field {
+    pub name: String,
+    pub kind: FieldType,
+    pub multiple: bool,
+    pub required: bool,
+    /// Used when the record has no values, already in the field's type and
+    /// cardinality.
+    pub default: Option<Value>,
+    pub description: Option<String>,
+}
+
+impl Field {
+    /// The field's output from the values its rules extracted: strings are
+    /// converted to its type (dropping those that do not convert) and
+    /// objects (from grouped fields) kept as they are.
+    pub fn shape(&self, values: Vec<Value>) -> anyhow::Result<Value> {
+        let mut values = values.into_iter().filter_map(|value| match value {
+            Value::String(value) => self.kind.convert(&value),
+            value => Some(value),
+        });
+        let shaped = if self.multiple {
+            let values: Vec<_> = values.collect();
+            (!values.is_empty()).then_some(Value::Array(values))
+        } else {
+            values.next()
+        };
+
+        match shaped.or_else(|| self.default.clone()) {
+            Some(value) => Ok(value),
+            None if self.required => match &self.description {
+                Some(description) => {
+                    anyhow::bail!("Required field '{}' ({description}) is empty", self.name)
+                }
+                None => anyhow::bail!("Required field '{}' is empty", self.name),
+            },
+            None if self.multiple => Ok(Value::Array(Vec::new())),
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+/// Parse a TOML rules file into its fields and the rules extracting them.
+pub(super) fn parse(text: &str) -> anyhow::Result<RuleSet> {
+    let file: RulesFile = toml::from_str(text)?;
+    if file.fields.is_empty() {
+        anyhow::bail!("no [fields] entries");
+    }
+
+    let mut rules = Vec::new();
+    let mut fields = Vec::new();
+    for (name, spec) in file.fields {
+        let (field, field_rules) = spec
+            .build(name.clone(), &file.namespaces)
+            .with_context(|| format!("Invalid field '{name}'"))?;
+        fields.push(field);
+        rules.extend(field_rules);
+    }
+    Ok(RuleSet::build(rules, file.skip, Some(fields)))
+}
+
+impl FieldSpec {
+    fn build(
+        self,
+        name: String,
+        namespaces: &HashMap<String, String>,
+    ) -> anyhow::Result<(Field, Vec<Rule>)> {
+        let transforms = self
+            .transforms
+            .iter()
+            .map(|spec| Transform::parse(spec))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let group = match self.properties {
+            Some(properties) => Some(
+                properties
+                    .into_iter()
+                    .map(|(key, spec)| Ok((key, selector(&spec, namespaces)?)))
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            ),
+            None => None,
+        };
+        if group.is_some() {
+            if self.kind != FieldType::String {
+                anyhow::bail!("grouped fields hold objects, so take no type");
+            }
+            if !transforms.is_empty() || self.default.is_some() {
+                anyhow::bail!("grouped fields take no transforms or default");
+            }
+        }
+
+        let paths = match self.path {
+            Paths::One(path) => vec![path],
+            Paths::Many(paths) => paths,
+        };
+        if paths.is_empty() {
+            anyhow::bail!("no path");
+        }
+        let rules = paths
+            .iter()
+            .map(|path| {
+                // Required is checked per field, once its values are typed.
+                let mut rule = Rule::new(name.clone(), path, false, namespaces)?;
+                if group.is_some() && rule.attribute.is_some() {
+                    anyhow::bail!("a grouped field's path must end at an element");
+                }
+                rule.transforms = transforms.clone();
+                rule.group = group.clone();
+                Ok(rule)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let default = match self.default {
+            Some(default) => Some(
+                typed_default(self.kind, self.multiple, default).with_context(|| {
+                    let shape = if self.multiple { "an array of " } else { "" };
+                    format!("default must be {shape}{} value(s)", self.kind)
+                })?,
+            ),
+            None => None,
+        };
+
+        let field = Field {
+            name,
+            kind: self.kind,
+            multiple: self.multiple,
+            required: self.required,
+            default,
+            description: self.description,
+        };
+        Ok((field, rules))
+    }
+}
+
+/// A default in the field's type and cardinality, if it is one.
+fn typed_default(kind: FieldType, multiple: bool, default: toml::Value) -> Option<Value> {
+    match (multiple, default) {
+        (true, toml::Value::Array(items)) => items
+            .into_iter()
+            .map(|item| kind.convert_default(item))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array),
+        (true, _) => None,
+        (false, default) => kind.convert_default(default),
+    }
+}
+
+/// A group property: `text()`, `name()` or `@attr`.
+fn selector(spec: &str, namespaces: &HashMap<String, String>) -> anyhow::Result<Selector> {
+    match spec.trim() {
+        "text()" => Ok(Selector::Text),
+        "name()" => Ok(Selector::Name),
+        spec => match spec.strip_prefix('@').map(str::trim) {
+            Some(name) if !name.is_empty() => Ok(Selector::Attribute(rules::attribute_key(
+                name.to_string(),
+                namespaces,
+            ))),
+            _ => anyhow::bail!("invalid property '{spec}': expected text(), name() or @attr"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn field(kind: FieldType, multiple: bool, required: bool) -> Field {
+        Field {
+            name: "f".to_string(),
+            kind,
+            multiple,
+            required,
+            default: None,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn converts_values_to_the_field_type() {
+        assert_eq!(FieldType::Integer.convert(" 42 "), Some(json!(42)));
+        assert_eq!(FieldType::Integer.convert("4.2"), None);
+        assert_eq!(
+            FieldType::Date.convert("1950-1960"),
+            Some(json!("1950/1960"))
+        );
+        assert_eq!(FieldType::Date.convert("undated"), None);
+        assert_eq!(FieldType::Boolean.convert("Yes"), Some(json!(true)));
+        assert_eq!(FieldType::Boolean.convert("0"), Some(json!(false)));
+        assert_eq!(FieldType::Boolean.convert("maybe"), None);
+    }
+
+    #[test]
+    fn shapes_values_by_cardinality() {
+        let values = || vec![json!("3"), json!("x"), json!("5")];
+        assert_eq!(
+            field(FieldType::Integer, true, false)
+                .shape(values())
+                .unwrap(),
+            json!([3, 5])
+        );
+        assert_eq!(
+            field(FieldType::Integer, false, false)
+                .shape(values())
+                .unwrap(),
+            json!(3)
+        );
+        assert_eq!(
+            field(FieldType::String, true, false).shape(vec![]).unwrap(),
+            json!([])
+        );
+        assert_eq!(
+            field(FieldType::String, false, false)
+                .shape(vec![])
+                .unwrap(),
+            Value::Null
+        );
+
+        let mut with_default = field(FieldType::Boolean, false, true);
+        with_default.default = Some(json!(false));
+        assert_eq!(
+            with_default.shape(vec![json!("n/a")]).unwrap(),
+            json!(false)
+        );
+
+        let mut required = field(FieldType::Date, false, true);
+        required.description = Some("Collection dates".to_string());
+        assert_eq!(
+            required
+                .shape(vec![json!("undated")])
+                .unwrap_err()
+                .to_string(),
+            "Required field 'f' (Collection dates) is empty"
+        );
+    }
+
+    #[test]
+    fn parses_fields_into_rules() {
+        let toml = r#"
+skip = []
+
+[namespaces]
+xlink = "http://www.w3.org/1999/xlink"
+
+[fields.title]
+path = "/ead/archdesc/did/unittitle"
+required = true
+description = "Collection title"
+
+[fields.dates]
+path = ["unitdate@normal", "unitdate"]
+type = "date"
+multiple = true
+default = ["1900"]
+
+[fields.links]
+path = "extref"
+multiple = true
+properties = { href = "@xlink:href", label = "text()" }
+"#;
+        let rules = parse(toml).unwrap();
+        assert!(!rules.skips("dsc"));
+        assert!(rules.uses_attribute("{http://www.w3.org/1999/xlink}href"));
+
+        let fields = rules.fields().unwrap();
+        let names: Vec<_> = fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, ["dates", "links", "title"]);
+        assert_eq!(fields[0].default, Some(json!(["1900"])));
+        assert!(fields[2].required);
+
+        let keys: Vec<_> = rules.rules().iter().map(|rule| rule.key.as_str()).collect();
+        assert_eq!(keys, ["dates", "dates", "links", "title"]);
+        // Required is checked per field, not per rule.
+        assert_eq!(rules.required().count(), 0);
+        assert_eq!(
+            rules.rules()[2].group,
+            Some(vec![
+                (
+                    "href".to_string(),
+                    Selector::Attribute("{http://www.w3.org/1999/xlink}href".to_string())
+                ),
+                ("label".to_string(), Selector::Text),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        let error = |toml: &str| format!("{:#}", parse(toml).unwrap_err());
+
+        assert!(error("").contains("no [fields] entries"));
+        assert!(error("[fields.a]\npath = \"x\"\ncolor = \"red\"\n").contains("unknown field"));
+        assert!(error("[fields.a]\npath = \"x\"\ntype = \"float\"\n").contains("unknown variant"));
+        assert_eq!(
+            error("[fields.a]\npath = \"x\"\ntype = \"integer\"\ndefault = \"ten\"\n"),
+            "Invalid field 'a': default must be integer value(s)"
+        );
+        assert_eq!(
+            error("[fields.a]\npath = \"x\"\nmultiple = true\ndefault = \"x\"\n"),
+            "Invalid field 'a': default must be an array of string value(s)"
+        );
+        assert!(
+            error("[fields.a]\npath = \"x\"\ntype = \"date\"\nproperties = { v = \"text()\" }\n")
+                .contains("grouped fields hold objects")
+        );
+        assert!(
+            error("[fields.a]\npath = \"x\"\nproperties = { v = \"value()\" }\n")
+                .contains("invalid property 'value()'")
+        );
+        assert!(error("[fields.a]\npath = \"dc:title\"\n").contains("not declared"));
+    }
+}
@@ -0,0 +1,472 @@
+//! The HTTP side of a harvest. Every OAI request for a scope goes through one
+//! `OaiHttp`, which paces requests to the configured rate and waits out
+//! `503`/`429` responses (honouring `Retry-After`) instead of failing them.
+//! Pacing is per endpoint: scopes harvesting the same endpoint at once (sets
+//! or prefixes in a sources file) share one `Limiter` from the run's
+//! `Limiters`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use oai_pmh::client::response::{GetRecordResponse, IdentifyResponse};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Client, StatusCode, Url};
+use tokio::time::{Instant, sleep_until, timeout};
+use tracing::warn;
+
+use crate::oai::FailureCategory;
+
+use super::provenance;
+
+/// Throttled responses retried per request before giving up.
+const MAX_THROTTLE_RETRIES: u32 = 5;
+
+/// Wait after a throttled response without a usable `Retry-After`, doubled on
+/// each further throttle of the same request.
+const THROTTLE_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Upper bound on a single `Retry-After` wait.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(600);
+
+/// Longest response body excerpt carried in an HTTP error message.
+const ERROR_BODY_CHARS: usize = 200;
+
+#[derive(Debug)]
+pub(super) enum FetchError {
+    /// No complete response within the OAI timeout.
+    Timeout { verb: &'static str, seconds: u64 },
+    /// Still throttled (`503`/`429`) after `MAX_THROTTLE_RETRIES` waits.
+    Throttled { status: StatusCode },
+    /// Any other non-success status.
+    Status { status: StatusCode, excerpt: String },
+    /// Connection, TLS or body read failure.
+    Transport(reqwest::Error),
+    /// The response was not a parseable OAI-PMH document.
+    Parse(oai_pmh::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout { verb, seconds } => {
+                write!(f, "OAI {verb} timed out after {seconds}s")
+            }
+            Self::Throttled { status } => write!(
+                f,
+                "HTTP {status}: still throttled after {MAX_THROTTLE_RETRIES} retries"
+            ),
+            Self::Status { status, excerpt } => write!(f, "HTTP {status}: {excerpt}"),
+            Self::Transport(error) => write!(f, "HTTP request failed: {error}"),
+            Self::Parse(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl FetchError {
+    /// Client errors (other than `429`) will not go away on their own;
+    /// everything else may succeed on a later attempt.
+    pub(super) fn category(&self) -> FailureCategory {
+        match self {
+            Self::Status { status, .. } if status.is_client_error() => FailureCategory::Permanent,
+            _ => FailureCategory::Transient,
+        }
+    }
+
+    /// Whether the failure suggests the endpoint is overloaded (so adaptive
+    /// concurrency backs off): timeouts, throttling, server errors and
+    /// connection failures.
+    pub(super) fn is_overload(&self) -> bool {
+        match self {
+            Self::Timeout { .. } | Self::Throttled { .. } | Self::Transport(_) => true,
+            Self::Status { status, .. } => status.is_server_error(),
+            Self::Parse(_) => false,
+        }
+    }
+}
+
+pub(super) struct OaiHttp {
+    client: Client,
+    endpoint: String,
+    timeout: Duration,
+    limiter: Arc<Limiter>,
+    throttled: AtomicUsize,
+}
+
+impl OaiHttp {
+    pub(super) fn new(
+        endpoint: &str,
+        timeout: Duration,
+        requests_per_second: Option<f64>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::new(),
+            endpoint: endpoint.to_string(),
+            timeout,
+            limiter: Arc::new(Limiter::new(
+                requests_per_second.map(request_interval).transpose()?,
+            )),
+            throttled: AtomicUsize::new(0),
+        })
+    }
+
+    /// Pace requests together with the other scopes on this endpoint in
+    /// `limiters`, instead of on their own.
+    pub(super) fn share_limiter(&mut self, limiters: &Limiters) {
+        let interval = self.limiter.state.lock().unwrap().interval;
+        self.limiter = limiters.for_endpoint(&self.endpoint, interval);
+    }
+
+    /// Throttled (`503`/`429`) responses received so far.
+    pub(super) fn throttled(&self) -> usize {
+        self.throttled.load(Ordering::Relaxed)
+    }
+
+    pub(super) async fn identify(&self) -> Result<IdentifyResponse, FetchError> {
+        let xml = self.get("Identify", &[]).await?;
+        IdentifyResponse::new(&xml).map_err(FetchError::Parse)
+    }
+
+    /// Fetch a record, with the inner XML of each of its `<about>`
+    /// containers.
+    pub(super) async fn get_record(
+        &self,
+        identifier: &str,
+        metadata_prefix: &str,
+    ) -> Result<(GetRecordResponse, Vec<String>), FetchError> {
+        let xml = self
+            .get(
+                "GetRecord",
+                &[
+                    ("identifier", identifier),
+                    ("metadataPrefix", metadata_prefix),
+                ],
+            )
+            .await?;
+        let (response, about) = parse_with_about(&xml, GetRecordResponse::new)?;
+        Ok((response, about.into_iter().next().unwrap_or_default()))
+    }
+
+    /// Send `verb` with `args`, returning the response body. Throttled
+    /// responses are retried after their `Retry-After` (or a doubling
+    /// backoff), which holds every other request to the endpoint too; the
+    /// timeout applies to each attempt, not to the waits.
+    pub(super) async fn get(
+        &self,
+        verb: &'static str,
+        args: &[(&str, &str)],
+    ) -> Result<String, FetchError> {
+        let url = request_url(&self.endpoint, verb, args)?;
+        let mut throttles = 0;
+
+        loop {
+            self.limiter.pace().await;
+            let attempt = async {
+                let response = self.client.get(url.clone()).send().await?;
+                let status = response.status();
+                let headers = response.headers().clone();
+                let body = response.text().await?;
+                Ok::<_, reqwest::Error>((status, headers, body))
+            };
+            let (status, headers, body) = timeout(self.timeout, attempt)
+                .await
+                .map_err(|_| FetchError::Timeout {
+                    verb,
+                    seconds: self.timeout.as_secs(),
+                })?
+                .map_err(FetchError::Transport)?;
+
+            if status.is_success() {
+                return Ok(body);
+            }
+            if !is_throttle(status) {
+                let excerpt = body.chars().take(ERROR_BODY_CHARS).collect();
+                return Err(FetchError::Status { status, excerpt });
+            }
+
+            self.throttled.fetch_add(1, Ordering::Relaxed);
+            if throttles >= MAX_THROTTLE_RETRIES {
+                return Err(FetchError::Throttled { status });
+            }
+            let wait = retry_after(&headers, Utc::now())
+                .unwrap_or(THROTTLE_BACKOFF * 2u32.pow(throttles))
+                .min(MAX_RETRY_AFTER);
+            throttles += 1;
+            warn!(
+                "OAI {verb} throttled ({status}), retry {throttles}/{MAX_THROTTLE_RETRIES} in {}s",
+                wait.as_secs()
+            );
+            let resume_at = Instant::now() + wait;
+            self.limiter.hold_until(resume_at);
+            // `pace` holds this request too; the sleep is a fallback so its
+            // retry never goes out early.
+            sleep_until(resume_at).await;
+        }
+    }
+}
+
+/// The limiters of the endpoints harvested in one run (a sources file), by
+/// normalized endpoint.
+#[derive(Default)]
+pub(super) struct Limiters(Mutex<HashMap<String, Arc<Limiter>>>);
+
+impl Limiters {
+    /// The limiter for `endpoint`, created on first use. When scopes ask for
+    /// different rates, the slowest applies.
+    fn for_endpoint(&self, endpoint: &str, interval: Option<Duration>) -> Arc<Limiter> {
+        let mut limiters = self.0.lock().unwrap();
+        let limiter = limiters
+            .entry(normalize_endpoint(endpoint))
+            .or_insert_with(|| Arc::new(Limiter::new(interval)));
+        let mut state = limiter.state.lock().unwrap();
+        state.interval = state.interval.max(interval);
+        drop(state);
+        limiter.clone()
+    }
+}
+
+/// Paces the requests to one endpoint, across every `OaiHttp` sharing it.
+struct Limiter {
+    state: Mutex<LimiterState>,
+}
+
+struct LimiterState {
+    /// Minimum spacing between requests; `None` sends as fast as callers ask.
+    interval: Option<Duration>,
+    /// When the next request may go out: paced by `interval`, and pushed
+    /// back by throttled responses so every request waits them out.
+    next_request_at: Instant,
+}
+
+impl Limiter {
+    fn new(interval: Option<Duration>) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                interval,
+                next_request_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait for this request's slot under the rate limit and any throttle
+    /// hold.
+    async fn pace(&self) {
+        let slot = {
+            let mut state = self.state.lock().unwrap();
+            let slot = state.next_request_at.max(Instant::now());
+            state.next_request_at = slot + state.interval.unwrap_or_default();
+            slot
+        };
+        sleep_until(slot).await;
+    }
+
+    /// Hold every request to the endpoint until `resume_at`.
+    fn hold_until(&self, resume_at: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.next_request_at = state.next_request_at.max(resume_at);
+    }
+}
+
+/// The endpoint's URL without its query or trailing slashes, so spellings
+/// of the same server (`Url` also lowercases the host and drops a default
+/// port) share a limiter.
+fn normalize_endpoint(endpoint: &str) -> String {
+    match Url::parse(endpoint) {
+        Ok(mut url) => {
+            url.set_query(None);
+            url.set_fragment(None);
+            url.as_str().trim_end_matches('/').to_string()
+        }
+        Err(_) => endpoint.trim_end_matches('/').to_string(),
+    }
+}
+
+/// Parse a response whose records may carry `<about>` containers, returning
+/// them (per record, in document order) alongside it.
+pub(super) fn parse_with_about<R>(
+    xml: &str,
+    parse: impl FnOnce(&str) -> oai_pmh::Result<R>,
+) -> Result<(R, Vec<Vec<String>>), FetchError> {
+    match provenance::strip_about(xml) {
+        Ok(stripped) => {
+            let response = parse(&stripped.xml).map_err(FetchError::Parse)?;
+            Ok((response, stripped.about))
+        }
+        // Not well-formed XML: leave the OAI parser to report it.
+        Err(_) => Ok((parse(xml).map_err(FetchError::Parse)?, Vec::new())),
+    }
+}
+
+/// The spacing between requests under a `requests_per_second` limit, which
+/// must be positive and finite (and not so small the spacing overflows).
+pub(super) fn request_interval(rate: f64) -> anyhow::Result<Duration> {
+    if !rate.is_finite() || rate <= 0.0 {
+        anyhow::bail!("requests per second must be a positive number, not {rate}");
+    }
+    Duration::try_from_secs_f64(1.0 / rate)
+        .map_err(|_| anyhow::anyhow!("requests per second {rate} is too low"))
+}
+
+fn is_throttle(status: StatusCode) -> bool {
+    status == StatusCode::SERVICE_UNAVAILABLE || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// `Retry-After` as a wait from `now`: either delay-seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+fn request_url(endpoint: &str, verb: &str, args: &[(&str, &str)]) -> Result<Url, FetchError> {
+    let mut url =
+        Url::parse(endpoint).map_err(|error| FetchError::Parse(oai_pmh::Error::UrlParse(error)))?;
+    url.query_pairs_mut()
+        .append_pair("verb", verb)
+        .extend_pairs(args);
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap());
+        headers
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_http_dates() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 4, 5, 6, 0).unwrap();
+        assert_eq!(
+            retry_after(&headers("120"), now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            retry_after(&headers("Wed, 04 Mar 2026 05:06:30 GMT"), now),
+            Some(Duration::from_secs(30))
+        );
+        // A date already past means retry now.
+        assert_eq!(
+            retry_after(&headers("Wed, 04 Mar 2026 05:00:00 GMT"), now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&headers("soon"), now), None);
+        assert_eq!(retry_after(&HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn request_url_appends_encoded_arguments() {
+        let url = request_url(
+            "https://example.org/oai?repo=1",
+            "GetRecord",
+            &[("identifier", "oai:x/1"), ("metadataPrefix", "oai_ead")],
+        )
+        .unwrap();
+        assert_eq!(
+            url.query(),
+            Some("repo=1&verb=GetRecord&identifier=oai%3Ax%2F1&metadataPrefix=oai_ead")
+        );
+    }
+
+    #[test]
+    fn client_errors_are_permanent() {
+        let status = |status| FetchError::Status {
+            status,
+            excerpt: String::new(),
+        };
+        assert_eq!(
+            status(StatusCode::NOT_FOUND).category(),
+            FailureCategory::Permanent
+        );
+        assert_eq!(
+            status(StatusCode::BAD_GATEWAY).category(),
+            FailureCategory::Transient
+        );
+        assert_eq!(
+            FetchError::Throttled {
+                status: StatusCode::TOO_MANY_REQUESTS
+            }
+            .category(),
+            FailureCategory::Transient
+        );
+    }
+
+    #[test]
+    fn request_interval_rejects_unusable_rates() {
+        assert_eq!(request_interval(4.0).unwrap(), Duration::from_millis(250));
+        assert_eq!(request_interval(0.5).unwrap(), Duration::from_secs(2));
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e-30] {
+            assert!(request_interval(rate).is_err(), "{rate}");
+        }
+    }
+
+    #[tokio::test]
+    async fn pace_spaces_requests_to_the_rate_limit() {
+        let http = OaiHttp::new(
+            "https://pace.example.org/oai",
+            Duration::from_secs(5),
+            Some(100.0),
+        )
+        .unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            http.limiter.pace().await;
+        }
+        // The first request goes straight out; the next two wait 10ms each.
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn a_throttle_holds_every_request() {
+        let http =
+            OaiHttp::new("https://hold.example.org/oai", Duration::from_secs(5), None).unwrap();
+        let start = Instant::now();
+        http.limiter.hold_until(start + Duration::from_millis(30));
+        // An earlier hold never shortens a later one.
+        http.limiter.hold_until(start + Duration::from_millis(10));
+        http.limiter.pace().await;
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn scopes_on_one_endpoint_share_its_limiter_at_the_slowest_rate() {
+        let timeout = Duration::from_secs(5);
+        let limiters = Limiters::default();
+        let shared = |endpoint, rate| {
+            let mut http = OaiHttp::new(endpoint, timeout, rate).unwrap();
+            http.share_limiter(&limiters);
+            http
+        };
+        let set_a = shared("https://example.org/oai?set=a", None);
+        let set_b = shared("https://example.org/oai?set=b", Some(4.0));
+        let prefix = shared("https://EXAMPLE.org:443/oai/", Some(10.0));
+        let other = shared("https://other.example.org/oai", None);
+
+        assert!(Arc::ptr_eq(&set_a.limiter, &set_b.limiter));
+        assert!(Arc::ptr_eq(&set_a.limiter, &prefix.limiter));
+        assert!(!Arc::ptr_eq(&set_a.limiter, &other.limiter));
+        assert_eq!(
+            set_a.limiter.state.lock().unwrap().interval,
+            Some(Duration::from_millis(250))
+        );
+
+        // Outside a shared run, each scope paces on its own.
+        let alone = OaiHttp::new("https://example.org/oai", timeout, None).unwrap();
+        assert!(!Arc::ptr_eq(&set_a.limiter, &alone.limiter));
+        assert_eq!(alone.limiter.state.lock().unwrap().interval, None);
+    }
+}
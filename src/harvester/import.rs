@@ -1,77 +1,326 @@
-use std::time::Duration;
+use std::collections::HashMap;
 
-use tracing::info;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::future;
+use futures::stream::{self, StreamExt};
+use tracing::{info, warn};
 
 use crate::{
+    db::endpoints,
     db::harvester::{ImportStats, batch_upsert_records},
-    oai::OaiHeader,
+    db::resumption::{self, SavedListing},
+    db::runs,
+    oai::{HarvestMode, OaiHeader, OaiRecordStatus, ProtocolError},
 };
 
-use super::Harvester;
+use super::download;
+use super::listing::{self, ListResponse, Page, PageRequest};
+use super::{BatchStats, Harvester};
 
-use oai_pmh::{Client, ListIdentifiersArgs};
+use oai_pmh::client::response::{ListIdentifiersResponse, ListRecordsResponse};
 
 const BATCH_SIZE: usize = 100;
 
+/// Subtracted from the last completed run's start time when computing an
+/// incremental `from`, to absorb clock skew between us and the provider and
+/// records committed while that run was listing.
+const FROM_OVERLAP: TimeDelta = TimeDelta::hours(1);
+
+/// The seconds-level granularity an endpoint may declare in `Identify`. Any
+/// other value (including a missing response) falls back to day granularity,
+/// which every OAI-PMH repository must support.
+const SECONDS_GRANULARITY: &str = "YYYY-MM-DDThh:mm:ssZ";
+
 pub(super) async fn run(harvester: &Harvester) -> anyhow::Result<ImportStats> {
     let total = process(harvester).await?;
     info!(
-        "Imported {} records (active: {}, deleted: {})",
-        total.processed, total.imported, total.deleted
+        "Imported {} records (active: {}, deleted: {}, failed: {})",
+        total.processed, total.imported, total.deleted, total.failed
     );
     Ok(total)
 }
 
 async fn process(harvester: &Harvester) -> anyhow::Result<ImportStats> {
-    let duration = Duration::from_secs(harvester.config.oai_timeout);
-    let scope = &harvester.config.scope;
-    let client = Client::new(&scope.endpoint)?;
+    let identify = harvester.http.identify().await?;
+    if let Some(identify) = &identify.payload {
+        endpoints::upsert_identify(&harvester.pool, &harvester.config.scope.endpoint, identify)
+            .await?;
+    }
+    let granularity = identify
+        .payload
+        .map(|payload| payload.granularity)
+        .unwrap_or_default();
 
-    oai_timeout("identify", duration, client.identify()).await??;
+    let from = incremental_from(harvester, &granularity).await?;
 
-    let args = ListIdentifiersArgs::new(&scope.metadata_prefix);
-    let mut stream =
-        oai_timeout("list_identifiers", duration, client.list_identifiers(args)).await??;
+    match harvester.config.mode {
+        HarvestMode::ListIdentifiers => {
+            list::<ListIdentifiersResponse>(harvester, from, async |page| {
+                let response_date = page.response.response_date;
+                let headers: Vec<_> = page
+                    .response
+                    .payload
+                    .into_iter()
+                    .flat_map(|payload| payload.header)
+                    .map(OaiHeader::from)
+                    .collect();
+                let mut stats = ImportStats::default();
+                for batch in headers.chunks(BATCH_SIZE) {
+                    let changed = batch_upsert_records(
+                        &harvester.pool,
+                        &harvester.config.scope,
+                        batch,
+                        &response_date,
+                    )
+                    .await?;
+                    stats.accumulate(&ImportStats::tally(&changed));
+                }
+                Ok(stats)
+            })
+            .await
+        }
+        HarvestMode::ListRecords => {
+            list::<ListRecordsResponse>(harvester, from, async |page| {
+                let response_date = page.response.response_date;
+                let mut about = page.about.into_iter();
+                let mut headers = Vec::new();
+                let mut payloads = HashMap::new();
+                for record in page.response.payload.into_iter().flat_map(|p| p.record) {
+                    let header = OaiHeader::from(record.header);
+                    let payload = InlinePayload {
+                        metadata: record.metadata,
+                        about: about.next().unwrap_or_default(),
+                    };
+                    payloads.insert(header.identifier.clone(), payload);
+                    headers.push(header);
+                }
+                let mut stats = ImportStats::default();
+                for batch in headers.chunks(BATCH_SIZE) {
+                    stats.accumulate(
+                        &import_records(harvester, batch, &payloads, &response_date).await?,
+                    );
+                }
+                Ok(stats)
+            })
+            .await
+        }
+    }
+}
 
-    let mut batch = Vec::with_capacity(BATCH_SIZE);
-    let mut total = ImportStats::default();
+/// Page through a listing, importing each page with `import_page`. After every
+/// page the resumption token is saved, so an interrupted or failed run leaves
+/// a position the next run resumes from (while the token is valid); the saved
+/// token is removed once the listing completes.
+async fn list<R: ListResponse>(
+    harvester: &Harvester,
+    from: Option<String>,
+    mut import_page: impl AsyncFnMut(Page<R>) -> anyhow::Result<ImportStats>,
+) -> anyhow::Result<ImportStats> {
+    let pool = &harvester.pool;
+    let scope = &harvester.config.scope;
 
-    while let Some(response) =
-        oai_timeout("list_identifiers page fetch", duration, stream.try_next()).await??
-    {
-        if harvester.is_shutdown() {
-            break;
-        }
-        if let Some(e) = response.error {
-            return Err(anyhow::anyhow!("OAI-PMH error: {:?}", e));
+    let mut total = ImportStats::default();
+    let mut listing_started_at = Utc::now();
+    let mut request = PageRequest::Start { from: from.clone() };
+    let mut restarted = false;
+    match saved_listing::<R>(harvester, &from).await? {
+        Some(saved) => {
+            info!(
+                "Resuming {} listing started at {}",
+                R::VERB,
+                saved.listing_started_at
+            );
+            listing_started_at = saved.listing_started_at;
+            total.listing_started_at = Some(listing_started_at);
+            request = PageRequest::Resume(saved.token);
         }
+        None => match &from {
+            Some(from) => info!("Listing records changed since {from}"),
+            None => info!("Listing all records"),
+        },
+    }
+
+    while !harvester.is_shutdown() {
+        let page: Page<R> = listing::fetch_page(&harvester.http, scope, &request).await?;
+        let response = &page.response;
 
-        if let Some(payload) = response.payload {
-            for header in payload.header {
-                batch.push(OaiHeader::from(header));
-                if batch.len() >= BATCH_SIZE {
-                    let stats = batch_upsert_records(&harvester.pool, scope, &batch).await?;
-                    total.accumulate(&stats);
-                    batch.clear();
+        if let Some(error) = response.error() {
+            match ProtocolError::from(error) {
+                // The provider no longer honours the token (a saved one it
+                // has forgotten, or one that expired mid-listing): start the
+                // listing over, once per run.
+                ProtocolError::BadResumptionToken(_)
+                    if matches!(request, PageRequest::Resume(_)) && !restarted =>
+                {
+                    warn!("Resumption token was rejected; listing from the start");
+                    resumption::clear(pool, scope).await?;
+                    listing_started_at = Utc::now();
+                    total.listing_started_at = None;
+                    request = PageRequest::Start { from: from.clone() };
+                    restarted = true;
+                    continue;
+                }
+                // An empty listing, routine for incremental harvests of quiet
+                // endpoints: zero changes.
+                ProtocolError::NoRecordsMatch(_) => {
+                    resumption::clear(pool, scope).await?;
+                    break;
                 }
+                error => return Err(error.into()),
             }
         }
-    }
 
-    if !batch.is_empty() {
-        let stats = batch_upsert_records(&harvester.pool, scope, &batch).await?;
-        total.accumulate(&stats);
+        let next = response
+            .resumption_token()
+            .filter(|token| !token.token.is_empty())
+            .map(|token| SavedListing {
+                verb: R::VERB.to_string(),
+                token: token.token.clone(),
+                expires_at: token
+                    .expiration_date
+                    .as_deref()
+                    .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                    .map(|date| date.with_timezone(&Utc)),
+                listing_from: from.clone(),
+                listing_started_at,
+            });
+
+        total.accumulate(&import_page(page).await?);
+
+        match next {
+            Some(next) => {
+                resumption::save(pool, scope, &next).await?;
+                request = PageRequest::Resume(next.token);
+            }
+            None => {
+                resumption::clear(pool, scope).await?;
+                break;
+            }
+        }
     }
 
     Ok(total)
 }
 
-async fn oai_timeout<T>(
-    label: &str,
-    duration: Duration,
-    future: impl Future<Output = T>,
-) -> anyhow::Result<T> {
-    tokio::time::timeout(duration, future)
-        .await
-        .map_err(|_| anyhow::anyhow!("OAI {} timed out after {}s", label, duration.as_secs()))
+/// The scope's saved listing, if this run can resume it: same verb, not
+/// expired, and — for `--full` runs — itself a complete listing.
+async fn saved_listing<R: ListResponse>(
+    harvester: &Harvester,
+    from: &Option<String>,
+) -> anyhow::Result<Option<SavedListing>> {
+    let Some(saved) = resumption::load(&harvester.pool, &harvester.config.scope).await? else {
+        return Ok(None);
+    };
+
+    let expired = saved
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now());
+    let resumable =
+        saved.verb == R::VERB && !expired && (from.is_some() || saved.listing_from.is_none());
+    if !resumable {
+        info!("Discarding saved {} resumption token", saved.verb);
+        resumption::clear(&harvester.pool, &harvester.config.scope).await?;
+        return Ok(None);
+    }
+
+    Ok(Some(saved))
+}
+
+/// A record's payload as delivered inline by `ListRecords`.
+struct InlinePayload {
+    metadata: String,
+    /// The inner XML of the record's `<about>` containers.
+    about: Vec<String>,
+}
+
+/// Upsert a page of `ListRecords` headers, then store the payloads of the
+/// records that are new or changed.
+async fn import_records(
+    harvester: &Harvester,
+    headers: &[OaiHeader],
+    payloads: &HashMap<String, InlinePayload>,
+    response_date: &str,
+) -> anyhow::Result<ImportStats> {
+    let changed = batch_upsert_records(
+        &harvester.pool,
+        &harvester.config.scope,
+        headers,
+        response_date,
+    )
+    .await?;
+    let mut stats = ImportStats::tally(&changed);
+
+    let results: Vec<_> = stream::iter(&changed)
+        .filter(|record| future::ready(record.status == OaiRecordStatus::Pending))
+        .filter_map(|record| {
+            let payload = payloads
+                .get(&record.identifier)
+                .filter(|payload| !payload.metadata.is_empty());
+            future::ready(payload.map(|payload| (record, payload)))
+        })
+        .map(|(record, payload)| {
+            download::store(harvester, record, &payload.metadata, &payload.about)
+        })
+        .buffer_unordered(harvester.config.concurrency)
+        .collect()
+        .await;
+    let stored = BatchStats::from_downloads(results);
+    stats.failed = stored.failed;
+    stats.unchanged = stored.unchanged;
+
+    Ok(stats)
+}
+
+/// The `from` datestamp for this harvest: the start of the last completed run
+/// for the scope minus `FROM_OVERLAP`, at the endpoint's granularity. `None`
+/// (a complete listing) for `--full` runs and scopes never harvested to
+/// completion.
+async fn incremental_from(
+    harvester: &Harvester,
+    granularity: &str,
+) -> anyhow::Result<Option<String>> {
+    if harvester.config.full {
+        return Ok(None);
+    }
+
+    let last_started_at = runs::last_completed_started_at(
+        &harvester.pool,
+        runs::KIND_HARVEST,
+        &harvester.config.scope,
+    )
+    .await?;
+
+    Ok(last_started_at.map(|started_at| format_from(started_at - FROM_OVERLAP, granularity)))
+}
+
+fn format_from(since: DateTime<Utc>, granularity: &str) -> String {
+    if granularity == SECONDS_GRANULARITY {
+        since.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+    } else {
+        since.format("%Y-%m-%d").to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn formats_from_at_seconds_granularity() {
+        let since = Utc.with_ymd_and_hms(2026, 3, 4, 5, 6, 7).unwrap();
+        assert_eq!(
+            format_from(since, "YYYY-MM-DDThh:mm:ssZ"),
+            "2026-03-04T05:06:07Z"
+        );
+    }
+
+    #[test]
+    fn formats_from_at_day_granularity() {
+        let since = Utc.with_ymd_and_hms(2026, 3, 4, 5, 6, 7).unwrap();
+        assert_eq!(format_from(since, "YYYY-MM-DD"), "2026-03-04");
+        // Unknown or missing granularity falls back to the mandatory day level.
+        assert_eq!(format_from(since, ""), "2026-03-04");
+    }
 }
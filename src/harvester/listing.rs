@@ -0,0 +1,131 @@
+//! Paged `ListIdentifiers` / `ListRecords` requests. `oai_pmh::ResumableStream`
+//! keeps its resumption token private; fetching pages here exposes the token
+//! (and its expiry) so an unfinished listing can be saved and resumed by a
+//! later run.
+
+use oai_pmh::client::response::{
+    ListIdentifiersResponse, ListRecordsResponse, ResponseError, ResumptionToken,
+};
+
+use super::http::{self, FetchError, OaiHttp};
+use crate::oai::OaiScope;
+
+/// A list verb response.
+pub(super) trait ListResponse: Sized {
+    const VERB: &'static str;
+
+    fn parse(xml: &str) -> oai_pmh::Result<Self>;
+    fn error(&self) -> Option<&ResponseError>;
+    fn resumption_token(&self) -> Option<&ResumptionToken>;
+}
+
+impl ListResponse for ListIdentifiersResponse {
+    const VERB: &'static str = "ListIdentifiers";
+
+    fn parse(xml: &str) -> oai_pmh::Result<Self> {
+        Self::new(xml)
+    }
+
+    fn error(&self) -> Option<&ResponseError> {
+        self.error.as_ref()
+    }
+
+    fn resumption_token(&self) -> Option<&ResumptionToken> {
+        self.payload.as_ref()?.resumption_token.as_ref()
+    }
+}
+
+impl ListResponse for ListRecordsResponse {
+    const VERB: &'static str = "ListRecords";
+
+    fn parse(xml: &str) -> oai_pmh::Result<Self> {
+        Self::new(xml)
+    }
+
+    fn error(&self) -> Option<&ResponseError> {
+        self.error.as_ref()
+    }
+
+    fn resumption_token(&self) -> Option<&ResumptionToken> {
+        self.payload.as_ref()?.resumption_token.as_ref()
+    }
+}
+
+/// A fetched page of a listing.
+pub(super) struct Page<R> {
+    pub(super) response: R,
+    /// Per record, in document order: the inner XML of its `<about>`
+    /// containers (always empty for `ListIdentifiers`).
+    pub(super) about: Vec<Vec<String>>,
+}
+
+/// Which page of a listing to request.
+#[derive(Debug, Clone)]
+pub(super) enum PageRequest {
+    /// The first page, optionally limited to records changed since `from`.
+    Start { from: Option<String> },
+    /// A later page, by resumption token.
+    Resume(String),
+}
+
+pub(super) async fn fetch_page<R: ListResponse>(
+    http: &OaiHttp,
+    scope: &OaiScope,
+    request: &PageRequest,
+) -> Result<Page<R>, FetchError> {
+    let args = page_args(scope, request);
+    let args: Vec<_> = args.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    let xml = http.get(R::VERB, &args).await?;
+    let (response, about) = http::parse_with_about(&xml, R::parse)?;
+    Ok(Page { response, about })
+}
+
+fn page_args(scope: &OaiScope, request: &PageRequest) -> Vec<(&'static str, String)> {
+    match request {
+        // The token encodes every other argument; OAI-PMH forbids repeating
+        // them alongside it.
+        PageRequest::Resume(token) => vec![("resumptionToken", token.clone())],
+        PageRequest::Start { from } => {
+            let mut args = vec![("metadataPrefix", scope.metadata_prefix.clone())];
+            if let Some(from) = from {
+                args.push(("from", from.clone()));
+            }
+            if let Some(set) = &scope.set {
+                args.push(("set", set.clone()));
+            }
+            args
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_page_carries_listing_arguments() {
+        let scope = OaiScope::new("https://example.org/oai", "oai_ead")
+            .with_set(Some("coll:a".to_string()));
+        let request = PageRequest::Start {
+            from: Some("2026-03-04".to_string()),
+        };
+        assert_eq!(
+            page_args(&scope, &request),
+            vec![
+                ("metadataPrefix", "oai_ead".to_string()),
+                ("from", "2026-03-04".to_string()),
+                ("set", "coll:a".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn resumed_page_carries_only_the_token() {
+        let scope = OaiScope::new("https://example.org/oai", "oai_ead");
+        let request = PageRequest::Resume("abc/123==".to_string());
+        assert_eq!(
+            page_args(&scope, &request),
+            vec![("resumptionToken", "abc/123==".to_string())]
+        );
+    }
+}
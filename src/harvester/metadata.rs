@@ -1,16 +1,23 @@
 use std::{
     collections::HashMap,
-    fs::File,
     io::{BufReader, Read},
     path::PathBuf,
 };
 
-use quick_xml::{Reader, escape, events::Event};
+use quick_xml::{
+    NsReader, XmlVersion, escape,
+    events::{BytesStart, Event},
+    name::ResolveResult,
+};
 use serde_json::{Map, Value};
 
 use crate::{
-    harvester::{BatchStats, rules::RuleSet},
+    harvester::{
+        BatchStats,
+        rules::{Rule, RuleSet, Selector, Step},
+    },
     oai::{HarvestEvent, OaiRecord, OaiRecordStatus},
+    payload,
 };
 
 use super::Harvester;
@@ -19,7 +26,7 @@ pub(super) async fn run(
     harvester: &Harvester,
     rules: PathBuf,
 ) -> anyhow::Result<super::BatchStats> {
-    let rules = RuleSet::load(File::open(rules)?)?;
+    let rules = RuleSet::open(&rules)?;
     harvester
         .batched(
             OaiRecordStatus::Available,
@@ -46,18 +53,27 @@ async fn process_record(
     rules: &RuleSet,
     record: &OaiRecord,
 ) -> anyhow::Result<bool> {
-    let path = harvester.config.data_dir.join(record.path());
-    let file = match File::open(&path) {
+    let key = record.path();
+    let opened = match harvester.storage.get(&key).await {
+        Ok(Some(stored)) => payload::decoder(stored, record.payload_format).map_err(Into::into),
+        Ok(None) => Err(anyhow::anyhow!("not found")),
+        Err(error) => Err(error),
+    };
+    let file = match opened {
         Ok(file) => file,
         Err(error) => {
-            let message = format!("Unable to open metadata file {}: {}", path.display(), error);
+            let message = format!(
+                "Unable to open metadata file {}: {}",
+                harvester.storage.describe(&key),
+                error
+            );
             return harvester
                 .update(record, &HarvestEvent::MetadataFailed { message: &message })
                 .await;
         }
     };
 
-    match extract_metadata(&file, rules) {
+    match extract_metadata(file, rules) {
         Ok(metadata) => {
             harvester
                 .update(record, &HarvestEvent::MetadataExtracted { metadata })
@@ -74,28 +90,52 @@ async fn process_record(
 
 fn extract_metadata(reader: impl Read, rules: &RuleSet) -> anyhow::Result<Value> {
     let buf_reader = BufReader::new(reader);
-    let mut reader = Reader::from_reader(buf_reader);
-    let mut result: HashMap<String, Vec<String>> = HashMap::new();
-    let mut stack: Vec<String> = Vec::new();
+    let mut reader = NsReader::from_reader(buf_reader);
+    // Values extracted per rule, by position in the rules file.
+    let mut extracted: Vec<Vec<String>> = vec![Vec::new(); rules.rules().len()];
+    // Objects extracted per group rule, likewise.
+    let mut grouped: Vec<Vec<Value>> = vec![Vec::new(); rules.rules().len()];
+    let mut stack: Vec<OpenElement> = Vec::new();
     // Track accumulated text at each depth level to handle nested markup
     let mut text_at_depth: HashMap<usize, String> = HashMap::new();
     let mut buf = Vec::new();
 
-    // Skip these elements entirely (dsc contains container lists, often 90%+ of file)
-    const SKIP_ELEMENTS: &[&str] = &["dsc"];
-
     loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(e)) => {
+        let (namespace, event) = match reader.read_resolved_event_into(&mut buf) {
+            // Only resolved when a rule needs it.
+            Ok((namespace, event)) => (
+                rules
+                    .is_namespaced()
+                    .then(|| bound_namespace(&namespace))
+                    .flatten(),
+                event,
+            ),
+            Err(e) => return Err(anyhow::anyhow!("XML parse error: {}", e)),
+        };
+        match event {
+            Event::Start(e) => {
                 let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
-                if SKIP_ELEMENTS.contains(&name.as_str()) {
+                if rules.skips(&name) {
                     reader.read_to_end_into(e.name(), &mut Vec::new())?;
                 } else {
-                    stack.push(name);
+                    stack.push(OpenElement::new(name, namespace, &e, &reader, rules)?);
+                    extract_attributes(&stack, rules, &mut extracted);
                     text_at_depth.entry(stack.len()).or_default();
                 }
             }
-            Ok(Event::Text(e)) => {
+            Event::Empty(e) => {
+                // No text, but its attributes may be targets.
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                if rules.skips(&name) {
+                    buf.clear();
+                    continue;
+                }
+                stack.push(OpenElement::new(name, namespace, &e, &reader, rules)?);
+                extract_attributes(&stack, rules, &mut extracted);
+                extract_closing(&stack, "", rules, &mut extracted, &mut grouped);
+                stack.pop();
+            }
+            Event::Text(e) => {
                 let decoded = e
                     .decode()
                     .map_err(|err| anyhow::anyhow!("XML decode error: {}", err))?;
@@ -104,7 +144,7 @@ fn extract_metadata(reader: impl Read, rules: &RuleSet) -> anyhow::Result<Value>
                     text_at_depth.entry(depth).or_default().push_str(&decoded);
                 }
             }
-            Ok(Event::GeneralRef(e)) => {
+            Event::GeneralRef(e) => {
                 // Resolve entity references like &amp; -> &, &lt; -> <, etc.
                 let entity = e
                     .decode()
@@ -114,32 +154,52 @@ fn extract_metadata(reader: impl Read, rules: &RuleSet) -> anyhow::Result<Value>
                     text_at_depth.entry(depth).or_default().push_str(resolved);
                 }
             }
-            Ok(Event::End(_)) => {
-                let depth = stack.len();
-                if let Some(text) = text_at_depth.remove(&depth) {
-                    let text = text.trim();
-                    if !text.is_empty()
-                        && let Some(terminal) = stack.last()
-                    {
-                        for rule in rules.by_terminal(terminal) {
-                            if stack_matches_path(&stack, &rule.path) {
-                                result
-                                    .entry(rule.key.clone())
-                                    .or_default()
-                                    .push(text.to_string());
-                            }
-                        }
-                    }
-                }
+            Event::End(_) => {
+                let text = text_at_depth.remove(&stack.len()).unwrap_or_default();
+                extract_closing(&stack, text.trim(), rules, &mut extracted, &mut grouped);
                 stack.pop();
             }
-            Ok(Event::Eof) => break,
-            Err(e) => return Err(anyhow::anyhow!("XML parse error: {}", e)),
+            Event::Eof => break,
             _ => {}
         }
         buf.clear();
     }
 
+    let Some(fields) = rules.fields() else {
+        return untyped(rules, extracted);
+    };
+
+    // Transform each rule's values, group them (and any objects) by field in
+    // rule order, then shape each field by its type and cardinality
+    let mut result: HashMap<&str, Vec<Value>> = HashMap::new();
+    for ((rule, values), objects) in rules.rules().iter().zip(extracted).zip(grouped) {
+        result.entry(&rule.key).or_default().extend(
+            transformed(rule, values)
+                .into_iter()
+                .map(Value::String)
+                .chain(objects),
+        );
+    }
+    let mut json_map = Map::new();
+    for field in fields {
+        let values = result.remove(field.name.as_str()).unwrap_or_default();
+        json_map.insert(field.name.clone(), field.shape(values)?);
+    }
+
+    Ok(Value::Object(json_map))
+}
+
+/// CSV rules' output: an array of strings per key that has values.
+fn untyped(rules: &RuleSet, extracted: Vec<Vec<String>>) -> anyhow::Result<Value> {
+    // Transform each rule's values, then group them by key in rule order
+    let mut result: HashMap<String, Vec<String>> = HashMap::new();
+    for (rule, values) in rules.rules().iter().zip(extracted) {
+        let values = transformed(rule, values);
+        if !values.is_empty() {
+            result.entry(rule.key.clone()).or_default().extend(values);
+        }
+    }
+
     // Check for required fields
     for rule in rules.required() {
         if result.get(&rule.key).is_none_or(|v| v.is_empty()) {
@@ -156,17 +216,166 @@ fn extract_metadata(reader: impl Read, rules: &RuleSet) -> anyhow::Result<Value>
     Ok(Value::Object(json_map))
 }
 
-/// Check if element stack ends with the given path
+fn transformed(rule: &Rule, values: Vec<String>) -> Vec<String> {
+    rule.transforms
+        .iter()
+        .fold(values, |values, transform| transform.apply(values))
+}
+
+/// The namespace URI an element or attribute name resolved to, if bound.
+fn bound_namespace(resolved: &ResolveResult) -> Option<String> {
+    match resolved {
+        ResolveResult::Bound(namespace) => {
+            Some(String::from_utf8_lossy(namespace.as_ref()).into_owned())
+        }
+        _ => None,
+    }
+}
+
+/// An element on the scanner's stack: its local name, namespace (resolved
+/// only for namespaced rules) and the attributes any rule refers to, keyed by
+/// qualified name (`xlink:href`), local name (`href`) and/or `{uri}local`,
+/// whichever the rules use.
+struct OpenElement {
+    name: String,
+    namespace: Option<String>,
+    attributes: Vec<(String, String)>,
+}
+
+impl OpenElement {
+    fn new<R>(
+        name: String,
+        namespace: Option<String>,
+        start: &BytesStart,
+        reader: &NsReader<R>,
+        rules: &RuleSet,
+    ) -> anyhow::Result<Self> {
+        let mut attributes = Vec::new();
+        for attribute in start.attributes() {
+            let attribute =
+                attribute.map_err(|err| anyhow::anyhow!("XML attribute error: {}", err))?;
+            let qualified = String::from_utf8_lossy(attribute.key.as_ref());
+            let local = String::from_utf8_lossy(attribute.key.local_name().into_inner());
+            let expanded = if rules.is_namespaced() {
+                let (resolved, _) = reader.resolver().resolve_attribute(attribute.key);
+                bound_namespace(&resolved).map(|uri| format!("{{{uri}}}{local}"))
+            } else {
+                None
+            };
+            let keys = [
+                qualified.as_ref(),
+                local.as_ref(),
+                expanded.as_deref().unwrap_or_default(),
+            ];
+            if !keys.iter().any(|key| rules.uses_attribute(key)) {
+                continue;
+            }
+            let value = attribute
+                .decoded_and_normalized_value(XmlVersion::Implicit1_0, reader.decoder())
+                .map_err(|err| anyhow::anyhow!("XML decode error: {}", err))?;
+            for key in keys.iter().filter(|key| rules.uses_attribute(key)) {
+                attributes.push((key.to_string(), value.to_string()));
+            }
+        }
+        Ok(Self {
+            name,
+            namespace,
+            attributes,
+        })
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// A group rule's object for this element, with `text` its (trimmed)
+    /// text; missing or empty properties are `null`.
+    fn object(&self, properties: &[(String, Selector)], text: &str) -> Value {
+        let object: Map<_, _> = properties
+            .iter()
+            .map(|(key, selector)| {
+                let value = match selector {
+                    Selector::Text => Some(text),
+                    Selector::Name => Some(self.name.as_str()),
+                    Selector::Attribute(name) => self.attribute(name).map(str::trim),
+                };
+                let value = value
+                    .filter(|value| !value.is_empty())
+                    .map_or(Value::Null, |value| Value::String(value.to_string()));
+                (key.clone(), value)
+            })
+            .collect();
+        Value::Object(object)
+    }
+
+    fn matches(&self, step: &Step) -> bool {
+        (step.name == "*" || self.name == step.name)
+            && (step.namespace.is_none() || step.namespace == self.namespace)
+            && step
+                .predicates
+                .iter()
+                .all(|(name, value)| self.attribute(name) == Some(value.as_str()))
+    }
+}
+
+/// Extract the attribute targets of rules ending at the innermost element.
+fn extract_attributes(stack: &[OpenElement], rules: &RuleSet, extracted: &mut [Vec<String>]) {
+    let Some(element) = stack.last() else {
+        return;
+    };
+    for (index, rule) in rules.by_terminal(&element.name) {
+        let Some(attribute) = &rule.attribute else {
+            continue;
+        };
+        if let Some(value) = element.attribute(attribute).map(str::trim)
+            && !value.is_empty()
+            && stack_matches_rule(stack, rule)
+        {
+            extracted[index].push(value.to_string());
+        }
+    }
+}
+
+/// Extract the text and group rules ending at the innermost element, as it
+/// closes with `text`.
+fn extract_closing(
+    stack: &[OpenElement],
+    text: &str,
+    rules: &RuleSet,
+    extracted: &mut [Vec<String>],
+    grouped: &mut [Vec<Value>],
+) {
+    let Some(element) = stack.last() else {
+        return;
+    };
+    for (index, rule) in rules.by_terminal(&element.name) {
+        if rule.attribute.is_some() || !stack_matches_rule(stack, rule) {
+            continue;
+        }
+        match &rule.group {
+            Some(properties) => grouped[index].push(element.object(properties, text)),
+            None if !text.is_empty() => extracted[index].push(text.to_string()),
+            None => {}
+        }
+    }
+}
+
+/// Check if element stack ends with the rule's path, predicates included
 /// e.g., stack ["ead", "archdesc", "repository", "corpname"] matches path ["repository", "corpname"]
-fn stack_matches_path(stack: &[String], path: &[String]) -> bool {
-    if path.len() > stack.len() {
+/// An anchored path must match the whole stack.
+fn stack_matches_rule(stack: &[OpenElement], rule: &Rule) -> bool {
+    let path = &rule.path;
+    if path.len() > stack.len() || (rule.anchored && path.len() != stack.len()) {
         return false;
     }
     stack
         .iter()
         .rev()
         .zip(path.iter().rev())
-        .all(|(s, p)| s == p)
+        .all(|(element, step)| element.matches(step))
 }
 
 #[cfg(test)]
@@ -210,6 +419,279 @@ extent,extent,
         assert!(metadata.get("creator").is_none());
     }
 
+    #[test]
+    fn test_extract_metadata_attributes_and_predicates() {
+        let rules_csv = "\
+date_normal,unitdate@normal,
+aspace_uri,unitid[@type='aspace_uri'],
+ark,unitid[@type='ark']/extref@href,
+homepage,extptr@xlink:href,
+collection_title,archdesc[@level='collection']/did/unittitle,required
+series_title,archdesc[@level='series']/did/unittitle,
+call_number,unitid[@type='call'],
+";
+
+        let rules = RuleSet::load(rules_csv.as_bytes()).unwrap();
+        let file = File::open("fixtures/ead.xml").unwrap();
+        let metadata = extract_metadata(file, &rules).unwrap();
+
+        assert_eq!(metadata["date_normal"], serde_json::json!(["1950/1960"]));
+        assert_eq!(
+            metadata["aspace_uri"],
+            serde_json::json!(["/repositories/2/resources/73"])
+        );
+        // Attributes match by local name, or by qualified name as written.
+        assert_eq!(
+            metadata["ark"],
+            serde_json::json!(["https://test.archivesspace.org/ark:/99999/22626"])
+        );
+        // An empty element's attributes are extracted too.
+        assert_eq!(
+            metadata["homepage"],
+            serde_json::json!(["http://homepageurl.com"])
+        );
+        assert_eq!(
+            metadata["collection_title"],
+            serde_json::json!(["ANW-1805 test"])
+        );
+        assert!(metadata.get("series_title").is_none());
+        assert!(metadata.get("call_number").is_none());
+    }
+
+    #[test]
+    fn test_extract_metadata_skip_list_and_anchored_rules() {
+        let xml = r#"<?xml version="1.0"?>
+<ead xmlns="urn:isbn:1-931666-22-9">
+  <archdesc level="collection">
+    <did><unittitle>Collection</unittitle></did>
+    <bibliography><bibref><unittitle>Cited Work</unittitle></bibref></bibliography>
+    <relatedmaterial><p><unittitle>Related Papers</unittitle></p></relatedmaterial>
+    <dsc><c01><did><unittitle>Series 1</unittitle></did></c01></dsc>
+  </archdesc>
+</ead>"#;
+
+        // Default: only dsc is skipped, and suffix rules match anywhere else.
+        let rules = RuleSet::load("title,unittitle,\n".as_bytes()).unwrap();
+        let metadata = extract_metadata(xml.as_bytes(), &rules).unwrap();
+        assert_eq!(
+            metadata["title"],
+            serde_json::json!(["Collection", "Cited Work", "Related Papers"])
+        );
+
+        let rules_csv = "\
+@skip,bibref,relatedmaterial
+title,unittitle,
+collection_title,/ead/archdesc/did/unittitle,required
+misanchored,/archdesc/did/unittitle,
+";
+        let rules = RuleSet::load(rules_csv.as_bytes()).unwrap();
+        let metadata = extract_metadata(xml.as_bytes(), &rules).unwrap();
+        // dsc is no longer skipped once the file declares its own list.
+        assert_eq!(
+            metadata["title"],
+            serde_json::json!(["Collection", "Series 1"])
+        );
+        assert_eq!(
+            metadata["collection_title"],
+            serde_json::json!(["Collection"])
+        );
+        assert!(metadata.get("misanchored").is_none());
+
+        let file = File::open("fixtures/ead.xml").unwrap();
+        let metadata = extract_metadata(file, &rules).unwrap();
+        assert_eq!(
+            metadata["collection_title"],
+            serde_json::json!(["ANW-1805 test"])
+        );
+    }
+
+    #[test]
+    fn test_extract_metadata_transforms() {
+        let rules_csv = "\
+date,unitdate,,date
+date,unitdate@normal,,date
+date_range,unitdate,,date,dedupe
+unit_id,unitid,required,normalize,regex:^MSS\\d+$
+languages,language,,lowercase,dedupe,\"join:, \"
+first_language,language,,first
+";
+
+        let rules = RuleSet::load(rules_csv.as_bytes()).unwrap();
+        let file = File::open("fixtures/ead.xml").unwrap();
+        let metadata = extract_metadata(file, &rules).unwrap();
+
+        // Rules sharing a key are merged in rule order, after their transforms.
+        assert_eq!(
+            metadata["date"],
+            serde_json::json!(["1950/1960", "1950/1960"])
+        );
+        assert_eq!(metadata["date_range"], serde_json::json!(["1950/1960"]));
+        assert_eq!(metadata["unit_id"], serde_json::json!(["MSS54321"]));
+        assert_eq!(
+            metadata["languages"],
+            serde_json::json!(["english, latin script, english"])
+        );
+        assert_eq!(
+            metadata["first_language"],
+            serde_json::json!(["English, Latin script"])
+        );
+
+        // Transforms run before the required check.
+        let rules = RuleSet::load("unit_id,unitid,required,regex:^X\n".as_bytes()).unwrap();
+        let file = File::open("fixtures/ead.xml").unwrap();
+        let err = extract_metadata(file, &rules).unwrap_err().to_string();
+        assert_eq!(err, "Required field 'unit_id' is empty");
+    }
+
+    #[test]
+    fn test_extract_metadata_with_namespaces() {
+        let dc = r#"<?xml version="1.0"?>
+<oai_dc:dc xmlns:oai_dc="http://www.openarchives.org/OAI/2.0/oai_dc/"
+           xmlns:dc="http://purl.org/dc/elements/1.1/"
+           xmlns:terms="http://purl.org/dc/terms/">
+  <dc:title>Papers</dc:title>
+  <terms:title>Alternative</terms:title>
+  <dc:identifier xmlns:x="http://example.org/x" x:type="call">MSS 1</dc:identifier>
+</oai_dc:dc>"#;
+        let rules_csv = "\
+@ns,dc,http://purl.org/dc/elements/1.1/
+@ns,dcterms,http://purl.org/dc/terms/
+@ns,ex,http://example.org/x
+title,dc:title,required
+alternative,dcterms:title,
+any_title,title,
+call_number,dc:identifier[@ex:type='call'],
+";
+        let rules = RuleSet::load(rules_csv.as_bytes()).unwrap();
+        let metadata = extract_metadata(dc.as_bytes(), &rules).unwrap();
+        assert_eq!(metadata["title"], serde_json::json!(["Papers"]));
+        assert_eq!(metadata["alternative"], serde_json::json!(["Alternative"]));
+        // Unprefixed rules match by local name, in any namespace.
+        assert_eq!(
+            metadata["any_title"],
+            serde_json::json!(["Papers", "Alternative"])
+        );
+        // Prefixes are resolved, not compared as written.
+        assert_eq!(metadata["call_number"], serde_json::json!(["MSS 1"]));
+
+        let marc = r#"<record xmlns="http://www.loc.gov/MARC21/slim">
+  <datafield tag="245" ind1="1" ind2="0"><subfield code="a">Papers,</subfield><subfield code="f">1950-1960</subfield></datafield>
+  <datafield tag="650" ind1=" " ind2="0"><subfield code="a">Oral history</subfield></datafield>
+  <other:datafield xmlns:other="urn:other" tag="650"><other:subfield code="a">Not MARC</other:subfield></other:datafield>
+</record>"#;
+        let rules_csv = "\
+@ns,marc,http://www.loc.gov/MARC21/slim
+title,marc:datafield[@tag='245']/marc:subfield[@code='a'],required
+subject,marc:datafield[@tag='650']/marc:subfield[@code='a'],
+";
+        let rules = RuleSet::load(rules_csv.as_bytes()).unwrap();
+        let metadata = extract_metadata(marc.as_bytes(), &rules).unwrap();
+        assert_eq!(metadata["title"], serde_json::json!(["Papers,"]));
+        assert_eq!(metadata["subject"], serde_json::json!(["Oral history"]));
+    }
+
+    #[test]
+    fn test_extract_metadata_typed_fields() {
+        let rules_toml = r#"
+[fields.title]
+path = "/ead/archdesc/did/unittitle"
+required = true
+
+[fields.unit_id]
+path = "unitid"
+multiple = true
+transforms = ["regex:^MSS\\d+$"]
+
+[fields.dates]
+path = ["unitdate@normal", "unitdate"]
+type = "date"
+multiple = true
+
+[fields.extent_feet]
+path = "extent"
+type = "integer"
+transforms = ["regex:^(\\d+) Linear Feet$"]
+
+[fields.digitized]
+path = "dao@actuate"
+type = "boolean"
+default = false
+
+[fields.creator]
+path = "origination/persname"
+
+[fields.subjects]
+path = "controlaccess/*"
+multiple = true
+properties = { type = "name()", value = "text()" }
+"#;
+        let rules = super::super::fields::parse(rules_toml).unwrap();
+        let file = File::open("fixtures/ead.xml").unwrap();
+        let metadata = extract_metadata(file, &rules).unwrap();
+
+        // Every field is present, typed, and single or multi-valued.
+        assert_eq!(
+            metadata,
+            serde_json::json!({
+                "title": "ANW-1805 test",
+                "unit_id": ["MSS54321"],
+                "dates": ["1950/1960", "1950/1960"],
+                "extent_feet": 2,
+                "digitized": false,
+                "creator": null,
+                "subjects": [],
+            })
+        );
+
+        let xml = r#"<ead>
+  <archdesc>
+    <did><unittitle>Papers</unittitle></did>
+    <controlaccess>
+      <subject source="lcsh">Oral history</subject>
+      <persname source="local" role="interviewer">Doe, Jane</persname>
+      <geogname/>
+    </controlaccess>
+  </archdesc>
+</ead>"#;
+        let rules_toml = r#"
+[fields.subjects]
+path = "controlaccess/*"
+multiple = true
+properties = { type = "name()", value = "text()", source = "@source" }
+
+[fields.first_subject]
+path = "controlaccess/subject"
+properties = { value = "text()" }
+"#;
+        let rules = super::super::fields::parse(rules_toml).unwrap();
+        let metadata = extract_metadata(xml.as_bytes(), &rules).unwrap();
+        assert_eq!(
+            metadata["subjects"],
+            serde_json::json!([
+                {"type": "subject", "value": "Oral history", "source": "lcsh"},
+                {"type": "persname", "value": "Doe, Jane", "source": "local"},
+                {"type": "geogname", "value": null, "source": null},
+            ])
+        );
+        assert_eq!(
+            metadata["first_subject"],
+            serde_json::json!({"value": "Oral history"})
+        );
+
+        let rules_toml = r#"
+[fields.date]
+path = "unitdate"
+type = "date"
+required = true
+description = "Collection dates"
+"#;
+        let rules = super::super::fields::parse(rules_toml).unwrap();
+        let err = extract_metadata(xml.as_bytes(), &rules)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Required field 'date' (Collection dates) is empty");
+    }
+
     #[test]
     fn test_extract_metadata_missing_required() {
         let rules_csv = "\
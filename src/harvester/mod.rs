@@ -1,14 +1,24 @@
 pub mod cli;
+mod concurrency;
 mod download;
+mod fields;
+mod http;
 mod import;
+mod listing;
 mod metadata;
+mod provenance;
 mod rules;
+mod sources;
+mod transform;
+mod validate;
 
 use std::path::{self, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use sqlx::PgPool;
+use tokio::sync::Semaphore;
 use tracing::{error, info};
 
 use crate::OaiRecord;
@@ -16,10 +26,28 @@ use crate::batch;
 use crate::db::harvester::{fetch, transition};
 use crate::db::runs::{self, RunStats};
 use crate::oai::{HarvestEvent, OaiConfig, OaiRecordStatus};
+use crate::storage::Storage;
 
-const CONCURRENT_DOWNLOADS: usize = 10;
+use concurrency::Concurrency;
+use download::Downloaded;
+use http::{Limiters, OaiHttp};
 
-pub async fn perform(harvester: &Harvester, rules: Option<PathBuf>) -> anyhow::Result<()> {
+pub(crate) const DEFAULT_CONCURRENT_DOWNLOADS: usize = 10;
+
+/// Default cap on downloads in flight across every source of a sources file.
+pub(crate) const DEFAULT_MAX_CONCURRENT_DOWNLOADS: usize = 20;
+
+/// Default payload versions kept per record (the current one included). History
+/// is opt-in: every kept version is a second copy of a payload on storage.
+pub(crate) const DEFAULT_KEEP_VERSIONS: usize = 0;
+
+/// Default attempts budget for `harvest --retry`. Failed records at/above this
+/// many attempts are quarantined: left failed until retried with a higher
+/// `--max-attempts`.
+pub const DEFAULT_MAX_HARVEST_ATTEMPTS: i32 = 5;
+
+/// Harvest one scope, recording it as a run. Returns the run's counters.
+pub async fn perform(harvester: &Harvester, rules: Option<PathBuf>) -> anyhow::Result<RunStats> {
     let run_id = runs::start(
         &harvester.pool,
         runs::KIND_HARVEST,
@@ -30,8 +58,10 @@ pub async fn perform(harvester: &Harvester, rules: Option<PathBuf>) -> anyhow::R
 
     let mut stats = RunStats::default();
     let result = perform_inner(harvester, rules, &mut stats).await;
+    stats.throttled = harvester.http.throttled();
 
     let (outcome, error_sample) = match &result {
+        Ok(()) if harvester.is_shutdown() => (runs::OUTCOME_INTERRUPTED, String::new()),
         Ok(()) => (runs::OUTCOME_COMPLETED, String::new()),
         Err(e) => (
             runs::OUTCOME_FAILED,
@@ -41,8 +71,19 @@ pub async fn perform(harvester: &Harvester, rules: Option<PathBuf>) -> anyhow::R
     if let Err(e) = runs::finish(&harvester.pool, run_id, outcome, &stats, &error_sample).await {
         error!("Failed to record run {run_id}: {e}");
     }
+    info!(
+        "Run {run_id} {outcome}: processed {}, imported {}, deleted {}, failed {}, unchanged {}, \
+         throttled {}; download concurrency {}",
+        stats.processed,
+        stats.imported,
+        stats.deleted,
+        stats.failed,
+        stats.unchanged,
+        stats.throttled,
+        harvester.concurrency.summary()
+    );
 
-    result
+    result.map(|()| stats)
 }
 
 async fn perform_inner(
@@ -50,16 +91,24 @@ async fn perform_inner(
     rules: Option<PathBuf>,
     stats: &mut RunStats,
 ) -> anyhow::Result<()> {
+    if let Some(schema) = &harvester.config.schema {
+        validate::ensure_schema_usable(schema).await?;
+    }
+
     let import_stats = import::run(harvester).await?;
     stats.processed = import_stats.processed;
     stats.imported = import_stats.imported;
     stats.deleted = import_stats.deleted;
+    stats.failed += import_stats.failed;
+    stats.unchanged = import_stats.unchanged;
+    stats.listing_started_at = import_stats.listing_started_at;
     if harvester.is_shutdown() {
         return Ok(());
     }
 
     let download_stats = download::run(harvester).await?;
     stats.failed += download_stats.failed;
+    stats.unchanged += download_stats.unchanged;
     if harvester.is_shutdown() {
         return Ok(());
     }
@@ -80,15 +129,45 @@ pub struct Harvester {
     config: OaiConfig,
     pool: PgPool,
     shutdown: Arc<AtomicBool>,
+    http: OaiHttp,
+    concurrency: Concurrency,
+    /// A download slot shared with the other harvesters in the process, taken
+    /// alongside `concurrency`'s.
+    downloads: Option<Arc<Semaphore>>,
+    storage: Arc<dyn Storage>,
 }
 
 impl Harvester {
-    pub fn new(config: OaiConfig, pool: PgPool, shutdown: Arc<AtomicBool>) -> Self {
-        Self {
+    pub fn new(config: OaiConfig, pool: PgPool, shutdown: Arc<AtomicBool>) -> anyhow::Result<Self> {
+        let http = OaiHttp::new(
+            &config.scope.endpoint,
+            Duration::from_secs(config.oai_timeout),
+            config.requests_per_second,
+        )?;
+        let concurrency = Concurrency::new(config.concurrency, config.adaptive_concurrency);
+        let storage = config.storage.open();
+        Ok(Self {
             config,
             pool,
             shutdown,
-        }
+            http,
+            concurrency,
+            downloads: None,
+            storage,
+        })
+    }
+
+    /// Share `downloads` slots with other harvesters (a process-wide cap).
+    pub fn with_download_limit(mut self, downloads: Arc<Semaphore>) -> Self {
+        self.downloads = Some(downloads);
+        self
+    }
+
+    /// Pace requests together with the other harvesters of the same endpoint
+    /// in `limiters`.
+    fn with_rate_limiters(mut self, limiters: &Limiters) -> Self {
+        self.http.share_limiter(limiters);
+        self
     }
 
     pub(crate) fn is_shutdown(&self) -> bool {
@@ -115,6 +194,7 @@ impl Harvester {
         let total = BatchStats {
             processed: all.iter().map(|s| s.processed).sum(),
             failed: all.iter().map(|s| s.failed).sum(),
+            unchanged: all.iter().map(|s| s.unchanged).sum(),
         };
         info!(
             "{label} {} records (failed: {})",
@@ -145,6 +225,8 @@ impl Harvester {
 struct BatchStats {
     processed: usize,
     failed: usize,
+    /// Processed records restored unchanged (downloads only).
+    unchanged: usize,
 }
 
 impl BatchStats {
@@ -158,4 +240,19 @@ impl BatchStats {
         }
         stats
     }
+
+    fn from_downloads(results: impl IntoIterator<Item = anyhow::Result<Downloaded>>) -> Self {
+        let mut stats = Self::default();
+        for result in results {
+            match result {
+                Ok(Downloaded::Applied) => stats.processed += 1,
+                Ok(Downloaded::Unchanged) => {
+                    stats.processed += 1;
+                    stats.unchanged += 1;
+                }
+                Ok(Downloaded::NotApplied) | Err(_) => stats.failed += 1,
+            }
+        }
+        stats
+    }
 }
@@ -0,0 +1,140 @@
+//! Record `<about>` containers. `oai_pmh` reads `about` as plain text and
+//! rejects the whole response when one holds elements (as provenance and
+//! rights statements do), so they are cut out of the response before it is
+//! parsed and kept here as raw XML.
+
+use std::ops::Range;
+
+use quick_xml::{Reader, events::Event};
+
+/// An OAI-PMH response with its records' `<about>` containers removed.
+#[derive(Debug, PartialEq)]
+pub(super) struct Stripped {
+    /// The response, safe to hand to `oai_pmh`.
+    pub(super) xml: String,
+    /// Per `<record>`, in document order: the inner XML of each of its
+    /// `<about>` containers.
+    pub(super) about: Vec<Vec<String>>,
+}
+
+/// Remove the `<about>` containers of every `<record>` in `xml`, keeping
+/// their contents. Records are those directly inside the verb element
+/// (`GetRecord` or `ListRecords`).
+pub(super) fn strip_about(xml: &str) -> Result<Stripped, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    // Local names of the open elements, outermost first.
+    let mut open: Vec<Vec<u8>> = Vec::new();
+    let mut about: Vec<Vec<String>> = Vec::new();
+    let mut cuts: Vec<Range<usize>> = Vec::new();
+
+    loop {
+        let offset = reader.buffer_position() as usize;
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = e.local_name().as_ref().to_vec();
+                if in_record(&open) && name == b"about" {
+                    let span = reader.read_to_end(e.name())?;
+                    if let Some(containers) = about.last_mut() {
+                        containers.push(xml[span.start as usize..span.end as usize].to_string());
+                    }
+                    cuts.push(offset..reader.buffer_position() as usize);
+                    continue;
+                }
+                if open.len() == 2 && name == b"record" {
+                    about.push(Vec::new());
+                }
+                open.push(name);
+            }
+            Event::Empty(e) => {
+                let name = e.local_name();
+                if in_record(&open) && name.as_ref() == b"about" {
+                    if let Some(containers) = about.last_mut() {
+                        containers.push(String::new());
+                    }
+                    cuts.push(offset..reader.buffer_position() as usize);
+                } else if open.len() == 2 && name.as_ref() == b"record" {
+                    about.push(Vec::new());
+                }
+            }
+            Event::End(_) => {
+                open.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if cuts.is_empty() {
+        return Ok(Stripped {
+            xml: xml.to_string(),
+            about,
+        });
+    }
+    let mut stripped = String::with_capacity(xml.len());
+    let mut kept_from = 0;
+    for cut in cuts {
+        stripped.push_str(&xml[kept_from..cut.start]);
+        kept_from = cut.end;
+    }
+    stripped.push_str(&xml[kept_from..]);
+    Ok(Stripped {
+        xml: stripped,
+        about,
+    })
+}
+
+/// Whether `open` ends at a record: `OAI-PMH/<verb>/record`.
+fn in_record(open: &[Vec<u8>]) -> bool {
+    open.len() == 3 && open[2] == b"record"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_about_containers_per_record() {
+        let xml = r#"<OAI-PMH xmlns="http://www.openarchives.org/OAI/2.0/">
+<ListRecords>
+<record><header><identifier>a</identifier></header><metadata><ead><about/></ead></metadata><about><provenance><originDescription/></provenance></about><about>rights</about></record>
+<record><header status="deleted"><identifier>b</identifier></header></record>
+<record><header><identifier>c</identifier></header><about/></record>
+</ListRecords>
+</OAI-PMH>"#;
+        let stripped = strip_about(xml).unwrap();
+        assert_eq!(
+            stripped.about,
+            vec![
+                vec![
+                    "<provenance><originDescription/></provenance>".to_string(),
+                    "rights".to_string(),
+                ],
+                vec![],
+                vec![String::new()],
+            ]
+        );
+        assert!(!stripped.xml.contains("provenance"));
+        assert!(!stripped.xml.contains("rights"));
+        // Elements named `about` inside the metadata are payload, not
+        // containers.
+        assert!(stripped.xml.contains("<ead><about/></ead>"));
+        assert!(
+            stripped
+                .xml
+                .contains("<record><header><identifier>c</identifier></header></record>")
+        );
+    }
+
+    #[test]
+    fn leaves_responses_without_about_alone() {
+        let xml = "<OAI-PMH><GetRecord><record><header/><metadata><ead/></metadata></record>\
+                   </GetRecord></OAI-PMH>";
+        assert_eq!(
+            strip_about(xml).unwrap(),
+            Stripped {
+                xml: xml.to_string(),
+                about: vec![vec![]],
+            }
+        );
+    }
+}
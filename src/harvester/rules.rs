@@ -1,59 +1,261 @@
-use std::{collections::HashMap, io::Read};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
 
+use anyhow::Context;
+
+use super::fields::{self, Field};
+use super::transform::Transform;
+
+/// Elements skipped (with everything inside them) when no `@skip` directive
+/// is given: `dsc` holds the container lists, often 90%+ of a file.
+const DEFAULT_SKIP: &[&str] = &["dsc"];
+
+/// A metadata rule: `key,path[,required[,transform...]]`. A path is a chain
+/// of element names matched against the end of the open elements, e.g.
+/// `repository/corpname`, or, with a leading `/`, against all of them
+/// (`/ead/archdesc/did/unittitle`). Unprefixed names match any namespace;
+/// prefixed ones (`dc:title`) match the namespace an `@ns` directive maps the
+/// prefix to. Any step may carry attribute-equality predicates
+/// (`unitid[@type='call']`), and the last step may name an attribute whose
+/// value is extracted instead of the element's text (`unitdate@normal`). A
+/// `*` step matches any element.
 #[derive(Debug)]
 pub struct Rule {
     pub key: String,
-    pub path: Vec<String>,
+    pub path: Vec<Step>,
+    /// The path starts at the root element rather than anywhere.
+    pub anchored: bool,
+    /// Extract this attribute of the terminal element rather than its text.
+    pub attribute: Option<String>,
     pub required: bool,
+    /// Applied in order to the values the rule extracted from a record.
+    pub transforms: Vec<Transform>,
+    /// For grouped fields: the properties of the object extracted per
+    /// matching element, instead of its text.
+    pub group: Option<Vec<(String, Selector)>>,
+}
+
+/// What a group property takes from a matched element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    /// Its text (`text()`).
+    Text,
+    /// Its local name (`name()`).
+    Name,
+    /// An attribute (`@name`), keyed like predicate attributes.
+    Attribute(String),
+}
+
+impl Rule {
+    /// A rule extracting the text (or attribute target) at `path`, resolving
+    /// prefixes with `namespaces`; transforms and groups are added by the
+    /// caller.
+    pub(super) fn new(
+        key: String,
+        path: &str,
+        required: bool,
+        namespaces: &HashMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        let (anchored, path) = match path.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (false, path),
+        };
+        let (path, attribute) = parse_path(path)
+            .and_then(|(path, attribute)| resolve(path, attribute, namespaces))
+            .with_context(|| format!("Invalid path for rule '{key}'"))?;
+        Ok(Self {
+            key,
+            path,
+            anchored,
+            attribute,
+            required,
+            transforms: Vec::new(),
+            group: None,
+        })
+    }
+}
+
+/// One element of a rule path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// The element's local name.
+    pub name: String,
+    /// The namespace URI the element must be in, for prefixed names.
+    pub namespace: Option<String>,
+    /// `(attribute, value)` pairs the element must carry. Attributes with an
+    /// `@ns` prefix are named `{uri}local`; others as written.
+    pub predicates: Vec<(String, String)>,
 }
 
 #[derive(Debug)]
 pub struct RuleSet {
     rules: Vec<Rule>,
     by_terminal: HashMap<String, Vec<usize>>,
+    /// Attributes named by any rule, as targets or in predicates.
+    attributes: HashSet<String>,
+    /// Elements the scanner skips, contents included.
+    skip: HashSet<String>,
+    /// Whether any rule matches by namespace.
+    namespaced: bool,
+    /// The typed fields of a TOML rules file, which shape the output; `None`
+    /// for CSV rules (an array of strings per key).
+    fields: Option<Vec<Field>>,
 }
 
 impl RuleSet {
+    /// Load a rules file: typed fields from a `.toml` file, CSV rules from
+    /// anything else.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read rules file {}", path.display()))?;
+            fields::parse(&text).with_context(|| format!("Invalid rules file {}", path.display()))
+        } else {
+            Self::load(File::open(path)?)
+        }
+    }
+
+    /// Load rules, one per line, `@skip,<element>[,<element>...]` directives
+    /// naming the elements to skip (replacing the default `dsc`; `@skip,`
+    /// alone skips nothing), and `@ns,<prefix>,<uri>` directives mapping the
+    /// prefixes the rules after them use.
     pub fn load(reader: impl Read) -> anyhow::Result<Self> {
         let mut rules = Vec::new();
-        let mut by_terminal: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut skip: Option<HashSet<String>> = None;
+        let mut namespaces: HashMap<String, String> = HashMap::new();
 
         let mut csv_reader = csv::ReaderBuilder::new()
             .has_headers(false)
+            .flexible(true)
             .from_reader(reader);
 
         for record in csv_reader.records() {
             let record = record?;
             let key = record[0].to_string();
-            let path_str = &record[1];
+            if key == "@skip" {
+                let names = record
+                    .iter()
+                    .skip(1)
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty());
+                skip.get_or_insert_default()
+                    .extend(names.map(str::to_string));
+                continue;
+            }
+            if key == "@ns" {
+                let (Some(prefix), Some(uri)) = (record.get(1), record.get(2)) else {
+                    anyhow::bail!("@ns needs a prefix and a namespace URI");
+                };
+                let (prefix, uri) = (prefix.trim(), uri.trim());
+                if prefix.is_empty() || uri.is_empty() {
+                    anyhow::bail!("@ns needs a prefix and a namespace URI");
+                }
+                namespaces.insert(prefix.to_string(), uri.to_string());
+                continue;
+            }
+            if key.starts_with('@') {
+                anyhow::bail!("Unknown rules directive '{key}'");
+            }
+            let path_str = record
+                .get(1)
+                .with_context(|| format!("Rule '{key}' has no path"))?;
             let required = record.get(2).map(|s| s == "required").unwrap_or(false);
+            let mut rule = Rule::new(key, path_str, required, &namespaces)?;
+            rule.transforms = record
+                .iter()
+                .skip(3)
+                .filter(|spec| !spec.trim().is_empty())
+                .map(Transform::parse)
+                .collect::<anyhow::Result<Vec<_>>>()
+                .with_context(|| format!("Invalid transform for rule '{}'", rule.key))?;
+            rules.push(rule);
+        }
 
-            let path: Vec<String> = path_str.split('/').map(|s| s.to_string()).collect();
+        Ok(Self::build(rules, skip, None))
+    }
 
-            if let Some(terminal) = path.last() {
+    /// Index `rules` for the scanner. `skip` defaults to `dsc`.
+    pub(super) fn build(
+        rules: Vec<Rule>,
+        skip: Option<HashSet<String>>,
+        fields: Option<Vec<Field>>,
+    ) -> Self {
+        let mut by_terminal: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut attributes = HashSet::new();
+        for (index, rule) in rules.iter().enumerate() {
+            if let Some(terminal) = rule.path.last() {
                 by_terminal
-                    .entry(terminal.clone())
+                    .entry(terminal.name.clone())
                     .or_default()
-                    .push(rules.len());
+                    .push(index);
+            }
+            attributes.extend(rule.attribute.iter().cloned());
+            for step in &rule.path {
+                attributes.extend(step.predicates.iter().map(|(name, _)| name.clone()));
+            }
+            for (_, selector) in rule.group.iter().flatten() {
+                if let Selector::Attribute(name) = selector {
+                    attributes.insert(name.clone());
+                }
             }
+        }
 
-            rules.push(Rule {
-                key,
-                path,
-                required,
-            });
+        let namespaced = rules
+            .iter()
+            .any(|rule| rule.path.iter().any(|step| step.namespace.is_some()))
+            || attributes.iter().any(|name| name.starts_with('{'));
+        Self {
+            rules,
+            by_terminal,
+            attributes,
+            namespaced,
+            skip: skip
+                .unwrap_or_else(|| DEFAULT_SKIP.iter().map(|name| name.to_string()).collect()),
+            fields,
         }
+    }
+
+    /// Whether the scanner skips this element and everything inside it.
+    pub fn skips(&self, name: &str) -> bool {
+        self.skip.contains(name)
+    }
+
+    /// Whether any rule matches by namespace, so the scanner must resolve
+    /// them.
+    pub fn is_namespaced(&self) -> bool {
+        self.namespaced
+    }
 
-        Ok(Self { rules, by_terminal })
+    /// Whether any rule names this attribute, so the scanner need only keep
+    /// those.
+    pub fn uses_attribute(&self, name: &str) -> bool {
+        self.attributes.contains(name)
     }
 
-    /// Returns rules whose path ends with the given terminal element
-    pub fn by_terminal(&self, terminal: &str) -> impl Iterator<Item = &Rule> {
+    /// Returns rules whose path ends with the given terminal element (or
+    /// `*`), with their positions in `rules()`
+    pub fn by_terminal(&self, terminal: &str) -> impl Iterator<Item = (usize, &Rule)> {
+        let wildcard = (terminal != "*").then(|| self.by_terminal.get("*"));
         self.by_terminal
             .get(terminal)
             .into_iter()
+            .chain(wildcard.flatten())
             .flatten()
-            .map(|&idx| &self.rules[idx])
+            .map(|&idx| (idx, &self.rules[idx]))
+    }
+
+    /// The typed fields of a TOML rules file.
+    pub fn fields(&self) -> Option<&[Field]> {
+        self.fields.as_deref()
+    }
+
+    /// All rules, in file order.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
     }
 
     /// Returns rules marked as required
@@ -62,6 +264,111 @@ impl RuleSet {
     }
 }
 
+/// Parse `a/b[@x='1']/c@y` into steps and an optional attribute target.
+/// Predicate values are quoted (`'` or `"`) and may contain `/`.
+fn parse_path(path: &str) -> anyhow::Result<(Vec<Step>, Option<String>)> {
+    let mut chars = path.chars().peekable();
+    let mut steps = Vec::new();
+    let mut attribute = None;
+
+    loop {
+        let name = take_name(&mut chars);
+        if name.is_empty() {
+            anyhow::bail!("empty element name in '{path}'");
+        }
+        let mut predicates = Vec::new();
+        while chars.next_if_eq(&'[').is_some() {
+            if chars.next() != Some('@') {
+                anyhow::bail!("predicate on <{name}> must start with '@' in '{path}'");
+            }
+            let attribute = take_name(&mut chars);
+            if attribute.is_empty() || chars.next() != Some('=') {
+                anyhow::bail!("predicate on <{name}> must be [@name='value'] in '{path}'");
+            }
+            let quote = chars
+                .next()
+                .filter(|c| *c == '\'' || *c == '"')
+                .with_context(|| format!("predicate value on <{name}> must be quoted"))?;
+            let value: String = chars.by_ref().take_while(|c| *c != quote).collect();
+            if chars.next() != Some(']') {
+                anyhow::bail!("unclosed predicate on <{name}> in '{path}'");
+            }
+            predicates.push((attribute, value));
+        }
+        steps.push(Step {
+            name,
+            namespace: None,
+            predicates,
+        });
+
+        match chars.next() {
+            None => break,
+            Some('/') => {}
+            Some('@') => {
+                let name = take_name(&mut chars);
+                if name.is_empty() {
+                    anyhow::bail!("empty attribute name in '{path}'");
+                }
+                if chars.next().is_some() {
+                    anyhow::bail!("the attribute target must end the path in '{path}'");
+                }
+                attribute = Some(name);
+                break;
+            }
+            Some(c) => anyhow::bail!("unexpected '{c}' in '{path}'"),
+        }
+    }
+
+    Ok((steps, attribute))
+}
+
+/// How rules name an attribute: `{uri}local` when its prefix is mapped, as
+/// written otherwise.
+pub(super) fn attribute_key(name: String, namespaces: &HashMap<String, String>) -> String {
+    match name.split_once(':') {
+        Some((prefix, local)) if namespaces.contains_key(prefix) => {
+            format!("{{{}}}{local}", namespaces[prefix])
+        }
+        _ => name,
+    }
+}
+
+/// Resolve the prefixes of a parsed path with the `@ns` mappings so far:
+/// prefixed element names must be mapped, prefixed attribute names become
+/// `{uri}local` when mapped and stay as written otherwise.
+fn resolve(
+    mut path: Vec<Step>,
+    attribute: Option<String>,
+    namespaces: &HashMap<String, String>,
+) -> anyhow::Result<(Vec<Step>, Option<String>)> {
+    let attribute_key = |name: String| attribute_key(name, namespaces);
+    for step in &mut path {
+        if let Some((prefix, local)) = step.name.split_once(':') {
+            let uri = namespaces.get(prefix).with_context(|| {
+                format!(
+                    "namespace prefix '{prefix}' is not declared (add an @ns line before the rule)"
+                )
+            })?;
+            step.namespace = Some(uri.clone());
+            step.name = local.to_string();
+        }
+        step.predicates = std::mem::take(&mut step.predicates)
+            .into_iter()
+            .map(|(name, value)| (attribute_key(name), value))
+            .collect();
+    }
+    Ok((path, attribute.map(attribute_key)))
+}
+
+/// Consume an element or attribute name: everything up to a separator.
+fn take_name(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut name = String::new();
+    while let Some(c) = chars.next_if(|c| !matches!(c, '/' | '[' | ']' | '@' | '=')) {
+        name.push(c);
+    }
+    name.trim().to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -80,12 +387,23 @@ extent,extent,
         let ruleset = RuleSet::load(csv.as_bytes()).unwrap();
 
         // Test by_terminal lookup
-        let corpname_rules: Vec<_> = ruleset.by_terminal("corpname").collect();
+        let corpname_rules: Vec<_> = ruleset
+            .by_terminal("corpname")
+            .map(|(_, rule)| rule)
+            .collect();
         assert_eq!(corpname_rules.len(), 1);
         assert_eq!(corpname_rules[0].key, "repository");
-        assert_eq!(corpname_rules[0].path, vec!["repository", "corpname"]);
+        let names: Vec<_> = corpname_rules[0]
+            .path
+            .iter()
+            .map(|step| step.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["repository", "corpname"]);
 
-        let persname_rules: Vec<_> = ruleset.by_terminal("persname").collect();
+        let persname_rules: Vec<_> = ruleset
+            .by_terminal("persname")
+            .map(|(_, rule)| rule)
+            .collect();
         assert_eq!(persname_rules.len(), 1);
         assert_eq!(persname_rules[0].key, "creator");
 
@@ -100,4 +418,176 @@ extent,extent,
         assert!(required_keys.contains(&"unit_id"));
         assert!(required_keys.contains(&"repository"));
     }
+
+    #[test]
+    fn parses_attribute_targets_and_predicates() {
+        let csv = "\
+date_normal,unitdate@normal,
+call_number,did/unitid[@type='call'],
+digital_object,\"dao[@xlink:role='image'][@show=\"\"new/tab\"\"]@href\",
+";
+        let ruleset = RuleSet::load(csv.as_bytes()).unwrap();
+
+        let date: Vec<_> = ruleset
+            .by_terminal("unitdate")
+            .map(|(_, rule)| rule)
+            .collect();
+        assert_eq!(date[0].attribute.as_deref(), Some("normal"));
+        assert!(date[0].path[0].predicates.is_empty());
+
+        let call: Vec<_> = ruleset
+            .by_terminal("unitid")
+            .map(|(_, rule)| rule)
+            .collect();
+        assert_eq!(call[0].attribute, None);
+        assert_eq!(
+            call[0].path,
+            vec![
+                Step {
+                    name: "did".to_string(),
+                    namespace: None,
+                    predicates: vec![],
+                },
+                Step {
+                    name: "unitid".to_string(),
+                    namespace: None,
+                    predicates: vec![("type".to_string(), "call".to_string())],
+                },
+            ]
+        );
+
+        let dao: Vec<_> = ruleset.by_terminal("dao").map(|(_, rule)| rule).collect();
+        assert_eq!(dao[0].attribute.as_deref(), Some("href"));
+        assert_eq!(
+            dao[0].path[0].predicates,
+            vec![
+                ("xlink:role".to_string(), "image".to_string()),
+                ("show".to_string(), "new/tab".to_string()),
+            ]
+        );
+
+        for name in ["normal", "type", "xlink:role", "show", "href"] {
+            assert!(ruleset.uses_attribute(name), "{name}");
+        }
+        assert!(!ruleset.uses_attribute("calendar"));
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        for path in [
+            "did//unitid",
+            "unitid[@type='call'",
+            "unitid[type='call']",
+            "unitid[@type=call]",
+            "unitdate@normal/x",
+            "unitdate@",
+        ] {
+            let csv = format!("key,\"{path}\",\n");
+            assert!(RuleSet::load(csv.as_bytes()).is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn loads_skip_directives_and_anchored_paths() {
+        let csv = "\
+@skip,dsc,bibref
+@skip,relatedmaterial
+title,/ead/archdesc/did/unittitle,required
+any_title,unittitle
+";
+        let ruleset = RuleSet::load(csv.as_bytes()).unwrap();
+        for name in ["dsc", "bibref", "relatedmaterial"] {
+            assert!(ruleset.skips(name), "{name}");
+        }
+        assert!(!ruleset.skips("did"));
+
+        let titles: Vec<_> = ruleset
+            .by_terminal("unittitle")
+            .map(|(_, rule)| rule)
+            .collect();
+        assert!(titles[0].anchored);
+        assert_eq!(titles[0].path.len(), 4);
+        assert!(titles[0].required);
+        assert!(!titles[1].anchored);
+
+        // dsc is skipped unless the file says otherwise.
+        let default = RuleSet::load("title,unittitle,\n".as_bytes()).unwrap();
+        assert!(default.skips("dsc"));
+        let none = RuleSet::load("@skip,\ntitle,unittitle,\n".as_bytes()).unwrap();
+        assert!(!none.skips("dsc"));
+
+        assert!(RuleSet::load("@skipp,dsc\n".as_bytes()).is_err());
+        assert!(RuleSet::load("title\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn loads_transform_pipelines() {
+        let csv = "\
+date,unitdate,,normalize,date,dedupe
+title,unittitle,required
+subjects,\"controlaccess/subject\",,trim,\"join:, \"
+";
+        let ruleset = RuleSet::load(csv.as_bytes()).unwrap();
+        let transforms: Vec<_> = ruleset
+            .rules()
+            .iter()
+            .map(|rule| format!("{:?}", rule.transforms))
+            .collect();
+        assert_eq!(
+            transforms,
+            vec!["[normalize, date, dedupe]", "[]", "[trim, join:, ]"]
+        );
+
+        let unknown = RuleSet::load("date,unitdate,,iso\n".as_bytes()).unwrap_err();
+        assert_eq!(unknown.to_string(), "Invalid transform for rule 'date'");
+    }
+
+    #[test]
+    fn resolves_declared_namespace_prefixes() {
+        let csv = "\
+@ns,dc,http://purl.org/dc/elements/1.1/
+@ns,marc,http://www.loc.gov/MARC21/slim
+title,dc:title,required
+any_title,title
+subject,\"marc:datafield[@tag='650']/marc:subfield[@code='a']\"
+link,dao@xlink:href
+";
+        let ruleset = RuleSet::load(csv.as_bytes()).unwrap();
+        assert!(ruleset.is_namespaced());
+
+        let titles: Vec<_> = ruleset.by_terminal("title").map(|(_, rule)| rule).collect();
+        assert_eq!(titles.len(), 2);
+        assert_eq!(
+            titles[0].path[0].namespace.as_deref(),
+            Some("http://purl.org/dc/elements/1.1/")
+        );
+        assert_eq!(titles[1].path[0].namespace, None);
+
+        let subject: Vec<_> = ruleset
+            .by_terminal("subfield")
+            .map(|(_, rule)| rule)
+            .collect();
+        assert_eq!(subject[0].path[0].name, "datafield");
+        assert_eq!(
+            subject[0].path[1].namespace.as_deref(),
+            Some("http://www.loc.gov/MARC21/slim")
+        );
+        // Unprefixed attributes are still matched as written.
+        assert_eq!(
+            subject[0].path[1].predicates,
+            vec![("code".to_string(), "a".to_string())]
+        );
+        // Undeclared attribute prefixes are matched as written.
+        let link: Vec<_> = ruleset.by_terminal("dao").map(|(_, rule)| rule).collect();
+        assert_eq!(link[0].attribute.as_deref(), Some("xlink:href"));
+
+        let undeclared = RuleSet::load("title,dcterms:title\n".as_bytes()).unwrap_err();
+        assert_eq!(undeclared.to_string(), "Invalid path for rule 'title'");
+        assert!(RuleSet::load("@ns,dc\n".as_bytes()).is_err());
+        assert!(
+            !RuleSet::load("title,title\n".as_bytes())
+                .unwrap()
+                .is_namespaced()
+        );
+    }
 }
@@ -0,0 +1,160 @@
+//! Declarative multi-endpoint harvests: `harvest --sources sources.toml`.
+//!
+//! ```toml
+//! [[source]]
+//! endpoint = "https://test.archivesspace.org/oai"
+//! metadata_prefix = "oai_ead"
+//! rules = "rules.txt"
+//! oai_timeout = 300
+//! oai_retries = 2
+//! concurrency = 4
+//! adaptive_concurrency = true
+//! requests_per_second = 2.0
+//! keep_versions = 5
+//! payload_format = "zstd"
+//! schema = "ead.xsd"
+//! max_payload_bytes = 52428800
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use super::http;
+use crate::oai::{HarvestMode, PayloadFormat};
+
+/// One `[[source]]` entry. Unset options fall back to the `harvest` command
+/// line (and its environment defaults).
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Source {
+    pub(super) endpoint: String,
+    pub(super) metadata_prefix: Option<String>,
+    pub(super) set: Option<String>,
+    /// Relative paths are resolved against the sources file's directory.
+    pub(super) rules: Option<PathBuf>,
+    pub(super) oai_timeout: Option<u64>,
+    pub(super) oai_retries: Option<u32>,
+    pub(super) concurrency: Option<usize>,
+    pub(super) adaptive_concurrency: Option<bool>,
+    pub(super) requests_per_second: Option<f64>,
+    pub(super) keep_versions: Option<usize>,
+    pub(super) payload_format: Option<PayloadFormat>,
+    /// Resolved like `rules`.
+    pub(super) schema: Option<PathBuf>,
+    pub(super) max_payload_bytes: Option<u64>,
+    pub(super) mode: Option<HarvestMode>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SourcesFile {
+    #[serde(rename = "source", default)]
+    sources: Vec<Source>,
+}
+
+pub(super) fn load(path: &Path) -> anyhow::Result<Vec<Source>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read sources file {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    parse(&text, base).with_context(|| format!("Invalid sources file {}", path.display()))
+}
+
+fn parse(text: &str, base: &Path) -> anyhow::Result<Vec<Source>> {
+    let file: SourcesFile = toml::from_str(text)?;
+    if file.sources.is_empty() {
+        anyhow::bail!("no [[source]] entries");
+    }
+
+    file.sources
+        .into_iter()
+        .map(|mut source| {
+            if source.concurrency == Some(0) {
+                anyhow::bail!(
+                    "Invalid source {}: concurrency must be at least 1",
+                    source.endpoint
+                );
+            }
+            if let Some(rate) = source.requests_per_second {
+                http::request_interval(rate)
+                    .with_context(|| format!("Invalid source {}", source.endpoint))?;
+            }
+            source.rules = source.rules.map(|rules| base.join(rules));
+            source.schema = source.schema.map(|schema| base.join(schema));
+            Ok(source)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sources_with_optional_settings() {
+        let sources = parse(
+            r#"
+            [[source]]
+            endpoint = "https://a.example.org/oai"
+            metadata_prefix = "oai_ead"
+            rules = "rules/a.txt"
+            oai_timeout = 300
+            oai_retries = 2
+            concurrency = 4
+            requests_per_second = 0.5
+            keep_versions = 5
+            payload_format = "gzip"
+            schema = "ead.xsd"
+            max_payload_bytes = 1048576
+            mode = "list-records"
+
+            [[source]]
+            endpoint = "https://b.example.org/oai"
+            "#,
+            Path::new("/etc/harvester"),
+        )
+        .unwrap();
+
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].metadata_prefix.as_deref(), Some("oai_ead"));
+        assert_eq!(
+            sources[0].rules.as_deref(),
+            Some(Path::new("/etc/harvester/rules/a.txt"))
+        );
+        assert_eq!(sources[0].oai_timeout, Some(300));
+        assert_eq!(sources[0].concurrency, Some(4));
+        assert_eq!(sources[0].requests_per_second, Some(0.5));
+        assert_eq!(sources[0].keep_versions, Some(5));
+        assert_eq!(sources[0].payload_format, Some(PayloadFormat::Gzip));
+        assert_eq!(
+            sources[0].schema.as_deref(),
+            Some(Path::new("/etc/harvester/ead.xsd"))
+        );
+        assert_eq!(sources[0].max_payload_bytes, Some(1_048_576));
+        assert_eq!(sources[0].mode, Some(HarvestMode::ListRecords));
+        assert_eq!(sources[1].metadata_prefix, None);
+        assert_eq!(sources[1].rules, None);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_empty_files() {
+        let unknown = parse(
+            "[[source]]\nendpoint = \"https://a.example.org/oai\"\ntimeout = 5\n",
+            Path::new(""),
+        );
+        assert!(unknown.is_err());
+        assert!(parse("", Path::new("")).is_err());
+        let stalled = parse(
+            "[[source]]\nendpoint = \"https://a.example.org/oai\"\nrequests_per_second = 0.0\n",
+            Path::new(""),
+        );
+        assert!(stalled.is_err());
+        let idle = parse(
+            "[[source]]\nendpoint = \"https://a.example.org/oai\"\nconcurrency = 0\n",
+            Path::new(""),
+        );
+        assert!(idle.is_err());
+    }
+}
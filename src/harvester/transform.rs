@@ -0,0 +1,257 @@
+//! Per-rule value transforms for metadata rules, applied in order to the
+//! values a rule extracted from a record.
+
+use std::fmt;
+use std::sync::LazyLock;
+
+use regex_automata::meta::Regex;
+
+/// Separator for `join` when the rule gives none.
+const DEFAULT_JOIN: &str = "; ";
+
+/// One step of a rule's transform pipeline, written in the rules file as
+/// `normalize`, `trim`, `lowercase`, `regex:<pattern>`, `dedupe`, `first`,
+/// `join[:<separator>]` or `date`.
+#[derive(Clone)]
+pub enum Transform {
+    /// Collapse runs of whitespace to one space, and trim.
+    Normalize,
+    Trim,
+    Lowercase,
+    /// Keep the first capture group of the first match (the whole match
+    /// without groups); values that do not match are dropped.
+    Regex(Regex),
+    /// Drop repeated values, keeping the first of each.
+    Dedupe,
+    /// Keep only the first value.
+    First,
+    /// Join all values into one.
+    Join(String),
+    /// Normalize a date or date range to ISO 8601 (`1950/1960`); values that
+    /// are not recognizable dates are dropped.
+    Date,
+}
+
+impl Transform {
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let (name, argument) = match spec.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument)),
+            None => (spec.trim(), None),
+        };
+        let transform = match (name, argument) {
+            ("normalize", None) => Self::Normalize,
+            ("trim", None) => Self::Trim,
+            ("lowercase", None) => Self::Lowercase,
+            ("regex", Some(pattern)) => Self::Regex(
+                Regex::new(pattern)
+                    .map_err(|e| anyhow::anyhow!("invalid regex '{pattern}': {e}"))?,
+            ),
+            ("dedupe", None) => Self::Dedupe,
+            ("first", None) => Self::First,
+            ("join", separator) => Self::Join(separator.unwrap_or(DEFAULT_JOIN).to_string()),
+            ("date", None) => Self::Date,
+            ("regex", None) => anyhow::bail!("regex needs a pattern (regex:<pattern>)"),
+            _ => anyhow::bail!("unknown transform '{spec}'"),
+        };
+        Ok(transform)
+    }
+
+    pub fn apply(&self, values: Vec<String>) -> Vec<String> {
+        match self {
+            Self::Normalize => values
+                .into_iter()
+                .map(|value| value.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect(),
+            Self::Trim => values
+                .into_iter()
+                .map(|value| value.trim().to_string())
+                .collect(),
+            Self::Lowercase => values
+                .into_iter()
+                .map(|value| value.to_lowercase())
+                .collect(),
+            Self::Regex(regex) => values
+                .into_iter()
+                .filter_map(|value| capture(regex, &value))
+                .collect(),
+            Self::Dedupe => {
+                let mut kept: Vec<String> = Vec::with_capacity(values.len());
+                for value in values {
+                    if !kept.contains(&value) {
+                        kept.push(value);
+                    }
+                }
+                kept
+            }
+            Self::First => values.into_iter().take(1).collect(),
+            Self::Join(_) if values.is_empty() => Vec::new(),
+            Self::Join(separator) => vec![values.join(separator)],
+            Self::Date => values
+                .into_iter()
+                .filter_map(|value| iso_date(&value))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Debug for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Normalize => write!(f, "normalize"),
+            Self::Trim => write!(f, "trim"),
+            Self::Lowercase => write!(f, "lowercase"),
+            Self::Regex(_) => write!(f, "regex"),
+            Self::Dedupe => write!(f, "dedupe"),
+            Self::First => write!(f, "first"),
+            Self::Join(separator) => write!(f, "join:{separator}"),
+            Self::Date => write!(f, "date"),
+        }
+    }
+}
+
+/// The first group of `regex`'s first match in `value` (the whole match
+/// when the pattern has no groups).
+fn capture(regex: &Regex, value: &str) -> Option<String> {
+    let mut captures = regex.create_captures();
+    regex.captures(value, &mut captures);
+    let span = captures
+        .get_group(1)
+        .or_else(|| captures.get_match().map(|m| m.span()))?;
+    Some(value[span.range()].to_string())
+}
+
+/// A date (`1950`, `1950-06`, `1950-06-01`), decade (`1950s`) or range of
+/// them (`1950-1960`, `1950 to 1960`, `1950/1960`), optionally preceded by
+/// `circa`/`ca.`/`c.`.
+static DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?ix)
+        ^(?:circa|ca\.?|c\.)?\s*
+        (?P<start>\d{4}(?:-\d{2}(?:-\d{2})?)?)(?P<start_decade>s)?
+        (?:\s*(?:-|–|—|/|to)\s*
+           (?P<end>\d{4}(?:-\d{2}(?:-\d{2})?)?)(?P<end_decade>s)?)?
+        \s*$",
+    )
+    .expect("date pattern compiles")
+});
+
+/// `value` as an ISO 8601 date or `start/end` range, if it is one of the
+/// forms `DATE` accepts and its parts are valid and in order.
+pub(super) fn iso_date(value: &str) -> Option<String> {
+    let value = value.trim();
+    let mut captures = DATE.create_captures();
+    DATE.captures(value, &mut captures);
+    let group = |name: &str| {
+        captures
+            .get_group_by_name(name)
+            .map(|span| &value[span.range()])
+    };
+
+    let start = group("start")?;
+    let start_decade = match group("start_decade") {
+        Some(_) => Some(decade_end(start)?),
+        None => None,
+    };
+    let end = match (group("end"), group("end_decade")) {
+        (Some(end), Some(_)) => Some(decade_end(end)?),
+        (Some(end), None) => Some(end.to_string()),
+        (None, _) => start_decade,
+    };
+
+    if !valid_date(start) || end.as_deref().is_some_and(|end| !valid_date(end)) {
+        return None;
+    }
+    match end {
+        Some(end) if end.as_str() < start => None,
+        Some(end) if end != start => Some(format!("{start}/{end}")),
+        _ => Some(start.to_string()),
+    }
+}
+
+/// The last year of the decade starting at `year` (`1950` -> `1959`).
+fn decade_end(year: &str) -> Option<String> {
+    (year.len() == 4 && year.ends_with('0')).then(|| format!("{}9", &year[..3]))
+}
+
+/// Whether a `YYYY[-MM[-DD]]` date has a plausible month and day.
+fn valid_date(date: &str) -> bool {
+    let mut parts = date.split('-').skip(1).map(|part| part.parse::<u32>());
+    let month_ok = parts
+        .next()
+        .is_none_or(|m| m.is_ok_and(|m| (1..=12).contains(&m)));
+    let day_ok = parts
+        .next()
+        .is_none_or(|d| d.is_ok_and(|d| (1..=31).contains(&d)));
+    month_ok && day_ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(specs: &[&str], values: &[&str]) -> Vec<String> {
+        specs
+            .iter()
+            .map(|spec| Transform::parse(spec).unwrap())
+            .fold(
+                values.iter().map(|v| v.to_string()).collect(),
+                |values, transform| transform.apply(values),
+            )
+    }
+
+    #[test]
+    fn applies_transforms_in_order() {
+        assert_eq!(
+            run(
+                &["normalize", "lowercase", "dedupe"],
+                &["  Oral\n   History ", "oral history", "Maps"]
+            ),
+            vec!["oral history", "maps"]
+        );
+        assert_eq!(run(&["trim", "first"], &[" a ", "b"]), vec!["a"]);
+        assert_eq!(run(&["join:, "], &["a", "b"]), vec!["a, b"]);
+        assert_eq!(run(&["join"], &["a", "b"]), vec!["a; b"]);
+        assert!(run(&["join"], &[]).is_empty());
+        assert_eq!(
+            run(
+                &[r"regex:^MSS(\d+)", "dedupe"],
+                &["MSS54321", "ark:/1", "MSS54321"]
+            ),
+            vec!["54321"]
+        );
+        assert_eq!(run(&[r"regex:\d{4}"], &["ca. 1950"]), vec!["1950"]);
+    }
+
+    #[test]
+    fn normalizes_dates_to_iso_8601_ranges() {
+        for (input, expected) in [
+            ("1950-1960", Some("1950/1960")),
+            ("1950 - 1960", Some("1950/1960")),
+            ("1950 to 1960", Some("1950/1960")),
+            ("1950/1960", Some("1950/1960")),
+            ("1950–1960", Some("1950/1960")),
+            ("1950", Some("1950")),
+            ("1950-1950", Some("1950")),
+            ("1950-06", Some("1950-06")),
+            ("1950-06-01/1951-02-28", Some("1950-06-01/1951-02-28")),
+            ("circa 1950", Some("1950")),
+            ("ca. 1950-1960", Some("1950/1960")),
+            ("1950s", Some("1950/1959")),
+            ("1950s-1970s", Some("1950/1979")),
+            ("1960-1950", None),
+            ("1950-13", None),
+            ("1955s", None),
+            ("undated", None),
+        ] {
+            assert_eq!(iso_date(input).as_deref(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_or_incomplete_transforms() {
+        assert!(Transform::parse("uppercase").is_err());
+        assert!(Transform::parse("regex").is_err());
+        assert!(Transform::parse("regex:(").is_err());
+        assert!(Transform::parse("trim:x").is_err());
+    }
+}
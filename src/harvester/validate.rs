@@ -0,0 +1,311 @@
+//! Payload validation before a download is stored: well-formedness always,
+//! and XSD validation (via `xmllint`) when a schema is configured.
+
+use std::fmt;
+use std::path::Path;
+use std::process::Stdio;
+
+use quick_xml::{Reader, escape, events::Event};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Where (1-based) and why a payload is invalid. `column` is unknown for
+/// schema errors, which `xmllint` reports by line only.
+#[derive(Debug, PartialEq)]
+pub(super) struct ValidationError {
+    pub(super) line: usize,
+    pub(super) column: Option<usize>,
+    pub(super) message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}", self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ", column {column}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Check that `text` is a well-formed XML document: one root element, tags
+/// closed in order, attributes well-formed and entity references defined
+/// (when there is no DTD to define more). Returns the number of EAD
+/// components (`<c>`, `<c01>`..`<c12>`) counted on the way.
+pub(super) fn check_well_formed(text: &str) -> Result<usize, ValidationError> {
+    let mut reader = Reader::from_str(text);
+    // Open elements (name, offset), innermost last.
+    let mut open: Vec<(String, u64)> = Vec::new();
+    let mut seen_root = false;
+    let mut has_doctype = false;
+    let mut components = 0;
+
+    loop {
+        // Where the event starts, for errors about it.
+        let offset = reader.buffer_position();
+        let event = reader
+            .read_event()
+            .map_err(|e| at_offset(text, reader.error_position(), e.to_string()))?;
+        match event {
+            Event::Start(e) | Event::Empty(e) if open.is_empty() && seen_root => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                return Err(at_offset(
+                    text,
+                    offset,
+                    format!("element <{name}> follows the root element"),
+                ));
+            }
+            Event::Start(e) => {
+                check_attributes(text, offset, &e)?;
+                components += usize::from(is_component(e.local_name().as_ref()));
+                open.push((
+                    String::from_utf8_lossy(e.name().as_ref()).into_owned(),
+                    offset,
+                ));
+                seen_root = true;
+            }
+            Event::Empty(e) => {
+                check_attributes(text, offset, &e)?;
+                components += usize::from(is_component(e.local_name().as_ref()));
+                seen_root = true;
+            }
+            Event::End(_) => {
+                open.pop();
+            }
+            Event::Text(e) if open.is_empty() && !e.iter().all(u8::is_ascii_whitespace) => {
+                return Err(at_offset(text, offset, "text outside the root element"));
+            }
+            Event::GeneralRef(e) if open.is_empty() => {
+                let entity = String::from_utf8_lossy(&e).into_owned();
+                return Err(at_offset(
+                    text,
+                    offset,
+                    format!("reference &{entity}; outside the root element"),
+                ));
+            }
+            Event::GeneralRef(e) if !has_doctype => {
+                let valid = if e.is_char_ref() {
+                    e.resolve_char_ref().is_ok_and(|c| c.is_some())
+                } else {
+                    let entity = String::from_utf8_lossy(&e);
+                    escape::resolve_predefined_entity(&entity).is_some()
+                };
+                if !valid {
+                    let entity = String::from_utf8_lossy(&e).into_owned();
+                    return Err(at_offset(
+                        text,
+                        offset,
+                        format!("undefined entity &{entity};"),
+                    ));
+                }
+            }
+            Event::DocType(_) => has_doctype = true,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if let Some((name, offset)) = open.pop() {
+        return Err(at_offset(
+            text,
+            offset,
+            format!("element <{name}> is not closed"),
+        ));
+    }
+    if !seen_root {
+        return Err(at_offset(text, text.len() as u64, "no root element"));
+    }
+    Ok(components)
+}
+
+/// Whether a local name is an EAD component: `c` or numbered `c01`..`c12`.
+fn is_component(name: &[u8]) -> bool {
+    match name {
+        b"c" => true,
+        [b'c', tens, ones] => matches!((tens, ones), (b'0', b'1'..=b'9') | (b'1', b'0'..=b'2')),
+        _ => false,
+    }
+}
+
+fn check_attributes(
+    text: &str,
+    offset: u64,
+    start: &quick_xml::events::BytesStart,
+) -> Result<(), ValidationError> {
+    for attribute in start.attributes().with_checks(true) {
+        attribute.map_err(|e| at_offset(text, offset, e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// A `ValidationError` at byte `offset` of `text`.
+fn at_offset(text: &str, offset: u64, message: impl Into<String>) -> ValidationError {
+    let before = &text.as_bytes()[..(offset as usize).min(text.len())];
+    let line_start = before
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    ValidationError {
+        line: before.iter().filter(|&&b| b == b'\n').count() + 1,
+        column: Some(
+            String::from_utf8_lossy(&before[line_start..])
+                .chars()
+                .count()
+                + 1,
+        ),
+        message: message.into(),
+    }
+}
+
+/// Validate `text` against the XSD at `schema` with `xmllint`.
+pub(super) async fn check_schema(
+    schema: &Path,
+    text: &str,
+) -> anyhow::Result<Result<(), ValidationError>> {
+    let mut child = Command::new("xmllint")
+        .args(["--noout", "--nonet", "--schema"])
+        .arg(schema)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            anyhow::anyhow!("failed to run xmllint (is it installed and on PATH?): {e}")
+        })?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow::anyhow!("failed to open xmllint stdin"))?;
+    let input = text.to_string();
+    let writer = tokio::spawn(async move {
+        // xmllint may exit before reading all input (e.g. a schema error).
+        let _ = stdin.write_all(input.as_bytes()).await;
+    });
+    let output = child.wait_with_output().await?;
+    let _ = writer.await;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match output.status.code() {
+        Some(0) => Ok(Ok(())),
+        // 1: not well-formed, 3: schema validity errors.
+        Some(1 | 3) => Ok(Err(parse_xmllint_error(&stderr))),
+        _ => anyhow::bail!(
+            "xmllint could not validate against {}: {}",
+            schema.display(),
+            stderr.trim()
+        ),
+    }
+}
+
+/// Fail early when `xmllint` is missing or `schema` does not compile.
+pub(super) async fn ensure_schema_usable(schema: &Path) -> anyhow::Result<()> {
+    if !schema.is_file() {
+        anyhow::bail!("schema file {} was not found", schema.display());
+    }
+    // Any document will do: only a schema or tool failure is an error here,
+    // not whether the document is valid.
+    let _ = check_schema(schema, "<schema-check/>").await?;
+    Ok(())
+}
+
+/// The first error in `xmllint` stderr, e.g. `-:12: Schemas validity error :
+/// Element 'x': This element is not expected.` (older versions put
+/// `element x:` before `Schemas`).
+fn parse_xmllint_error(stderr: &str) -> ValidationError {
+    let first = stderr.lines().next().unwrap_or_default();
+    let located = first.strip_prefix("-:").and_then(|rest| {
+        let (line, message) = rest.split_once(':')?;
+        Some((line.parse().ok()?, message.trim()))
+    });
+    let Some((line, message)) = located else {
+        return ValidationError {
+            line: 1,
+            column: None,
+            message: first.trim().to_string(),
+        };
+    };
+    let message = message
+        .split_once("validity error :")
+        .map_or(message, |(_, detail)| detail)
+        .trim();
+    ValidationError {
+        line,
+        column: None,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        check_well_formed(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn accepts_well_formed_documents() {
+        for text in [
+            "<ead/>",
+            "<?xml version=\"1.0\"?>\n<ead a=\"1\"><did>&amp;&#233;</did></ead>\n",
+            "<!DOCTYPE ead [<!ENTITY x \"y\">]><ead>&x;</ead>",
+            "<!-- before --><ead><![CDATA[<not markup>]]></ead><!-- after -->",
+        ] {
+            assert_eq!(check_well_formed(text), Ok(0), "{text}");
+        }
+    }
+
+    #[test]
+    fn counts_components() {
+        let text = "<ead xmlns=\"urn:isbn:1-931666-22-9\"><archdesc><dsc>\
+                    <c01><c02><c03/></c02><c02/></c01><c01/>\
+                    <ead:c xmlns:ead=\"urn:isbn:1-931666-22-9\"><c/></ead:c>\
+                    <c00/><c13/><container/></dsc></archdesc></ead>";
+        assert_eq!(check_well_formed(text), Ok(7));
+    }
+
+    #[test]
+    fn reports_the_line_and_column_of_malformed_xml() {
+        assert!(
+            error("<ead>\n  <did>\n  </dsc>\n</ead>").starts_with("line 3, column 3: "),
+            "{}",
+            error("<ead>\n  <did>\n  </dsc>\n</ead>")
+        );
+        assert_eq!(
+            error("<ead>\n  <did>"),
+            "line 2, column 3: element <did> is not closed"
+        );
+        assert_eq!(
+            error("<ead>\n <p>&nbsp;</p></ead>"),
+            "line 2, column 5: undefined entity &nbsp;"
+        );
+        assert_eq!(
+            error("<ead/>\n<ead/>"),
+            "line 2, column 1: element <ead> follows the root element"
+        );
+        assert!(error("<ead a=\"1\" a=\"2\"/>").starts_with("line 1, column 1: "));
+        assert_eq!(error(""), "line 1, column 1: no root element");
+    }
+
+    #[test]
+    fn parses_xmllint_schema_errors() {
+        assert_eq!(
+            parse_xmllint_error(
+                "-:2: Schemas validity error : Element 'archdesc': This element is not \
+                 expected.\n- fails to validate\n"
+            ),
+            ValidationError {
+                line: 2,
+                column: None,
+                message: "Element 'archdesc': This element is not expected.".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_xmllint_error("-:7: element did: Schemas validity error : Element 'did': x")
+                .to_string(),
+            "line 7: Element 'did': x"
+        );
+    }
+}
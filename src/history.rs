@@ -0,0 +1,469 @@
+//! `record diff`: compare two stored payload versions of a record, as an
+//! XML-aware line diff of the payloads and a field diff of their extracted
+//! metadata.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Args;
+use quick_xml::{Reader, escape, events::BytesStart, events::Event};
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+
+use crate::db::history::{self, RecordVersion};
+use crate::expand_path;
+use crate::oai::OaiRecord;
+use crate::payload;
+use crate::storage::{Storage, StorageArgs};
+
+/// Unchanged lines shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// Largest changed region (old lines x new lines) diffed line by line; past
+/// this the whole region is shown as replaced.
+const MAX_DIFF_CELLS: usize = 16_000_000;
+
+#[derive(Debug, Args)]
+pub struct RecordDiffArgs {
+    /// OAI record identifier
+    pub identifier: String,
+
+    /// OAI endpoint url (needed when the identifier is harvested from several)
+    #[arg(long)]
+    pub endpoint: Option<String>,
+
+    /// OAI metadata prefix (needed when the identifier is harvested in several)
+    #[arg(short, long)]
+    pub metadata_prefix: Option<String>,
+
+    /// Base directory for downloads
+    #[arg(short, long, default_value = "data", env = "DATA_DIR")]
+    pub dir: PathBuf,
+
+    #[command(flatten)]
+    pub storage: StorageArgs,
+
+    /// Version to diff from (default: the stored version before --to)
+    #[arg(long)]
+    pub from: Option<i32>,
+
+    /// Version to diff to (default: the newest stored version)
+    #[arg(long)]
+    pub to: Option<i32>,
+}
+
+/// Print the payload and field differences between two stored versions of a
+/// record.
+pub async fn record_diff(cfg: RecordDiffArgs, pool: Pool<Postgres>) -> anyhow::Result<()> {
+    let mut records = history::find_records(
+        &pool,
+        &cfg.identifier,
+        cfg.endpoint.as_deref(),
+        cfg.metadata_prefix.as_deref(),
+    )
+    .await?;
+    let scoped = match records.len() {
+        0 => anyhow::bail!("no record {}", cfg.identifier),
+        1 => records.remove(0),
+        _ => {
+            let scopes: Vec<_> = records
+                .iter()
+                .map(|r| format!("{} ({})", r.endpoint, r.metadata_prefix))
+                .collect();
+            anyhow::bail!(
+                "{} is harvested from more than one scope, pass --endpoint and/or \
+                 --metadata-prefix: {}",
+                cfg.identifier,
+                scopes.join(", ")
+            );
+        }
+    };
+    let record = &scoped.record;
+
+    let versions = history::versions(&pool, record.id).await?;
+    let (from, to) = select_versions(&versions, cfg.from, cfg.to)?;
+
+    let storage = cfg.storage.config(expand_path(&cfg.dir))?.open();
+
+    println!(
+        "--- {} v{} (datestamp {}, stored {})",
+        record.identifier, from.version, from.datestamp, from.stored_at
+    );
+    println!(
+        "+++ {} v{} (datestamp {}, stored {})",
+        record.identifier, to.version, to.datestamp, to.stored_at
+    );
+
+    if from.content_hash == to.content_hash {
+        println!("Payloads are identical");
+    } else {
+        let old = normalize_xml(&read(storage.as_ref(), record, from).await?)
+            .with_context(|| format!("v{} is not well-formed XML", from.version))?;
+        let new = normalize_xml(&read(storage.as_ref(), record, to).await?)
+            .with_context(|| format!("v{} is not well-formed XML", to.version))?;
+        let lines = render(&diff_lines(&old, &new), CONTEXT_LINES);
+        if lines.is_empty() {
+            println!("Payloads differ only in formatting");
+        }
+        for line in lines {
+            println!("{line}");
+        }
+    }
+
+    println!();
+    match (&from.metadata, &to.metadata) {
+        (Some(old), Some(new)) => {
+            let changes = diff_fields(old, new);
+            if changes.is_empty() {
+                println!("Fields: unchanged");
+            } else {
+                println!("Fields:");
+                for change in changes {
+                    println!("{change}");
+                }
+            }
+        }
+        _ => println!("Fields: not compared (metadata is only kept once a version is parsed)"),
+    }
+
+    Ok(())
+}
+
+/// The stored payload of one version, decoded.
+async fn read(
+    storage: &dyn Storage,
+    record: &OaiRecord,
+    version: &RecordVersion,
+) -> anyhow::Result<String> {
+    let key = record.version_path(version.version, version.payload_format);
+    let stored = storage
+        .get(&key)
+        .await
+        .and_then(|stored| stored.context("not found"))
+        .with_context(|| format!("Failed to read payload version {}", storage.describe(&key)))?;
+    Ok(payload::decode(stored, version.payload_format)?)
+}
+
+/// Resolve `--from`/`--to` against the stored versions (oldest first).
+fn select_versions(
+    versions: &[RecordVersion],
+    from: Option<i32>,
+    to: Option<i32>,
+) -> anyhow::Result<(&RecordVersion, &RecordVersion)> {
+    let stored = || {
+        versions
+            .iter()
+            .map(|v| format!("v{}", v.version))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let find = |wanted: i32| {
+        versions
+            .iter()
+            .find(|v| v.version == wanted)
+            .with_context(|| format!("v{wanted} is not stored (stored: {})", stored()))
+    };
+
+    let to = match to {
+        Some(version) => find(version)?,
+        None => versions.last().context("no stored versions")?,
+    };
+    let from = match from {
+        Some(version) => find(version)?,
+        None => versions
+            .iter()
+            .rev()
+            .find(|v| v.version < to.version)
+            .with_context(|| {
+                format!(
+                    "no stored version before v{} (stored: {})",
+                    to.version,
+                    stored()
+                )
+            })?,
+    };
+    Ok((from, to))
+}
+
+/// One line per start tag, end tag and run of text, indented by depth, with
+/// attributes sorted and text whitespace collapsed, so a line diff shows
+/// content changes rather than formatting. Comments, processing instructions
+/// and the declaration are dropped.
+fn normalize_xml(xml: &str) -> anyhow::Result<Vec<String>> {
+    let mut reader = Reader::from_str(xml);
+    let mut lines = Vec::new();
+    let mut text = String::new();
+    let mut depth = 0usize;
+
+    let flush = |text: &mut String, lines: &mut Vec<String>, depth: usize| {
+        let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !collapsed.is_empty() {
+            lines.push(format!("{}{collapsed}", "  ".repeat(depth)));
+        }
+        text.clear();
+    };
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                flush(&mut text, &mut lines, depth);
+                lines.push(format!("{}<{}>", "  ".repeat(depth), tag(&e)?));
+                depth += 1;
+            }
+            Event::Empty(e) => {
+                flush(&mut text, &mut lines, depth);
+                lines.push(format!("{}<{}/>", "  ".repeat(depth), tag(&e)?));
+            }
+            Event::End(e) => {
+                flush(&mut text, &mut lines, depth);
+                depth = depth.saturating_sub(1);
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                lines.push(format!("{}</{name}>", "  ".repeat(depth)));
+            }
+            Event::Text(e) => text.push_str(&e.decode()?),
+            Event::CData(e) => text.push_str(&String::from_utf8_lossy(&e)),
+            Event::GeneralRef(e) => {
+                let entity = e.decode()?;
+                match escape::resolve_predefined_entity(&entity) {
+                    Some(resolved) => text.push_str(resolved),
+                    None => text.push_str(&format!("&{entity};")),
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    flush(&mut text, &mut lines, depth);
+    Ok(lines)
+}
+
+/// `name a="1" b="2"`, attributes sorted by name.
+fn tag(start: &BytesStart) -> anyhow::Result<String> {
+    let mut attributes = start
+        .attributes()
+        .map(|attribute| {
+            let attribute = attribute?;
+            Ok(format!(
+                "{}=\"{}\"",
+                String::from_utf8_lossy(attribute.key.as_ref()),
+                String::from_utf8_lossy(&attribute.value)
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    attributes.sort();
+
+    let mut tag = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    for attribute in attributes {
+        tag.push(' ');
+        tag.push_str(&attribute);
+    }
+    Ok(tag)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Longest-common-subsequence line diff. The common prefix and suffix are
+/// matched directly; a changed region too large to diff within
+/// `MAX_DIFF_CELLS` is reported as removed then added.
+fn diff_lines<'a>(old: &'a [String], new: &'a [String]) -> Vec<DiffLine<'a>> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut lines: Vec<_> = old[..prefix]
+        .iter()
+        .map(|line| DiffLine::Same(line))
+        .collect();
+
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_DIFF_CELLS {
+        lines.extend(old_mid.iter().map(|line| DiffLine::Removed(line)));
+        lines.extend(new_mid.iter().map(|line| DiffLine::Added(line)));
+    } else {
+        // lcs[i][j]: common subsequence length of old_mid[i..] and new_mid[j..].
+        let width = new_mid.len() + 1;
+        let mut lcs = vec![0u32; (old_mid.len() + 1) * width];
+        for i in (0..old_mid.len()).rev() {
+            for j in (0..new_mid.len()).rev() {
+                lcs[i * width + j] = if old_mid[i] == new_mid[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < old_mid.len() && j < new_mid.len() {
+            if old_mid[i] == new_mid[j] {
+                lines.push(DiffLine::Same(&old_mid[i]));
+                i += 1;
+                j += 1;
+            } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+                lines.push(DiffLine::Removed(&old_mid[i]));
+                i += 1;
+            } else {
+                lines.push(DiffLine::Added(&new_mid[j]));
+                j += 1;
+            }
+        }
+        lines.extend(old_mid[i..].iter().map(|line| DiffLine::Removed(line)));
+        lines.extend(new_mid[j..].iter().map(|line| DiffLine::Added(line)));
+    }
+
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| DiffLine::Same(line)),
+    );
+    lines
+}
+
+/// Changed lines prefixed `-`/`+`, with `context` unchanged lines around
+/// each change and `...` between hunks. Empty when nothing changed.
+fn render(lines: &[DiffLine], context: usize) -> Vec<String> {
+    let changed: Vec<_> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Same(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut output = Vec::new();
+    let mut shown_until = 0;
+    for &index in &changed {
+        let start = index.saturating_sub(context).max(shown_until);
+        if start > shown_until {
+            output.push("...".to_string());
+        }
+        let end = (index + context + 1).min(lines.len());
+        for line in &lines[start..end] {
+            output.push(match line {
+                DiffLine::Same(text) => format!("  {text}"),
+                DiffLine::Removed(text) => format!("- {text}"),
+                DiffLine::Added(text) => format!("+ {text}"),
+            });
+        }
+        shown_until = shown_until.max(end);
+    }
+    if !changed.is_empty() && shown_until < lines.len() {
+        output.push("...".to_string());
+    }
+    output
+}
+
+/// Per-field changes between two extracted metadata objects, by field name.
+fn diff_fields(old: &Value, new: &Value) -> Vec<String> {
+    let empty = serde_json::Map::new();
+    let old = old.as_object().unwrap_or(&empty);
+    let new = new.as_object().unwrap_or(&empty);
+    let keys: BTreeSet<_> = old.keys().chain(new.keys()).collect();
+
+    keys.into_iter()
+        .filter_map(|key| match (old.get(key), new.get(key)) {
+            (Some(before), Some(after)) if before != after => {
+                Some(format!("~ {key}: {before} -> {after}"))
+            }
+            (Some(before), None) => Some(format!("- {key}: {before}")),
+            (None, Some(after)) => Some(format!("+ {key}: {after}")),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::oai::PayloadFormat;
+
+    fn version(version: i32) -> RecordVersion {
+        RecordVersion {
+            version,
+            datestamp: String::new(),
+            content_hash: String::new(),
+            payload_format: PayloadFormat::Xml,
+            metadata: None,
+            stored_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn normalize_xml_ignores_formatting_and_attribute_order() {
+        let a = normalize_xml(
+            "<?xml version=\"1.0\"?>\n<ead>\n  <did b=\"2\" a=\"1\">\n    Some   <!-- x -->\n    title &amp; more\n  </did>\n  <dsc/>\n</ead>",
+        )
+        .unwrap();
+        let b = normalize_xml("<ead><did a=\"1\" b=\"2\">Some title &amp; more</did><dsc/></ead>")
+            .unwrap();
+        assert_eq!(a, b);
+        assert_eq!(
+            b,
+            vec![
+                "<ead>",
+                "  <did a=\"1\" b=\"2\">",
+                "    Some title & more",
+                "  </did>",
+                "  <dsc/>",
+                "</ead>",
+            ]
+        );
+        assert!(normalize_xml("<ead><did></ead>").is_err());
+    }
+
+    #[test]
+    fn diff_renders_changes_with_context() {
+        let lines = |text: &str| text.split(' ').map(String::from).collect::<Vec<_>>();
+        let old = lines("a b c d e f g h i j");
+        let new = lines("a b c X e f g h i j k");
+
+        assert_eq!(
+            render(&diff_lines(&old, &new), 1),
+            vec!["...", "  c", "- d", "+ X", "  e", "...", "  j", "+ k"]
+        );
+        assert!(render(&diff_lines(&old, &old), 1).is_empty());
+    }
+
+    #[test]
+    fn diff_fields_reports_added_removed_and_changed() {
+        let old = serde_json::json!({"title": ["A"], "date": ["1900"], "extent": ["1 box"]});
+        let new = serde_json::json!({"title": ["B"], "extent": ["1 box"], "creator": ["C"]});
+        assert_eq!(
+            diff_fields(&old, &new),
+            vec![
+                "+ creator: [\"C\"]",
+                "- date: [\"1900\"]",
+                "~ title: [\"A\"] -> [\"B\"]",
+            ]
+        );
+    }
+
+    #[test]
+    fn select_versions_defaults_to_the_newest_two() {
+        let versions = vec![version(1), version(3), version(4)];
+        let pick = |from, to| {
+            select_versions(&versions, from, to).map(|(from, to)| (from.version, to.version))
+        };
+        assert_eq!(pick(None, None).unwrap(), (3, 4));
+        assert_eq!(pick(None, Some(3)).unwrap(), (1, 3));
+        assert_eq!(pick(Some(1), None).unwrap(), (1, 4));
+        assert!(pick(Some(2), None).is_err());
+        assert!(pick(None, Some(1)).is_err());
+    }
+}
@@ -7,6 +7,8 @@ use clap::Args;
 use sqlx::{Pool, Postgres};
 use tracing::info;
 
+use crate::storage::StorageArgs;
+
 use super::{
     ArcLightIndexer,
     config::{ARCLIGHT_METADATA_PREFIX, build_config},
@@ -29,6 +31,10 @@ pub struct ArcLightArgs {
     /// harvested EAD metadata)
     pub source_repository: String,
 
+    /// Only index records in this OAI set (setSpec)
+    #[arg(long)]
+    pub set: Option<String>,
+
     /// Traject configuration file path
     #[arg(short, long, default_value = "traject/ead2_config.rb")]
     pub configuration: PathBuf,
@@ -37,6 +43,9 @@ pub struct ArcLightArgs {
     #[arg(short, long, default_value = "data", env = "DATA_DIR")]
     pub dir: PathBuf,
 
+    #[command(flatten)]
+    pub storage: StorageArgs,
+
     /// Preview mode (show matching records, do not index or delete)
     #[arg(short, long, default_value_t = false)]
     pub preview: bool,
@@ -72,6 +81,12 @@ pub struct ArcLightArgs {
     #[arg(long, default_value_t = 300)]
     pub record_timeout_seconds: u64,
 
+    /// Extra traject timeout per MiB of payload, on top of
+    /// --record-timeout-seconds (default: none; records harvested before
+    /// sizes were recorded get the base timeout)
+    #[arg(long)]
+    pub record_timeout_seconds_per_mib: Option<u64>,
+
     /// Solr commit-within window for delete operations (interval commit strategy)
     #[arg(long, default_value_t = 10000)]
     pub solr_commit_within_ms: u64,
@@ -88,7 +103,8 @@ pub async fn index(
 ) -> anyhow::Result<()> {
     info!("Indexing records into {}", cfg.repository);
 
-    let scope = OaiScope::new(cfg.oai_endpoint.clone(), ARCLIGHT_METADATA_PREFIX);
+    let scope =
+        OaiScope::new(cfg.oai_endpoint.clone(), ARCLIGHT_METADATA_PREFIX).with_set(cfg.set.clone());
 
     if cfg.reindex {
         let result = db::indexer::reindex(&pool, &scope, &cfg.source_repository).await?;
@@ -5,6 +5,7 @@ use std::{
 };
 
 use crate::expand_path;
+use crate::storage::StorageConfig;
 
 use super::cli::ArcLightArgs;
 
@@ -13,10 +14,12 @@ pub const ARCLIGHT_METADATA_PREFIX: &str = "oai_ead";
 #[derive(Debug, Clone)]
 pub struct ArcLightIndexerConfig {
     pub configuration: PathBuf,
-    pub dir: PathBuf,
+    pub storage: StorageConfig,
     pub repository: String,
     pub repository_file: PathBuf,
     pub record_timeout_seconds: u64,
+    /// Extra traject time per MiB of payload (`None` = a flat timeout).
+    pub record_timeout_seconds_per_mib: Option<u64>,
     pub solr_url: String,
     pub solr_commit_within_ms: u64,
     pub solr_no_commit: bool,
@@ -24,15 +27,16 @@ pub struct ArcLightIndexerConfig {
 
 pub fn build_config(cfg: ArcLightArgs) -> anyhow::Result<ArcLightIndexerConfig> {
     ensure_traject_available()?;
-    let (configuration, data_dir) = resolve_paths(&cfg)?;
+    let (configuration, storage) = resolve_paths(&cfg)?;
     let repository_file = generate_repository_file(&cfg.repository, &cfg.source_repository)?;
 
     Ok(ArcLightIndexerConfig {
         configuration,
-        dir: data_dir,
+        storage,
         repository: cfg.repository,
         repository_file,
         record_timeout_seconds: cfg.record_timeout_seconds,
+        record_timeout_seconds_per_mib: cfg.record_timeout_seconds_per_mib,
         solr_url: cfg.solr_url,
         solr_commit_within_ms: cfg.solr_commit_within_ms,
         solr_no_commit: cfg.no_commit,
@@ -56,19 +60,21 @@ fn ensure_traject_available() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn resolve_paths(cfg: &ArcLightArgs) -> anyhow::Result<(PathBuf, PathBuf)> {
+fn resolve_paths(cfg: &ArcLightArgs) -> anyhow::Result<(PathBuf, StorageConfig)> {
     let configuration = path::absolute(expand_path(&cfg.configuration))?;
-    let data_dir = path::absolute(expand_path(&cfg.dir))?;
+    let storage = cfg.storage.config(path::absolute(expand_path(&cfg.dir))?)?;
 
     if !configuration.is_file() {
         anyhow::bail!("traject configuration was not found");
     }
 
-    if !data_dir.is_dir() {
+    if let StorageConfig::Local(data_dir) = &storage
+        && !data_dir.is_dir()
+    {
         anyhow::bail!("base directory was not found");
     }
 
-    Ok((configuration, data_dir))
+    Ok((configuration, storage))
 }
 
 fn generate_repository_file(repository: &str, name: &str) -> anyhow::Result<PathBuf> {
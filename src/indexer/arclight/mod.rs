@@ -2,8 +2,10 @@ pub mod cli;
 pub mod config;
 
 use std::{
-    io,
-    process::{Output, Stdio},
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::{self, Output, Stdio},
+    sync::Arc,
     time::Duration,
 };
 
@@ -13,25 +15,28 @@ use tokio::io::AsyncReadExt;
 use tokio::process::Command;
 use tokio::time::timeout;
 
-use crate::{OaiRecord, indexer::Indexer};
+use crate::{OaiRecord, indexer::Indexer, oai::PayloadFormat, payload, storage::Storage};
 
 use config::ArcLightIndexerConfig;
 
 pub struct ArcLightIndexer {
     config: ArcLightIndexerConfig,
     client: Client,
+    storage: Arc<dyn Storage>,
 }
 
 impl ArcLightIndexer {
     pub fn new(config: ArcLightIndexerConfig) -> Self {
+        let storage = config.storage.open();
         Self {
             config,
             client: Client::new(),
+            storage,
         }
     }
 
     async fn run_traject(&self, record: &OaiRecord) -> anyhow::Result<Output> {
-        let path = self.config.dir.join(record.path());
+        let input = TrajectInput::prepare(self.storage.as_ref(), record).await?;
 
         let mut child = Command::new("traject")
             .arg("-i")
@@ -44,7 +49,7 @@ impl ArcLightIndexer {
             .arg(format!("id={}", record.fingerprint))
             .arg("-u")
             .arg(&self.config.solr_url)
-            .arg(path)
+            .arg(input.path())
             .env("REPOSITORY_FILE", &self.config.repository_file)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -70,7 +75,8 @@ impl ArcLightIndexer {
             Ok::<Vec<u8>, io::Error>(buf)
         });
 
-        let status = match timeout(self.timeout_duration(), child.wait()).await {
+        let limit = self.traject_timeout(record);
+        let status = match timeout(limit, child.wait()).await {
             Ok(Ok(status)) => status,
             Ok(Err(error)) => {
                 let _ = stdout_task.await;
@@ -82,10 +88,7 @@ impl ArcLightIndexer {
                 let _ = child.wait().await;
                 let _ = stdout_task.await;
                 let _ = stderr_task.await;
-                anyhow::bail!(
-                    "traject timed out after {}s",
-                    self.config.record_timeout_seconds
-                );
+                anyhow::bail!("traject timed out after {}s", limit.as_secs());
             }
         };
 
@@ -148,6 +151,79 @@ impl ArcLightIndexer {
     fn timeout_duration(&self) -> Duration {
         Duration::from_secs(self.config.record_timeout_seconds)
     }
+
+    /// The timeout for traject on `record`, scaled by its payload size when
+    /// `record_timeout_seconds_per_mib` is set.
+    fn traject_timeout(&self, record: &OaiRecord) -> Duration {
+        scaled_timeout(
+            self.config.record_timeout_seconds,
+            self.config.record_timeout_seconds_per_mib,
+            record.payload_bytes,
+        )
+    }
+}
+
+/// `base_seconds`, plus `per_mib` seconds for each MiB (pro rata) of a
+/// payload of `payload_bytes`. Unknown sizes get the base.
+fn scaled_timeout(base_seconds: u64, per_mib: Option<u64>, payload_bytes: Option<i64>) -> Duration {
+    let base = Duration::from_secs(base_seconds);
+    let (Some(per_mib), Some(bytes)) = (per_mib, payload_bytes) else {
+        return base;
+    };
+    let mib = bytes.max(0) as f64 / (1024.0 * 1024.0);
+    base + Duration::from_secs(per_mib).mul_f64(mib)
+}
+
+/// The file traject reads: the stored payload itself when it is a plain XML
+/// file on local storage, otherwise a decoded copy in the temp dir (removed
+/// on drop).
+enum TrajectInput {
+    Stored(PathBuf),
+    Copied(PathBuf),
+}
+
+impl TrajectInput {
+    async fn prepare(storage: &dyn Storage, record: &OaiRecord) -> anyhow::Result<Self> {
+        let key = record.path();
+        let format = record.payload_format;
+        if format == PayloadFormat::Xml
+            && let Some(path) = storage.local_path(&key)
+        {
+            return Ok(Self::Stored(path));
+        }
+
+        let stored = storage
+            .get(&key)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No payload at {}", storage.describe(&key)))?;
+        let target = env::temp_dir().join(format!(
+            "harvester-traject-{}-{}.xml",
+            record.fingerprint,
+            process::id()
+        ));
+        // Constructed first so a partial copy is cleaned up too.
+        let input = Self::Copied(target.clone());
+        tokio::task::spawn_blocking(move || {
+            let mut reader = payload::decoder(stored, format)?;
+            io::copy(&mut reader, &mut fs::File::create(&target)?)
+        })
+        .await??;
+        Ok(input)
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            Self::Stored(path) | Self::Copied(path) => path,
+        }
+    }
+}
+
+impl Drop for TrajectInput {
+    fn drop(&mut self) {
+        if let Self::Copied(path) = self {
+            let _ = fs::remove_file(path);
+        }
+    }
 }
 
 /// Build the Solr `/update` delete-by-query body for a root fingerprint.
@@ -223,7 +299,9 @@ impl Indexer for ArcLightIndexer {
 
 #[cfg(test)]
 mod tests {
-    use super::{delete_by_root_payload, strip_ruby_logger_noise};
+    use std::time::Duration;
+
+    use super::{delete_by_root_payload, scaled_timeout, strip_ruby_logger_noise};
 
     #[test]
     fn delete_payload_includes_commit_within_by_default() {
@@ -241,6 +319,27 @@ mod tests {
         assert!(delete.get("commitWithin").is_none());
     }
 
+    #[test]
+    fn scales_the_timeout_by_payload_size_when_configured() {
+        let mib = 1024 * 1024;
+        assert_eq!(
+            scaled_timeout(300, None, Some(10 * mib)),
+            Duration::from_secs(300)
+        );
+        assert_eq!(
+            scaled_timeout(300, Some(30), None),
+            Duration::from_secs(300)
+        );
+        assert_eq!(
+            scaled_timeout(300, Some(30), Some(10 * mib)),
+            Duration::from_secs(600)
+        );
+        assert_eq!(
+            scaled_timeout(300, Some(30), Some(mib / 2)),
+            Duration::from_secs(315)
+        );
+    }
+
     #[test]
     fn drops_info_and_debug_banner_lines() {
         let stderr = "I, [2026-07-07T03:16:33 #15]  INFO -- : traject (3.8.3) executing\n\
@@ -102,6 +102,7 @@ impl<T: Indexer> IndexRunner<T> {
             imported: stats.indexed,
             deleted: stats.deleted,
             failed: stats.failed,
+            ..RunStats::default()
         };
         if let Err(error) =
             runs::finish(&self.pool, run_id, outcome, &run_stats, &error_sample).await
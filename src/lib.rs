@@ -1,14 +1,25 @@
 mod batch;
+mod compress;
 pub mod db;
+mod discovery;
+mod gc;
 mod harvester;
+mod history;
 mod indexer;
 pub mod oai;
+mod payload;
 mod report;
+pub mod storage;
 mod summarizer;
+mod verify;
 use std::path::{Path, PathBuf};
 
+pub use compress::{CompressArgs, compress};
+pub use discovery::{DiscoveryArgs, ListFormatsArgs, identify, list_formats, list_sets};
+pub use gc::{GcArgs, gc};
 pub use harvester::cli::{HarvesterArgs, harvest};
-pub use harvester::{Harvester, perform};
+pub use harvester::{DEFAULT_MAX_HARVEST_ATTEMPTS, Harvester, perform};
+pub use history::{RecordDiffArgs, record_diff};
 pub use indexer::arclight::ArcLightIndexer;
 pub use indexer::arclight::cli::{ArcLightArgs, index};
 pub use indexer::arclight::config::{
@@ -17,8 +28,9 @@ pub use indexer::arclight::config::{
 pub use indexer::{
     DEFAULT_MAX_INDEX_ATTEMPTS, IndexRunOptions, IndexRunner, IndexRunnerConfig, IndexSelectionMode,
 };
-pub use oai::{OaiConfig, OaiRecord, OaiScope};
+pub use oai::{HarvestMode, OaiConfig, OaiRecord, OaiScope};
 pub use report::{ReportArgs, report};
+pub use verify::{VerifyArgs, verify};
 
 pub fn expand_path(path: &Path) -> PathBuf {
     PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).as_ref())
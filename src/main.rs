@@ -2,7 +2,10 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use clap::{Parser, Subcommand};
-use harvester::{ArcLightArgs, HarvesterArgs, ReportArgs, db};
+use harvester::{
+    ArcLightArgs, CompressArgs, DiscoveryArgs, GcArgs, HarvesterArgs, ListFormatsArgs,
+    RecordDiffArgs, ReportArgs, VerifyArgs, db,
+};
 use tracing::info;
 
 /// OAI-PMH harvester
@@ -22,12 +25,23 @@ struct Cli {
     command: Commands,
 }
 
-// TODO: identify etc.
 #[derive(Debug, Subcommand)]
 enum Commands {
     #[command(arg_required_else_help = true)]
     Harvest(HarvesterArgs),
 
+    /// Show and store an endpoint's Identify response
+    #[command(arg_required_else_help = true)]
+    Identify(DiscoveryArgs),
+
+    /// Show and store the metadata formats an endpoint disseminates
+    #[command(arg_required_else_help = true)]
+    ListFormats(ListFormatsArgs),
+
+    /// Show and store an endpoint's sets
+    #[command(arg_required_else_help = true)]
+    ListSets(DiscoveryArgs),
+
     /// Index records into a target system
     #[command(subcommand)]
     Index(IndexCommands),
@@ -35,6 +49,26 @@ enum Commands {
     /// Health reports (stale index entries, records missing from the feed)
     #[command(arg_required_else_help = true)]
     Report(ReportArgs),
+
+    /// Inspect a single record
+    #[command(subcommand)]
+    Record(RecordCommands),
+
+    /// Rewrite stored payloads in another format (e.g. compress a data dir)
+    Compress(CompressArgs),
+
+    /// Report (and with --apply remove) data dir files no record needs
+    Gc(GcArgs),
+
+    /// Check stored payloads and requeue missing or corrupt ones for download
+    Verify(VerifyArgs),
+}
+
+#[derive(Debug, Subcommand)]
+enum RecordCommands {
+    /// Diff two stored payload versions of a record (XML and extracted fields)
+    #[command(arg_required_else_help = true)]
+    Diff(RecordDiffArgs),
 }
 
 #[derive(Debug, Subcommand)]
@@ -72,12 +106,33 @@ async fn main() -> anyhow::Result<()> {
         Commands::Harvest(cfg) => {
             harvester::harvest(cfg, pool, shutdown).await?;
         }
+        Commands::Identify(cfg) => {
+            harvester::identify(cfg, pool).await?;
+        }
+        Commands::ListFormats(cfg) => {
+            harvester::list_formats(cfg, pool).await?;
+        }
+        Commands::ListSets(cfg) => {
+            harvester::list_sets(cfg, pool).await?;
+        }
         Commands::Index(IndexCommands::ArcLight(cfg)) => {
             harvester::index(cfg, pool, shutdown).await?;
         }
         Commands::Report(cfg) => {
             harvester::report(cfg, pool).await?;
         }
+        Commands::Record(RecordCommands::Diff(cfg)) => {
+            harvester::record_diff(cfg, pool).await?;
+        }
+        Commands::Compress(cfg) => {
+            harvester::compress(cfg, pool, shutdown).await?;
+        }
+        Commands::Gc(cfg) => {
+            harvester::gc(cfg, pool, shutdown).await?;
+        }
+        Commands::Verify(cfg) => {
+            harvester::verify(cfg, pool, shutdown).await?;
+        }
     }
 
     Ok(())
@@ -1,6 +1,6 @@
 use std::fmt;
 
-use super::status::OaiRecordStatus;
+use super::status::{FailureCategory, OaiRecordStatus, PayloadFormat};
 
 /// Events that drive single-record `oai_records.status` transitions.
 ///
@@ -8,10 +8,42 @@ use super::status::OaiRecordStatus;
 /// db functions rather than enum variants.
 #[derive(Debug)]
 pub enum HarvestEvent<'a> {
-    DownloadSucceeded,
-    DownloadFailed { message: &'a str },
-    MetadataExtracted { metadata: serde_json::Value },
-    MetadataFailed { message: &'a str },
+    /// A payload was stored in `payload_format`; `content_hash` is its
+    /// SHA-256 and `about` the record's `<about>` containers.
+    DownloadSucceeded {
+        content_hash: &'a str,
+        payload_format: PayloadFormat,
+        about: &'a [String],
+        size: PayloadSize,
+    },
+    /// The payload hashes the same as the one last stored: the record returns
+    /// to the status it had before the import requeued it. It was rewritten,
+    /// in `payload_format`, with its `about` containers (and size) refreshed.
+    DownloadUnchanged {
+        content_hash: &'a str,
+        payload_format: PayloadFormat,
+        about: &'a [String],
+        size: PayloadSize,
+    },
+    DownloadFailed {
+        message: &'a str,
+        category: FailureCategory,
+    },
+    MetadataExtracted {
+        metadata: serde_json::Value,
+    },
+    MetadataFailed {
+        message: &'a str,
+    },
+}
+
+/// How big a stored payload is, saved on its record.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PayloadSize {
+    /// Length in bytes, before compression.
+    pub bytes: i64,
+    /// EAD components (`<c>`, `<c01>`..`<c12>`).
+    pub components: i32,
 }
 
 /// Events that drive single-record `indexer_records.status` transitions.
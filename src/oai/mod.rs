@@ -2,29 +2,73 @@
 //! events that drive db transitions.
 
 mod events;
+mod protocol;
 mod record;
 mod status;
 
 use std::path::PathBuf;
 
-pub use events::{HarvestEvent, IndexEvent, RecordAction};
+use crate::storage::StorageConfig;
+
+pub use events::{HarvestEvent, IndexEvent, PayloadSize, RecordAction};
+pub use protocol::ProtocolError;
 pub use record::{OaiHeader, OaiRecord};
-pub use status::{OaiIndexStatus, OaiRecordStatus};
+pub use status::{FailureCategory, OaiIndexStatus, OaiRecordStatus, PayloadFormat};
 
 #[derive(Debug, Clone)]
 pub struct OaiConfig {
-    pub data_dir: PathBuf,
+    /// Where payloads are stored.
+    pub storage: StorageConfig,
     pub scope: OaiScope,
     pub oai_timeout: u64,
     pub oai_retries: u32,
+    /// List every record instead of only those changed since the last
+    /// completed harvest.
+    pub full: bool,
+    pub mode: HarvestMode,
+    /// Records downloaded (or stored from a `ListRecords` page) at once; the
+    /// ceiling with `adaptive_concurrency`.
+    pub concurrency: usize,
+    /// Adapt the number of `GetRecord` downloads in flight to the endpoint:
+    /// grow while it responds promptly, halve on timeouts and server errors.
+    pub adaptive_concurrency: bool,
+    /// Cap on OAI requests per second to the endpoint (`None` = unlimited).
+    pub requests_per_second: Option<f64>,
+    /// Payload versions kept per record for `record diff`, the current one
+    /// included (0 keeps no history).
+    pub keep_versions: usize,
+    /// How newly downloaded payloads are written to disk.
+    pub payload_format: PayloadFormat,
+    /// XSD downloaded payloads are validated against (they are always checked
+    /// for well-formedness).
+    pub schema: Option<PathBuf>,
+    /// Payloads larger than this many bytes fail as `oversize` instead of
+    /// being stored (`None` = no limit).
+    pub max_payload_bytes: Option<u64>,
+}
+
+/// How records are discovered and fetched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HarvestMode {
+    /// `ListIdentifiers`, then one `GetRecord` per new or changed record.
+    #[default]
+    ListIdentifiers,
+    /// `ListRecords`: headers and payloads arrive together, page by page.
+    ListRecords,
 }
 
 /// Identifies an (endpoint, metadata_prefix) pair — the scope under which all
 /// records in `oai_records` live. Most db operations are keyed by this pair.
+///
+/// An optional `set` narrows listing and record selection to members of one
+/// OAI set (`setSpec`). It is a filter, not part of a record's key: a record
+/// harvested through several sets is still a single row.
 #[derive(Debug, Clone)]
 pub struct OaiScope {
     pub endpoint: String,
     pub metadata_prefix: String,
+    pub set: Option<String>,
 }
 
 impl OaiScope {
@@ -32,6 +76,17 @@ impl OaiScope {
         Self {
             endpoint: endpoint.into(),
             metadata_prefix: metadata_prefix.into(),
+            set: None,
         }
     }
+
+    pub fn with_set(mut self, set: Option<String>) -> Self {
+        self.set = set;
+        self
+    }
+
+    /// The set as stored on `runs.set_spec` (empty for the whole endpoint).
+    pub fn set_spec(&self) -> &str {
+        self.set.as_deref().unwrap_or_default()
+    }
 }
@@ -0,0 +1,107 @@
+use std::fmt;
+
+use oai_pmh::client::response::{ErrorCode, ResponseError};
+
+/// An OAI-PMH `<error>` response: the provider answered the request, but with
+/// an error code instead of a payload. Each variant carries the provider's
+/// message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    BadArgument(String),
+    /// The resumption token expired or is no longer known to the provider.
+    BadResumptionToken(String),
+    BadVerb(String),
+    /// The endpoint does not offer the requested metadata prefix (for the
+    /// repository, or for one record).
+    CannotDisseminateFormat(String),
+    IdDoesNotExist(String),
+    /// An empty listing: zero changes, not a failure.
+    NoRecordsMatch(String),
+    NoMetadataFormats(String),
+    NoSetHierarchy(String),
+}
+
+impl ProtocolError {
+    /// The OAI-PMH error code, as sent by the provider.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadArgument(_) => "badArgument",
+            Self::BadResumptionToken(_) => "badResumptionToken",
+            Self::BadVerb(_) => "badVerb",
+            Self::CannotDisseminateFormat(_) => "cannotDisseminateFormat",
+            Self::IdDoesNotExist(_) => "idDoesNotExist",
+            Self::NoRecordsMatch(_) => "noRecordsMatch",
+            Self::NoMetadataFormats(_) => "noMetadataFormats",
+            Self::NoSetHierarchy(_) => "noSetHierarchy",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::BadArgument(message)
+            | Self::BadResumptionToken(message)
+            | Self::BadVerb(message)
+            | Self::CannotDisseminateFormat(message)
+            | Self::IdDoesNotExist(message)
+            | Self::NoRecordsMatch(message)
+            | Self::NoMetadataFormats(message)
+            | Self::NoSetHierarchy(message) => message,
+        }
+    }
+}
+
+impl From<&ResponseError> for ProtocolError {
+    fn from(error: &ResponseError) -> Self {
+        let message = error.message.trim().to_string();
+        match error.code {
+            ErrorCode::BadArgument => Self::BadArgument(message),
+            ErrorCode::BadResumptionToken => Self::BadResumptionToken(message),
+            ErrorCode::BadVerb => Self::BadVerb(message),
+            ErrorCode::CannotDisseminateFormat => Self::CannotDisseminateFormat(message),
+            ErrorCode::IdDoesNotExist => Self::IdDoesNotExist(message),
+            ErrorCode::NoRecordsMatch => Self::NoRecordsMatch(message),
+            ErrorCode::NoMetadataFormats => Self::NoMetadataFormats(message),
+            ErrorCode::NoSetHierarchy => Self::NoSetHierarchy(message),
+        }
+    }
+}
+
+/// `OAI-PMH <code>: <message>`, so each kind is distinct in `runs.error_sample`
+/// and record messages.
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OAI-PMH {}: {}", self.code(), self.message())?;
+        if let Self::CannotDisseminateFormat(_) = self {
+            f.write_str(" (the endpoint does not offer this metadata prefix; see `list-formats`)")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displays_code_and_message() {
+        let error = ProtocolError::from(&ResponseError {
+            code: ErrorCode::IdDoesNotExist,
+            message: " Unknown identifier\n".to_string(),
+        });
+        assert_eq!(
+            error,
+            ProtocolError::IdDoesNotExist("Unknown identifier".into())
+        );
+        assert_eq!(
+            error.to_string(),
+            "OAI-PMH idDoesNotExist: Unknown identifier"
+        );
+        assert!(
+            ProtocolError::CannotDisseminateFormat("oai_dc only".into())
+                .to_string()
+                .starts_with("OAI-PMH cannotDisseminateFormat: oai_dc only (")
+        );
+    }
+}
@@ -3,24 +3,46 @@ use std::path::PathBuf;
 use oai_pmh::client::response::Header;
 use tracing::warn;
 
-use super::status::OaiRecordStatus;
+use super::status::{OaiRecordStatus, PayloadFormat};
 
 /// A record row as fetched from the DB for download, parse, index, or delete
-/// work. Carries enough to locate the on-disk file (`fingerprint`), dispatch
-/// the right action (`status`), and key index transitions (`id`).
+/// work. Carries enough to locate the on-disk file (`fingerprint`,
+/// `payload_format`), dispatch the right action (`status`), and key index
+/// transitions (`id`).
 #[derive(sqlx::FromRow)]
 pub struct OaiRecord {
     pub id: i64,
     pub identifier: String,
     pub fingerprint: String,
     pub status: OaiRecordStatus,
+    /// How the current payload is stored.
+    pub payload_format: PayloadFormat,
+    /// The current payload's size in bytes, when recorded. Only the indexer's
+    /// fetch selects it.
+    #[sqlx(default)]
+    pub payload_bytes: Option<i64>,
 }
 
 impl OaiRecord {
+    /// Where the current payload is stored, relative to the data dir.
     pub fn path(&self) -> PathBuf {
+        self.path_as(self.payload_format)
+    }
+
+    /// Where the current payload is stored when written in `format`.
+    pub fn path_as(&self, format: PayloadFormat) -> PathBuf {
         PathBuf::from(&self.fingerprint[0..2])
             .join(&self.fingerprint[2..4])
-            .join(format!("{}.xml", self.fingerprint))
+            .join(format!("{}.{}", self.fingerprint, format.extension()))
+    }
+
+    /// Where stored payload `version` is kept, next to the current payload.
+    pub fn version_path(&self, version: i32, format: PayloadFormat) -> PathBuf {
+        self.path().with_file_name(format!(
+            "{}.v{version}.{}",
+            self.fingerprint,
+            format.extension()
+        ))
     }
 }
 
@@ -31,6 +53,9 @@ pub struct OaiHeader {
     pub identifier: String,
     pub datestamp: String,
     pub status: OaiRecordStatus,
+    /// The header's `status` attribute as sent, which `status` interprets.
+    pub header_status: Option<String>,
+    pub set_specs: Vec<String>,
 }
 
 impl From<Header> for OaiHeader {
@@ -51,6 +76,8 @@ impl From<Header> for OaiHeader {
             identifier: value.identifier,
             datestamp: value.datestamp,
             status,
+            header_status: value.status,
+            set_specs: value.set_spec,
         }
     }
 }
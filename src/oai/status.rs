@@ -52,9 +52,13 @@ status_enum! {
     ///
     /// Expected transitions:
     /// - import: `* -> pending|deleted` for changed records (`failed` records are intentionally sticky)
-    /// - download: `pending -> available|failed`
+    /// - download: `pending -> available|failed`, or back to a prior
+    ///   `available|parsed` when the payload is unchanged
     /// - metadata: `available -> parsed|failed`
-    /// - retry: `failed -> pending` (batch)
+    /// - retry: `failed -> pending` (batch, under the attempts budget)
+    ///
+    /// Each `* -> failed` transition counts an attempt; reaching `parsed` or
+    /// an upstream change resets the count.
     ///
     /// Index lifecycle ownership (`indexer_records`):
     /// - metadata success upserts the indexer row to `pending` (full reset)
@@ -69,6 +73,23 @@ status_enum! {
     }
 }
 
+status_enum! {
+    /// Why a download failed, stored on `oai_records.failure_category` for
+    /// `failed` records. `transient` failures (network errors, HTTP 5xx,
+    /// malformed responses) may succeed on a later attempt; `permanent` ones
+    /// (OAI errors such as `idDoesNotExist` or `cannotDisseminateFormat`)
+    /// will not until the provider changes; `invalid` payloads arrived but are
+    /// not well-formed or fail schema validation; `oversize` payloads arrived
+    /// but are larger than `--max-payload-bytes`. Metadata failures are left
+    /// unclassified.
+    pub enum FailureCategory {
+        Invalid   => "invalid",
+        Oversize  => "oversize",
+        Permanent => "permanent",
+        Transient => "transient",
+    }
+}
+
 status_enum! {
     /// Index lifecycle states for `indexer_records.status`. A row exists only
     /// once a record has index work or history (first parse or deletion).
@@ -87,3 +108,27 @@ status_enum! {
         PurgeFailed => "purge_failed",
     }
 }
+
+status_enum! {
+    /// How a payload is stored on disk, for `oai_records.payload_format` and
+    /// `record_versions.payload_format`. Also the `--payload-format` new
+    /// payloads are written in.
+    #[derive(Hash, serde::Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum PayloadFormat {
+        Gzip => "gzip",
+        Xml  => "xml",
+        Zstd => "zstd",
+    }
+}
+
+impl PayloadFormat {
+    /// File extension for payloads in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Gzip => "xml.gz",
+            Self::Xml => "xml",
+            Self::Zstd => "xml.zst",
+        }
+    }
+}
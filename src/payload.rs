@@ -0,0 +1,79 @@
+//! Reading and writing stored payloads in their `PayloadFormat`.
+
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+
+use crate::oai::PayloadFormat;
+
+/// zstd level for new payloads (the library default).
+const ZSTD_LEVEL: i32 = 3;
+
+/// `text` encoded for storage in `format`.
+pub(crate) fn encode(format: PayloadFormat, text: &str) -> io::Result<Cow<'_, [u8]>> {
+    match format {
+        PayloadFormat::Xml => Ok(Cow::Borrowed(text.as_bytes())),
+        PayloadFormat::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(text.as_bytes())?;
+            Ok(Cow::Owned(encoder.finish()?))
+        }
+        PayloadFormat::Zstd => Ok(Cow::Owned(zstd::encode_all(text.as_bytes(), ZSTD_LEVEL)?)),
+    }
+}
+
+/// A stream of the decoded payload, from a reader over its stored bytes.
+pub(crate) fn decoder(
+    stored: impl Read + Send + 'static,
+    format: PayloadFormat,
+) -> io::Result<Box<dyn Read + Send>> {
+    Ok(match format {
+        PayloadFormat::Xml => Box::new(stored),
+        PayloadFormat::Gzip => Box::new(GzDecoder::new(stored)),
+        PayloadFormat::Zstd => Box::new(zstd::Decoder::new(stored)?),
+    })
+}
+
+/// The decoded payload, from a reader over its stored bytes.
+pub(crate) fn decode(
+    stored: impl Read + Send + 'static,
+    format: PayloadFormat,
+) -> io::Result<String> {
+    let mut text = String::new();
+    decoder(stored, format)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Hex SHA-256 of a decoded payload, as stored on `content_hash`.
+pub(crate) fn content_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads_round_trip_in_every_format() {
+        let text = "<ead><did><unittitle>Title</unittitle></did></ead>\n".repeat(50);
+        for format in [PayloadFormat::Xml, PayloadFormat::Gzip, PayloadFormat::Zstd] {
+            let encoded = encode(format, &text).unwrap().into_owned();
+            if format != PayloadFormat::Xml {
+                assert!(encoded.len() < text.len() / 4, "{format} compresses");
+            }
+            assert_eq!(decode(io::Cursor::new(encoded), format).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn content_hash_is_hex_sha256() {
+        assert_eq!(
+            content_hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -13,16 +13,25 @@ pub struct ReportArgs {
     #[arg(short, long, env = "METADATA_PREFIX")]
     pub metadata_prefix: String,
 
+    /// Only report records in this OAI set (setSpec)
+    #[arg(long)]
+    pub set: Option<String>,
+
     /// Also report records absent from the OAI feed for this many days
     #[arg(long)]
     pub not_seen_days: Option<i64>,
+
+    /// Also list this many records with the largest stored payloads
+    #[arg(long)]
+    pub largest: Option<i64>,
 }
 
 /// Print health reports for a scope: records serving stale content from the
 /// index (indexed but harvest failed), and optionally records that have
-/// dropped out of the OAI feed without a delete notice.
+/// dropped out of the OAI feed without a delete notice and the records with
+/// the largest payloads.
 pub async fn report(cfg: ReportArgs, pool: Pool<Postgres>) -> anyhow::Result<()> {
-    let scope = OaiScope::new(cfg.endpoint, cfg.metadata_prefix);
+    let scope = OaiScope::new(cfg.endpoint, cfg.metadata_prefix).with_set(cfg.set);
 
     let stale = db::report::stale_in_index(&pool, &scope).await?;
     if stale.is_empty() {
@@ -52,5 +61,23 @@ pub async fn report(cfg: ReportArgs, pool: Pool<Postgres>) -> anyhow::Result<()>
         }
     }
 
+    if let Some(limit) = cfg.largest {
+        let largest = db::report::largest_payloads(&pool, &scope, limit).await?;
+        if largest.is_empty() {
+            info!("Largest payloads: none recorded");
+        } else {
+            info!("Largest payloads ({}):", largest.len());
+            for row in &largest {
+                let components = row
+                    .component_count
+                    .map_or_else(|| "?".to_string(), |count| count.to_string());
+                info!(
+                    "  {} bytes, {components} component(s): {}",
+                    row.payload_bytes, row.identifier
+                );
+            }
+        }
+    }
+
     Ok(())
 }
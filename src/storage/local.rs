@@ -0,0 +1,160 @@
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::future::BoxFuture;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use super::Storage;
+
+/// Suffix of the temp files payloads are written to before being renamed
+/// into place (`.<file name>.<pid>-<n>.tmp`).
+const TEMP_SUFFIX: &str = ".tmp";
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether `file_name` is a temp file left by `LocalStorage::put` (e.g. after
+/// a crash mid-write).
+pub fn is_temp_file(file_name: &str) -> bool {
+    file_name.starts_with('.') && file_name.ends_with(TEMP_SUFFIX)
+}
+
+/// Directories `sync_dir` flushed, so tests can check a put was made durable.
+#[cfg(test)]
+static SYNCED_DIRS: std::sync::Mutex<Vec<PathBuf>> = std::sync::Mutex::new(Vec::new());
+
+/// Flush `dir`'s entries (a rename into it) to disk.
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::File::open(dir)?.sync_all()?;
+    #[cfg(test)]
+    SYNCED_DIRS.lock().unwrap().push(dir.to_path_buf());
+    Ok(())
+}
+
+/// Payloads as files under a data dir, at their key's relative path.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+impl Storage for LocalStorage {
+    /// Written to a temp file in the same directory, synced, then renamed over
+    /// `key`, so a reader (or a crash) never sees a partial payload. The
+    /// directory is synced after the rename so a crash cannot undo it.
+    fn put<'a>(&'a self, key: &'a Path, bytes: Vec<u8>) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let path = self.root.join(key);
+            let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+                anyhow::bail!("invalid payload key {}", key.display());
+            };
+            fs::create_dir_all(parent).await?;
+
+            let temp = parent.join(format!(
+                ".{}.{}-{}{TEMP_SUFFIX}",
+                file_name.to_string_lossy(),
+                process::id(),
+                TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            let written = async {
+                let mut file = fs::File::create(&temp).await?;
+                file.write_all(&bytes).await?;
+                file.sync_all().await?;
+                fs::rename(&temp, &path).await
+            }
+            .await;
+            if written.is_err() {
+                let _ = fs::remove_file(&temp).await;
+            }
+            written?;
+
+            let parent = parent.to_path_buf();
+            tokio::task::spawn_blocking(move || sync_dir(&parent)).await??;
+            Ok(())
+        })
+    }
+
+    /// The file itself, read as it is consumed.
+    fn get<'a>(
+        &'a self,
+        key: &'a Path,
+    ) -> BoxFuture<'a, anyhow::Result<Option<Box<dyn Read + Send>>>> {
+        Box::pin(async move {
+            match fs::File::open(self.root.join(key)).await {
+                Ok(file) => Ok(Some(Box::new(file.into_std().await) as Box<dyn Read + Send>)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a Path) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match fs::remove_file(self.root.join(key)).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    fn local_path(&self, key: &Path) -> Option<PathBuf> {
+        Some(self.root.join(key))
+    }
+
+    fn describe(&self, key: &Path) -> String {
+        self.root.join(key).display().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn put_replaces_the_file_without_leaving_a_temp_file() {
+        let root = std::env::temp_dir().join(format!("harvester-local-storage-{}", process::id()));
+        let storage = LocalStorage::new(root.clone());
+        let key = Path::new("ab/cd/abcd.xml");
+
+        storage.put(key, b"old".to_vec()).await.unwrap();
+        storage.put(key, b"new".to_vec()).await.unwrap();
+
+        let mut stored = Vec::new();
+        storage
+            .get(key)
+            .await
+            .unwrap()
+            .unwrap()
+            .read_to_end(&mut stored)
+            .unwrap();
+        assert_eq!(stored, b"new");
+        let names: Vec<_> = std::fs::read_dir(root.join("ab/cd"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["abcd.xml"]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn put_syncs_the_directory_it_renames_into() {
+        let root = std::env::temp_dir().join(format!("harvester-local-sync-{}", process::id()));
+        let storage = LocalStorage::new(root.clone());
+
+        // The first payload of a shard creates its directories; the new
+        // directory is flushed after the rename, not just the file.
+        storage
+            .put(Path::new("ef/01/ef01.xml"), b"payload".to_vec())
+            .await
+            .unwrap();
+        assert!(SYNCED_DIRS.lock().unwrap().contains(&root.join("ef/01")));
+        assert!(sync_dir(&root.join("ef/02")).is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+//! Where payloads live. Every phase reads and writes payloads through a
+//! `Storage`, keyed by the record's relative path (`OaiRecord::path`), so the
+//! harvester and the indexer need not share a volume.
+
+mod local;
+mod s3;
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use clap::Args;
+use futures::future::BoxFuture;
+
+pub use local::{LocalStorage, is_temp_file};
+pub use s3::{S3Config, S3Storage};
+
+pub trait Storage: Send + Sync {
+    /// Store `bytes` under `key`, replacing any existing object.
+    fn put<'a>(&'a self, key: &'a Path, bytes: Vec<u8>) -> BoxFuture<'a, anyhow::Result<()>>;
+
+    /// A reader over the object under `key`, or `None` if there is none.
+    /// Reads are blocking, like file reads.
+    fn get<'a>(
+        &'a self,
+        key: &'a Path,
+    ) -> BoxFuture<'a, anyhow::Result<Option<Box<dyn Read + Send>>>>;
+
+    /// Remove the object under `key`. Removing a missing object succeeds.
+    fn delete<'a>(&'a self, key: &'a Path) -> BoxFuture<'a, anyhow::Result<()>>;
+
+    /// Where the object under `key` can be read directly as a file, for
+    /// backends that keep objects on the local filesystem.
+    fn local_path(&self, _key: &Path) -> Option<PathBuf> {
+        None
+    }
+
+    /// Human-readable location of `key`, for messages.
+    fn describe(&self, key: &Path) -> String;
+}
+
+/// Which backend payloads are stored in.
+#[derive(Debug, Clone)]
+pub enum StorageConfig {
+    /// Files under a data dir.
+    Local(PathBuf),
+    /// Objects in an S3-compatible bucket.
+    S3(S3Config),
+}
+
+impl StorageConfig {
+    pub fn open(&self) -> Arc<dyn Storage> {
+        match self {
+            Self::Local(dir) => Arc::new(LocalStorage::new(dir.clone())),
+            Self::S3(config) => Arc::new(S3Storage::new(config.clone())),
+        }
+    }
+}
+
+/// Object storage options shared by every command that reads or writes
+/// payloads. Without `--storage-url`, payloads are files under `--dir`.
+#[derive(Debug, Clone, Args)]
+pub struct StorageArgs {
+    /// Store payloads in an S3-compatible bucket instead of the data dir
+    /// (s3://bucket/optional/prefix); credentials come from AWS_ACCESS_KEY_ID
+    /// and AWS_SECRET_ACCESS_KEY
+    #[arg(long, env = "STORAGE_URL")]
+    pub storage_url: Option<String>,
+
+    /// S3 endpoint url, e.g. http://localhost:9000 for MinIO (default: AWS)
+    #[arg(long, env = "S3_ENDPOINT")]
+    pub s3_endpoint: Option<String>,
+
+    /// S3 region
+    #[arg(long, default_value = "us-east-1", env = "AWS_REGION")]
+    pub s3_region: String,
+}
+
+impl StorageArgs {
+    /// The configured backend, with `dir` as the local data dir.
+    pub fn config(&self, dir: PathBuf) -> anyhow::Result<StorageConfig> {
+        let Some(url) = &self.storage_url else {
+            return Ok(StorageConfig::Local(dir));
+        };
+        let (bucket, prefix) = parse_storage_url(url)?;
+        let endpoint = self
+            .s3_endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", self.s3_region));
+        Ok(StorageConfig::S3(S3Config {
+            endpoint,
+            bucket,
+            prefix,
+            region: self.s3_region.clone(),
+            access_key_id: std::env::var("AWS_ACCESS_KEY_ID")
+                .context("AWS_ACCESS_KEY_ID is required with --storage-url")?,
+            secret_access_key: std::env::var("AWS_SECRET_ACCESS_KEY")
+                .context("AWS_SECRET_ACCESS_KEY is required with --storage-url")?,
+        }))
+    }
+}
+
+/// `s3://bucket/some/prefix` as (`bucket`, `some/prefix/`).
+fn parse_storage_url(url: &str) -> anyhow::Result<(String, String)> {
+    let rest = url
+        .strip_prefix("s3://")
+        .with_context(|| format!("unsupported storage url {url} (expected s3://bucket/prefix)"))?;
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if bucket.is_empty() {
+        anyhow::bail!("storage url {url} has no bucket");
+    }
+    let prefix = prefix.trim_matches('/');
+    let prefix = if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{prefix}/")
+    };
+    Ok((bucket.to_string(), prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bucket_and_prefix_from_storage_urls() {
+        assert_eq!(
+            parse_storage_url("s3://payloads").unwrap(),
+            ("payloads".to_string(), String::new())
+        );
+        assert_eq!(
+            parse_storage_url("s3://payloads/ead/prod/").unwrap(),
+            ("payloads".to_string(), "ead/prod/".to_string())
+        );
+        assert!(parse_storage_url("file:///data").is_err());
+        assert!(parse_storage_url("s3:///prefix").is_err());
+    }
+}
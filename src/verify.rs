@@ -0,0 +1,221 @@
+//! `verify`: check that every available or parsed record's payload is in
+//! storage, decodes, and matches its stored checksum, and requeue the records
+//! whose payload is missing or corrupt for download.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::Args;
+use futures::stream::{self, StreamExt};
+use sqlx::{Pool, Postgres};
+use tracing::{info, warn};
+
+use crate::db::{self, payload::StoredPayload};
+use crate::expand_path;
+use crate::payload;
+use crate::storage::{Storage, StorageArgs};
+
+/// Payloads read and checked concurrently.
+const CHECK_CONCURRENCY: usize = 8;
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// Base directory for downloads
+    #[arg(short, long, default_value = "data", env = "DATA_DIR")]
+    pub dir: PathBuf,
+
+    #[command(flatten)]
+    pub storage: StorageArgs,
+
+    /// Only report findings; leave the records as they are
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+}
+
+/// What is wrong with a stored payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Finding {
+    /// Nothing is stored under the record's key.
+    Missing,
+    /// The stored bytes do not decode in the record's `payload_format`.
+    Undecodable(String),
+    /// The payload decodes, but does not hash to the record's `content_hash`.
+    Mismatch,
+}
+
+impl Finding {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Undecodable(_) => "undecodable",
+            Self::Mismatch => "checksum mismatch",
+        }
+    }
+
+    /// The message left on a requeued record.
+    fn message(&self) -> String {
+        match self {
+            Self::Missing => "Requeued by verify: payload is missing".to_string(),
+            Self::Undecodable(e) => format!("Requeued by verify: payload does not decode: {e}"),
+            Self::Mismatch => "Requeued by verify: payload does not match its checksum".to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct VerifyStats {
+    checked: usize,
+    /// Payloads stored without a checksum, checked for presence and decoding
+    /// only.
+    unhashed: usize,
+    missing: usize,
+    undecodable: usize,
+    mismatched: usize,
+    requeued: usize,
+    /// Payloads storage failed to read (e.g. permissions, an S3 outage).
+    unreadable: usize,
+}
+
+impl VerifyStats {
+    fn add(&mut self, finding: &Finding) {
+        *match finding {
+            Finding::Missing => &mut self.missing,
+            Finding::Undecodable(_) => &mut self.undecodable,
+            Finding::Mismatch => &mut self.mismatched,
+        } += 1;
+    }
+
+    fn findings(&self) -> usize {
+        self.missing + self.undecodable + self.mismatched
+    }
+}
+
+/// Check every payload that should be stored, and requeue each record whose
+/// payload is missing or corrupt to pending, to be downloaded (then parsed and
+/// indexed) again by the next harvest. Payloads storage fails to read are
+/// reported but not requeued, so an outage does not trigger a full
+/// re-download.
+pub async fn verify(
+    cfg: VerifyArgs,
+    pool: Pool<Postgres>,
+    shutdown: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    let storage = cfg.storage.config(expand_path(&cfg.dir))?.open();
+    let storage = storage.as_ref();
+    let mut stats = VerifyStats::default();
+    let mut last_id = 0;
+
+    while !shutdown.load(Ordering::Relaxed) {
+        let batch = db::payload::fetch_stored(&pool, last_id).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        last_id = last.record.id;
+
+        let results: Vec<_> = stream::iter(&batch)
+            .map(|stored| async move { (stored, check(storage, stored).await) })
+            .buffer_unordered(CHECK_CONCURRENCY)
+            .collect()
+            .await;
+
+        for (stored, result) in results {
+            let record = &stored.record;
+            stats.checked += 1;
+            if stored.content_hash.is_none() {
+                stats.unhashed += 1;
+            }
+            let finding = match result {
+                Ok(None) => continue,
+                Ok(Some(finding)) => finding,
+                Err(e) => {
+                    warn!("Failed to read {}: {e}", storage.describe(&record.path()));
+                    stats.unreadable += 1;
+                    continue;
+                }
+            };
+            info!(
+                "{}: {} ({})",
+                finding.label(),
+                record.identifier,
+                storage.describe(&record.path())
+            );
+            stats.add(&finding);
+            if !cfg.dry_run {
+                stats.requeued += db::payload::requeue_for_download(
+                    &pool,
+                    record.id,
+                    record.status,
+                    &finding.message(),
+                )
+                .await? as usize;
+            }
+        }
+    }
+
+    info!(
+        "Checked {} payload(s) ({} without a checksum): {} missing, {} undecodable, {} checksum \
+         mismatch(es), {} unreadable; requeued {} record(s) for download",
+        stats.checked,
+        stats.unhashed,
+        stats.missing,
+        stats.undecodable,
+        stats.mismatched,
+        stats.unreadable,
+        stats.requeued
+    );
+    if cfg.dry_run && stats.findings() > 0 {
+        info!("Run again without --dry-run to requeue them");
+    }
+    if stats.unreadable > 0 {
+        anyhow::bail!("{} payload(s) could not be read", stats.unreadable);
+    }
+    Ok(())
+}
+
+/// What is wrong with a record's payload, if anything. Errors are storage
+/// failures, which say nothing about the payload.
+async fn check(storage: &dyn Storage, stored: &StoredPayload) -> anyhow::Result<Option<Finding>> {
+    let record = &stored.record;
+    let Some(reader) = storage.get(&record.path()).await? else {
+        return Ok(Some(Finding::Missing));
+    };
+    let format = record.payload_format;
+    let expected = stored.content_hash.clone();
+    Ok(tokio::task::spawn_blocking(move || {
+        inspect(payload::decode(reader, format), expected.as_deref())
+    })
+    .await?)
+}
+
+/// Judge a decoded payload against its expected checksum.
+fn inspect(decoded: std::io::Result<String>, expected: Option<&str>) -> Option<Finding> {
+    match decoded {
+        Err(e) => Some(Finding::Undecodable(e.to_string())),
+        Ok(text) => expected
+            .filter(|expected| *expected != payload::content_hash(&text))
+            .map(|_| Finding::Mismatch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn inspect_compares_checksums_when_there_is_one() {
+        let hash = payload::content_hash("<ead/>");
+        assert_eq!(inspect(Ok("<ead/>".to_string()), Some(&hash)), None);
+        assert_eq!(inspect(Ok("<ead/>".to_string()), None), None);
+        assert_eq!(
+            inspect(Ok("<ead></ead>".to_string()), Some(&hash)),
+            Some(Finding::Mismatch)
+        );
+        assert_eq!(
+            inspect(Err(io::Error::other("bad magic")), None),
+            Some(Finding::Undecodable("bad magic".to_string()))
+        );
+    }
+}
@@ -0,0 +1,126 @@
+mod support;
+
+use harvester::{DiscoveryArgs, ListFormatsArgs, identify, list_formats, list_sets};
+use sqlx::Row;
+use support::{
+    METADATA_PREFIX, MockOaiConfig, acquire_test_lock, create_temp_dir, run_harvest,
+    setup_test_pool, start_mock_oai_server,
+};
+
+fn discovery_args(endpoint: &str) -> DiscoveryArgs {
+    DiscoveryArgs {
+        endpoint: endpoint.to_string(),
+        oai_timeout: 10,
+    }
+}
+
+#[tokio::test]
+async fn identify_stores_endpoint_description() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let server = start_mock_oai_server(MockOaiConfig::default()).await?;
+
+    identify(discovery_args(&server.endpoint), pool.clone()).await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT repository_name, admin_emails, deleted_record, granularity,
+               identified_at IS NOT NULL AS identified
+        FROM endpoints
+        WHERE endpoint = $1
+        "#,
+    )
+    .bind(&server.endpoint)
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(
+        row.try_get::<String, _>("repository_name")?,
+        "Integration Test Repository"
+    );
+    assert_eq!(
+        row.try_get::<Vec<String>, _>("admin_emails")?,
+        vec!["integration@example.com"]
+    );
+    assert_eq!(row.try_get::<String, _>("deleted_record")?, "persistent");
+    assert_eq!(row.try_get::<String, _>("granularity")?, "YYYY-MM-DD");
+    assert!(row.try_get::<bool, _>("identified")?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_formats_and_sets_store_endpoint_capabilities() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let server = start_mock_oai_server(MockOaiConfig {
+        sets: vec!["manuscripts".to_string(), "photographs".to_string()],
+        ..Default::default()
+    })
+    .await?;
+
+    list_formats(
+        ListFormatsArgs {
+            discovery: discovery_args(&server.endpoint),
+            identifier: None,
+        },
+        pool.clone(),
+    )
+    .await?;
+    list_sets(discovery_args(&server.endpoint), pool.clone()).await?;
+
+    let row = sqlx::query("SELECT metadata_formats, sets FROM endpoints WHERE endpoint = $1")
+        .bind(&server.endpoint)
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(
+        row.try_get::<serde_json::Value, _>("metadata_formats")?,
+        serde_json::json!([{
+            "metadata_prefix": METADATA_PREFIX,
+            "schema": "https://www.loc.gov/ead/ead.xsd",
+            "metadata_namespace": "urn:isbn:1-931666-22-9",
+        }])
+    );
+    assert_eq!(
+        row.try_get::<serde_json::Value, _>("sets")?,
+        serde_json::json!([
+            { "set_spec": "manuscripts", "set_name": "Set manuscripts" },
+            { "set_spec": "photographs", "set_name": "Set photographs" },
+        ])
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_sets_stores_no_sets_without_a_set_hierarchy() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let server = start_mock_oai_server(MockOaiConfig::default()).await?;
+
+    list_sets(discovery_args(&server.endpoint), pool.clone()).await?;
+
+    let sets = sqlx::query_scalar::<_, serde_json::Value>(
+        "SELECT sets FROM endpoints WHERE endpoint = $1",
+    )
+    .bind(&server.endpoint)
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(sets, serde_json::json!([]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn harvest_records_identify_for_the_endpoint() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("discovery-harvest")?;
+    let server = start_mock_oai_server(MockOaiConfig::default()).await?;
+
+    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+
+    let granularity =
+        sqlx::query_scalar::<_, String>("SELECT granularity FROM endpoints WHERE endpoint = $1")
+            .bind(&server.endpoint)
+            .fetch_one(&pool)
+            .await?;
+    assert_eq!(granularity, "YYYY-MM-DD");
+    Ok(())
+}
@@ -1,13 +1,33 @@
 mod support;
 
-use std::{collections::HashMap, fs};
+use std::{
+    collections::HashMap,
+    fs,
+    io::Read,
+    path::PathBuf,
+    sync::{Arc, atomic::AtomicBool},
+};
 
-use harvester::{OaiRecord, OaiScope, db::harvester::retry, oai::OaiRecordStatus};
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use harvester::{
+    CompressArgs, DEFAULT_MAX_HARVEST_ATTEMPTS, GcArgs, HarvestMode, HarvesterArgs, OaiRecord,
+    OaiScope, VerifyArgs, compress,
+    db::harvester::{RetryFilter, count_quarantined, retry},
+    db::history,
+    db::resumption::{self, SavedListing},
+    gc,
+    oai::{FailureCategory, OaiRecordStatus, PayloadFormat},
+    storage::{S3Config, StorageArgs, StorageConfig},
+    verify,
+};
+use sha2::{Digest, Sha256};
 use support::{
-    DEFAULT_DATESTAMP, EAD_XML, GetRecordSpec, MockOaiConfig, acquire_test_lock,
-    count_records_for_identifier, create_rules_file, create_temp_dir, create_temp_file,
-    fetch_fingerprint, fetch_latest_run, fetch_record_id, fetch_record_snapshot, header_spec,
-    insert_record, insert_record_with_index, run_harvest, setup_test_pool, start_mock_oai_server,
+    DEFAULT_DATESTAMP, EAD_XML, GetRecordSpec, InFlight, METADATA_PREFIX, MockOaiConfig,
+    acquire_test_lock, count_records_for_identifier, create_rules_file, create_rules_file_with,
+    create_temp_dir, create_temp_file, fetch_fingerprint, fetch_latest_run, fetch_record_id,
+    fetch_record_snapshot, harvest_config, header_spec, insert_record, insert_record_with_index,
+    run_harvest, run_harvest_with, setup_test_pool, start_mock_oai_server, start_mock_s3_server,
 };
 
 #[tokio::test]
@@ -25,6 +45,7 @@ async fn run_without_rules_leaves_records_available() -> anyhow::Result<()> {
     let server = start_mock_oai_server(MockOaiConfig {
         headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
         records,
+        ..Default::default()
     })
     .await?;
 
@@ -54,6 +75,8 @@ async fn run_without_rules_leaves_records_available() -> anyhow::Result<()> {
         identifier: identifier.to_string(),
         fingerprint,
         status: OaiRecordStatus::Available,
+        payload_format: PayloadFormat::Xml,
+        payload_bytes: None,
     };
     assert!(data_dir.join(record.path()).is_file());
     Ok(())
@@ -69,6 +92,7 @@ async fn import_marks_deleted_headers_as_deleted() -> anyhow::Result<()> {
     let server = start_mock_oai_server(MockOaiConfig {
         headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, Some("deleted"))],
         records: HashMap::new(),
+        ..Default::default()
     })
     .await?;
 
@@ -96,6 +120,7 @@ async fn import_deleted_preserves_index_history_for_purge() -> anyhow::Result<()
     let server = start_mock_oai_server(MockOaiConfig {
         headers: vec![header_spec(identifier, newer_datestamp, Some("deleted"))],
         records: HashMap::new(),
+        ..Default::default()
     })
     .await?;
 
@@ -145,6 +170,7 @@ async fn import_is_idempotent_for_same_data() -> anyhow::Result<()> {
     let server = start_mock_oai_server(MockOaiConfig {
         headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
         records,
+        ..Default::default()
     })
     .await?;
 
@@ -156,7 +182,11 @@ async fn import_is_idempotent_for_same_data() -> anyhow::Result<()> {
     .fetch_one(&pool)
     .await?;
 
-    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+    // A full listing re-sends the unchanged header (an incremental one would
+    // skip it entirely).
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.full = true;
+    run_harvest_with(&pool, config, None).await?;
 
     let count = count_records_for_identifier(&pool, &server.endpoint, identifier).await?;
     assert_eq!(count, 1);
@@ -196,6 +226,7 @@ async fn import_does_not_reset_failed_record() -> anyhow::Result<()> {
     let server = start_mock_oai_server(MockOaiConfig {
         headers: vec![header_spec(identifier, newer_datestamp, None)],
         records,
+        ..Default::default()
     })
     .await?;
 
@@ -235,6 +266,7 @@ async fn retry_resets_failed_records_and_harvest_reprocesses_them() -> anyhow::R
     let server = start_mock_oai_server(MockOaiConfig {
         headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
         records,
+        ..Default::default()
     })
     .await?;
 
@@ -250,6 +282,7 @@ async fn retry_resets_failed_records_and_harvest_reprocesses_them() -> anyhow::R
     let result = retry(
         &pool,
         &OaiScope::new(&server.endpoint, support::METADATA_PREFIX),
+        RetryFilter::default(),
     )
     .await?;
     assert_eq!(result.rows_affected(), 1);
@@ -273,6 +306,7 @@ async fn download_marks_failed_when_payload_is_missing() -> anyhow::Result<()> {
     let server = start_mock_oai_server(MockOaiConfig {
         headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
         records,
+        ..Default::default()
     })
     .await?;
 
@@ -284,6 +318,151 @@ async fn download_marks_failed_when_payload_is_missing() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn download_fails_payloads_that_are_not_well_formed_as_invalid() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("download-malformed")?;
+    let identifier = "record-malformed";
+
+    let mut records = HashMap::new();
+    records.insert(
+        identifier.to_string(),
+        GetRecordSpec::Payload(format!("{EAD_XML}\n<ead/>")),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+        records,
+        ..Default::default()
+    })
+    .await?;
+
+    run_harvest(&pool, &server.endpoint, data_dir.clone(), None).await?;
+
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "failed");
+    assert_eq!(snapshot.failure_category.as_deref(), Some("invalid"));
+    assert_eq!(
+        snapshot.message,
+        "Payload is not well-formed XML at line 2, column 1: element <ead> follows the root \
+         element"
+    );
+    assert_eq!(fs::read_dir(&data_dir)?.count(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn download_validates_payloads_against_a_schema() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("download-schema")?;
+    let schema = create_temp_file("download-schema-xsd")?;
+    fs::write(
+        &schema,
+        r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"
+           targetNamespace="urn:isbn:1-931666-22-9" xmlns="urn:isbn:1-931666-22-9"
+           elementFormDefault="qualified">
+  <xs:element name="ead">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element name="archdesc">
+          <xs:complexType>
+            <xs:sequence><xs:any processContents="skip" maxOccurs="unbounded"/></xs:sequence>
+          </xs:complexType>
+        </xs:element>
+      </xs:sequence>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#,
+    )?;
+
+    let mut records = HashMap::new();
+    records.insert(
+        "record-valid".to_string(),
+        GetRecordSpec::Payload(EAD_XML.to_string()),
+    );
+    records.insert(
+        "record-invalid".to_string(),
+        GetRecordSpec::Payload(
+            "<ead xmlns=\"urn:isbn:1-931666-22-9\">\n  <eadheader/>\n</ead>".to_string(),
+        ),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![
+            header_spec("record-valid", DEFAULT_DATESTAMP, None),
+            header_spec("record-invalid", DEFAULT_DATESTAMP, None),
+        ],
+        records,
+        ..Default::default()
+    })
+    .await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.schema = Some(schema);
+    run_harvest_with(&pool, config, None).await?;
+
+    let valid = fetch_record_snapshot(&pool, &server.endpoint, "record-valid").await?;
+    assert_eq!(valid.status, "available", "{}", valid.message);
+    let invalid = fetch_record_snapshot(&pool, &server.endpoint, "record-invalid").await?;
+    assert_eq!(invalid.status, "failed");
+    assert_eq!(invalid.failure_category.as_deref(), Some("invalid"));
+    assert!(
+        invalid
+            .message
+            .contains(" at line 2: Element '{urn:isbn:1-931666-22-9}eadheader'"),
+        "{}",
+        invalid.message
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn download_records_payload_size_and_fails_oversize_payloads() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("download-oversize")?;
+
+    let small = "<ead><dsc><c01><c02/></c01><c01/></dsc></ead>";
+    let large = format!("<ead><dsc>{}</dsc></ead>", "<c/>".repeat(100));
+    let mut records = HashMap::new();
+    records.insert(
+        "record-small".to_string(),
+        GetRecordSpec::Payload(small.to_string()),
+    );
+    records.insert("record-large".to_string(), GetRecordSpec::Payload(large));
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![
+            header_spec("record-small", DEFAULT_DATESTAMP, None),
+            header_spec("record-large", DEFAULT_DATESTAMP, None),
+        ],
+        records,
+        ..Default::default()
+    })
+    .await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.max_payload_bytes = Some(100);
+    run_harvest_with(&pool, config, None).await?;
+
+    let stored = fetch_record_snapshot(&pool, &server.endpoint, "record-small").await?;
+    assert_eq!(stored.status, "available", "{}", stored.message);
+    assert_eq!(stored.payload_bytes, Some(small.len() as i64));
+    assert_eq!(stored.component_count, Some(3));
+
+    let oversize = fetch_record_snapshot(&pool, &server.endpoint, "record-large").await?;
+    assert_eq!(oversize.status, "failed");
+    assert_eq!(oversize.failure_category.as_deref(), Some("oversize"));
+    assert!(
+        oversize
+            .message
+            .ends_with(" bytes, over the --max-payload-bytes limit of 100"),
+        "{}",
+        oversize.message
+    );
+    assert_eq!(oversize.payload_bytes, None);
+    Ok(())
+}
+
 #[tokio::test]
 async fn download_marks_failed_when_write_to_disk_fails() -> anyhow::Result<()> {
     let _guard = acquire_test_lock().await;
@@ -299,6 +478,7 @@ async fn download_marks_failed_when_write_to_disk_fails() -> anyhow::Result<()>
     let server = start_mock_oai_server(MockOaiConfig {
         headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
         records,
+        ..Default::default()
     })
     .await?;
 
@@ -336,6 +516,7 @@ async fn download_batch_handles_mixed_record_outcomes() -> anyhow::Result<()> {
             header_spec(missing_record_id, DEFAULT_DATESTAMP, None),
         ],
         records,
+        ..Default::default()
     })
     .await?;
 
@@ -349,9 +530,350 @@ async fn download_batch_handles_mixed_record_outcomes() -> anyhow::Result<()> {
     assert_eq!(missing_payload.status, "failed");
     assert!(missing_payload.message.contains("missing payload"));
 
+    assert_eq!(
+        missing_payload.failure_category.as_deref(),
+        Some("transient")
+    );
+
     let missing_record = fetch_record_snapshot(&pool, &server.endpoint, missing_record_id).await?;
     assert_eq!(missing_record.status, "failed");
-    assert!(!missing_record.message.is_empty());
+    assert!(missing_record.message.contains("idDoesNotExist"));
+    assert_eq!(
+        missing_record.failure_category.as_deref(),
+        Some("permanent")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn download_retries_transient_failures() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("download-transient")?;
+    let flaky_id = "record-flaky";
+    let missing_id = "record-missing-permanent";
+
+    let mut records = HashMap::new();
+    records.insert(
+        flaky_id.to_string(),
+        GetRecordSpec::Payload(EAD_XML.to_string()),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![
+            header_spec(flaky_id, DEFAULT_DATESTAMP, None),
+            header_spec(missing_id, DEFAULT_DATESTAMP, None),
+        ],
+        records,
+        flaky_records: HashMap::from([(flaky_id.to_string(), 2)]),
+        ..Default::default()
+    })
+    .await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.oai_retries = 2;
+    run_harvest_with(&pool, config, None).await?;
+
+    let flaky = fetch_record_snapshot(&pool, &server.endpoint, flaky_id).await?;
+    assert_eq!(flaky.status, "available");
+    assert_eq!(flaky.failure_category, None);
+
+    // Permanent failures are not retried.
+    let missing = fetch_record_snapshot(&pool, &server.endpoint, missing_id).await?;
+    assert_eq!(missing.status, "failed");
+    assert_eq!(missing.failure_category.as_deref(), Some("permanent"));
+    let get_records = server.requests("GetRecord");
+    let attempts = |identifier: &str| {
+        get_records
+            .iter()
+            .filter(|params| params.get("identifier").map(String::as_str) == Some(identifier))
+            .count()
+    };
+    assert_eq!(attempts(flaky_id), 3);
+    assert_eq!(attempts(missing_id), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn adaptive_concurrency_downloads_every_record_through_server_errors() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("adaptive-concurrency")?;
+
+    let identifiers: Vec<_> = (0..30).map(|i| format!("record-adaptive-{i:02}")).collect();
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: identifiers
+            .iter()
+            .map(|identifier| header_spec(identifier, DEFAULT_DATESTAMP, None))
+            .collect(),
+        records: identifiers
+            .iter()
+            .map(|identifier| {
+                (
+                    identifier.clone(),
+                    GetRecordSpec::Payload(EAD_XML.to_string()),
+                )
+            })
+            .collect(),
+        flaky_records: identifiers
+            .iter()
+            .step_by(3)
+            .map(|identifier| (identifier.clone(), 1))
+            .collect(),
+        ..Default::default()
+    })
+    .await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.concurrency = 8;
+    config.adaptive_concurrency = true;
+    config.oai_retries = 1;
+    run_harvest_with(&pool, config, None).await?;
+
+    for identifier in &identifiers {
+        let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+        assert_eq!(snapshot.status, "available", "{identifier}");
+    }
+    assert_eq!(server.requests("GetRecord").len(), 40);
+    Ok(())
+}
+
+#[tokio::test]
+async fn retry_can_target_transient_failures() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("retry-transient")?;
+    let flaky_id = "record-flaky-retry";
+    let missing_id = "record-missing-retry";
+
+    let mut records = HashMap::new();
+    records.insert(
+        flaky_id.to_string(),
+        GetRecordSpec::Payload(EAD_XML.to_string()),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![
+            header_spec(flaky_id, DEFAULT_DATESTAMP, None),
+            header_spec(missing_id, DEFAULT_DATESTAMP, None),
+        ],
+        records,
+        flaky_records: HashMap::from([(flaky_id.to_string(), 1)]),
+        ..Default::default()
+    })
+    .await?;
+
+    run_harvest(&pool, &server.endpoint, data_dir.clone(), None).await?;
+    let flaky = fetch_record_snapshot(&pool, &server.endpoint, flaky_id).await?;
+    assert_eq!(flaky.status, "failed");
+    assert_eq!(flaky.failure_category.as_deref(), Some("transient"));
+    assert!(flaky.message.contains("502"));
+
+    let result = retry(
+        &pool,
+        &OaiScope::new(&server.endpoint, METADATA_PREFIX),
+        RetryFilter {
+            category: Some(FailureCategory::Transient),
+            ..Default::default()
+        },
+    )
+    .await?;
+    assert_eq!(result.rows_affected(), 1);
+
+    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+    let flaky = fetch_record_snapshot(&pool, &server.endpoint, flaky_id).await?;
+    assert_eq!(flaky.status, "available");
+    let missing = fetch_record_snapshot(&pool, &server.endpoint, missing_id).await?;
+    assert_eq!(missing.status, "failed");
+    assert_eq!(missing.failure_category.as_deref(), Some("permanent"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn retry_quarantines_records_over_the_attempts_budget() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("retry-quarantine")?;
+    let identifier = "record-always-missing";
+
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+        ..Default::default()
+    })
+    .await?;
+    let scope = OaiScope::new(&server.endpoint, METADATA_PREFIX);
+    let filter = RetryFilter {
+        max_attempts: Some(2),
+        ..Default::default()
+    };
+
+    run_harvest(&pool, &server.endpoint, data_dir.clone(), None).await?;
+    assert_eq!(
+        fetch_record_snapshot(&pool, &server.endpoint, identifier)
+            .await?
+            .attempts,
+        1
+    );
+
+    assert_eq!(retry(&pool, &scope, filter).await?.rows_affected(), 1);
+    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "failed");
+    assert_eq!(snapshot.attempts, 2);
+
+    // At the budget: left failed, and counted as quarantined.
+    assert_eq!(retry(&pool, &scope, filter).await?.rows_affected(), 0);
+    assert_eq!(count_quarantined(&pool, &scope, 2).await?, 1);
+
+    // A larger budget releases it.
+    let filter = RetryFilter {
+        max_attempts: Some(3),
+        ..Default::default()
+    };
+    assert_eq!(retry(&pool, &scope, filter).await?.rows_affected(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_stored_payload_that_fails_metadata_is_parsed_again_after_retry() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("retry-stored-unparsed")?;
+    let rules = create_rules_file("retry-stored-unparsed-rules")?;
+    let failing_rules =
+        create_rules_file_with("retry-stored-unparsed-failing", "x,nonexistent,required\n")?;
+    let identifier = "record-stored-unparsed";
+
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+        records: HashMap::from([(
+            identifier.to_string(),
+            GetRecordSpec::Payload(EAD_XML.to_string()),
+        )]),
+        ..Default::default()
+    })
+    .await?;
+    let scope = OaiScope::new(&server.endpoint, METADATA_PREFIX);
+    let harvest = |rules: Option<PathBuf>| {
+        let mut config = harvest_config(&server.endpoint, data_dir.clone());
+        config.full = true;
+        run_harvest_with(&pool, config, rules)
+    };
+
+    harvest(Some(rules)).await?;
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "parsed");
+
+    // Upstream bumps the datestamp with a new payload (the stored hash stands
+    // in for the old one), which is stored but fails metadata extraction.
+    sqlx::query(
+        "UPDATE oai_records SET datestamp = '2000-01-01', content_hash = 'old' \
+         WHERE identifier = $1",
+    )
+    .bind(identifier)
+    .execute(&pool)
+    .await?;
+    harvest(Some(failing_rules)).await?;
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "failed");
+
+    // The re-download hashes the same as the stored payload, which was never
+    // parsed: it must not skip back to parsed with the old metadata.
+    assert_eq!(
+        retry(&pool, &scope, RetryFilter::default())
+            .await?
+            .rows_affected(),
+        1
+    );
+    harvest(None).await?;
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "available");
+    Ok(())
+}
+
+#[tokio::test]
+async fn attempts_reset_only_once_a_record_is_parsed() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("retry-recovered")?;
+    let rules = create_rules_file("retry-recovered-rules")?;
+    let filter = RetryFilter {
+        max_attempts: Some(DEFAULT_MAX_HARVEST_ATTEMPTS),
+        ..Default::default()
+    };
+
+    // Four failed downloads, then one that makes it through to parsed.
+    let recovered = "record-recovered";
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(recovered, DEFAULT_DATESTAMP, None)],
+        records: HashMap::from([(
+            recovered.to_string(),
+            GetRecordSpec::Payload(EAD_XML.to_string()),
+        )]),
+        flaky_records: HashMap::from([(recovered.to_string(), 4)]),
+        ..Default::default()
+    })
+    .await?;
+    let scope = OaiScope::new(&server.endpoint, METADATA_PREFIX);
+    run_harvest(
+        &pool,
+        &server.endpoint,
+        data_dir.clone(),
+        Some(rules.clone()),
+    )
+    .await?;
+    for _ in 0..4 {
+        assert_eq!(retry(&pool, &scope, filter).await?.rows_affected(), 1);
+        run_harvest(
+            &pool,
+            &server.endpoint,
+            data_dir.clone(),
+            Some(rules.clone()),
+        )
+        .await?;
+    }
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, recovered).await?;
+    assert_eq!(snapshot.status, "parsed");
+    assert_eq!(snapshot.attempts, 0);
+
+    // Downloads that succeed do not reset the count: a record whose metadata
+    // always fails still reaches the budget.
+    let unparseable = "record-unparseable";
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(unparseable, DEFAULT_DATESTAMP, None)],
+        records: HashMap::from([(
+            unparseable.to_string(),
+            GetRecordSpec::Payload(EAD_XML.to_string()),
+        )]),
+        ..Default::default()
+    })
+    .await?;
+    let scope = OaiScope::new(&server.endpoint, METADATA_PREFIX);
+    let failing_rules =
+        create_rules_file_with("retry-unparseable-rules", "x,nonexistent,required\n")?;
+    run_harvest(
+        &pool,
+        &server.endpoint,
+        data_dir.clone(),
+        Some(failing_rules.clone()),
+    )
+    .await?;
+    for _ in 1..DEFAULT_MAX_HARVEST_ATTEMPTS {
+        assert_eq!(retry(&pool, &scope, filter).await?.rows_affected(), 1);
+        run_harvest(
+            &pool,
+            &server.endpoint,
+            data_dir.clone(),
+            Some(failing_rules.clone()),
+        )
+        .await?;
+    }
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, unparseable).await?;
+    assert_eq!(snapshot.status, "failed");
+    assert_eq!(snapshot.attempts, DEFAULT_MAX_HARVEST_ATTEMPTS);
+    assert_eq!(retry(&pool, &scope, filter).await?.rows_affected(), 0);
+    assert_eq!(
+        count_quarantined(&pool, &scope, DEFAULT_MAX_HARVEST_ATTEMPTS).await?,
+        1
+    );
     Ok(())
 }
 
@@ -371,6 +893,7 @@ async fn metadata_missing_file_marks_failed_and_continues() -> anyhow::Result<()
             Some("deleted"),
         )],
         records: HashMap::new(),
+        ..Default::default()
     })
     .await?;
 
@@ -398,6 +921,8 @@ async fn metadata_missing_file_marks_failed_and_continues() -> anyhow::Result<()
         identifier: present_identifier.to_string(),
         fingerprint: present_fingerprint,
         status: OaiRecordStatus::Available,
+        payload_format: PayloadFormat::Xml,
+        payload_bytes: None,
     };
     let present_path = data_dir.join(present_record.path());
     if let Some(parent) = present_path.parent() {
@@ -431,6 +956,7 @@ async fn metadata_invalid_xml_marks_failed() -> anyhow::Result<()> {
             Some("deleted"),
         )],
         records: HashMap::new(),
+        ..Default::default()
     })
     .await?;
 
@@ -449,6 +975,8 @@ async fn metadata_invalid_xml_marks_failed() -> anyhow::Result<()> {
         identifier: identifier.to_string(),
         fingerprint,
         status: OaiRecordStatus::Available,
+        payload_format: PayloadFormat::Xml,
+        payload_bytes: None,
     };
     let path = data_dir.join(record.path());
     if let Some(parent) = path.parent() {
@@ -484,6 +1012,7 @@ async fn full_pipeline_with_rules_parses_metadata() -> anyhow::Result<()> {
     let server = start_mock_oai_server(MockOaiConfig {
         headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
         records,
+        ..Default::default()
     })
     .await?;
 
@@ -506,3 +1035,1261 @@ async fn full_pipeline_with_rules_parses_metadata() -> anyhow::Result<()> {
     );
     Ok(())
 }
+
+#[tokio::test]
+async fn unchanged_payload_skips_reparse_and_reindex() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("unchanged-payload")?;
+    let rules_path = create_rules_file("unchanged-payload-rules")?;
+    let identifier = "record-unchanged";
+
+    let mut records = HashMap::new();
+    records.insert(
+        identifier.to_string(),
+        GetRecordSpec::Payload(EAD_XML.to_string()),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+        records,
+        ..Default::default()
+    })
+    .await?;
+
+    run_harvest(
+        &pool,
+        &server.endpoint,
+        data_dir.clone(),
+        Some(rules_path.clone()),
+    )
+    .await?;
+    let record_id = fetch_record_id(&pool, &server.endpoint, identifier).await?;
+    sqlx::query("UPDATE indexer_records SET status = 'indexed' WHERE record_id = $1")
+        .bind(record_id)
+        .execute(&pool)
+        .await?;
+
+    // A trivial save upstream: new datestamp, same XML.
+    let bump_datestamp = async || {
+        sqlx::query("UPDATE oai_records SET datestamp = '2000-01-01' WHERE id = $1")
+            .bind(record_id)
+            .execute(&pool)
+            .await
+    };
+    bump_datestamp().await?;
+    let mut config = harvest_config(&server.endpoint, data_dir.clone());
+    config.full = true;
+    run_harvest_with(&pool, config.clone(), Some(rules_path.clone())).await?;
+
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "parsed");
+    assert_eq!(snapshot.version, 2);
+    assert_eq!(snapshot.index_status.as_deref(), Some("indexed"));
+    let run = fetch_latest_run(&pool, &server.endpoint).await?;
+    assert_eq!(run.unchanged, 1);
+
+    // A different payload goes through parse and reindex as before.
+    bump_datestamp().await?;
+    sqlx::query("UPDATE oai_records SET content_hash = 'stale' WHERE id = $1")
+        .bind(record_id)
+        .execute(&pool)
+        .await?;
+    run_harvest_with(&pool, config, Some(rules_path)).await?;
+
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "parsed");
+    assert_eq!(snapshot.index_status.as_deref(), Some("pending"));
+    let run = fetch_latest_run(&pool, &server.endpoint).await?;
+    assert_eq!(run.unchanged, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn changed_payloads_are_kept_as_versions() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("payload-versions")?;
+    let rules_path = create_rules_file("payload-versions-rules")?;
+    let identifier = "record-versions";
+
+    let mut records = HashMap::new();
+    records.insert(
+        identifier.to_string(),
+        GetRecordSpec::Payload(EAD_XML.to_string()),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+        records,
+        ..Default::default()
+    })
+    .await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir.clone());
+    config.full = true;
+    config.keep_versions = 2;
+    run_harvest_with(&pool, config.clone(), Some(rules_path.clone())).await?;
+    let record_id = fetch_record_id(&pool, &server.endpoint, identifier).await?;
+
+    // Two more upstream changes (forced through the unchanged check).
+    for _ in 0..2 {
+        sqlx::query(
+            "UPDATE oai_records SET datestamp = '2000-01-01', content_hash = 'stale' WHERE id = $1",
+        )
+        .bind(record_id)
+        .execute(&pool)
+        .await?;
+        run_harvest_with(&pool, config.clone(), Some(rules_path.clone())).await?;
+    }
+
+    let versions = history::versions(&pool, record_id).await?;
+    assert_eq!(
+        versions.iter().map(|v| v.version).collect::<Vec<_>>(),
+        vec![2, 3],
+        "pruned to the newest two"
+    );
+    assert!(versions.iter().all(|v| v.metadata.is_some()));
+
+    let fingerprint = fetch_fingerprint(&pool, &server.endpoint, identifier).await?;
+    let record = OaiRecord {
+        id: record_id,
+        identifier: identifier.to_string(),
+        fingerprint,
+        status: OaiRecordStatus::Parsed,
+        payload_format: PayloadFormat::Xml,
+        payload_bytes: None,
+    };
+    assert!(
+        !data_dir
+            .join(record.version_path(1, PayloadFormat::Xml))
+            .exists()
+    );
+    assert_eq!(
+        fs::read_to_string(data_dir.join(record.version_path(3, PayloadFormat::Xml)))?,
+        EAD_XML
+    );
+    assert!(data_dir.join(record.path()).exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn compressed_payloads_are_parsed_and_converted() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("compressed-payloads")?;
+    let rules_path = create_rules_file("compressed-payloads-rules")?;
+    let identifier = "record-compressed";
+
+    let mut records = HashMap::new();
+    records.insert(
+        identifier.to_string(),
+        GetRecordSpec::Payload(EAD_XML.to_string()),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+        records,
+        ..Default::default()
+    })
+    .await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir.clone());
+    config.payload_format = PayloadFormat::Zstd;
+    config.keep_versions = 1;
+    run_harvest_with(&pool, config, Some(rules_path)).await?;
+
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "parsed", "{}", snapshot.message);
+    assert_eq!(
+        snapshot.metadata["title"],
+        serde_json::json!(["Integration Title"])
+    );
+    let mut record = OaiRecord {
+        id: fetch_record_id(&pool, &server.endpoint, identifier).await?,
+        identifier: identifier.to_string(),
+        fingerprint: fetch_fingerprint(&pool, &server.endpoint, identifier).await?,
+        status: OaiRecordStatus::Parsed,
+        payload_format: PayloadFormat::Zstd,
+        payload_bytes: None,
+    };
+    let stored = fs::read(data_dir.join(record.path()))?;
+    assert_eq!(zstd::decode_all(stored.as_slice())?, EAD_XML.as_bytes());
+    assert!(!data_dir.join(record.path_as(PayloadFormat::Xml)).exists());
+
+    let cfg = CompressArgs {
+        dir: data_dir.clone(),
+        storage: StorageArgs {
+            storage_url: None,
+            s3_endpoint: None,
+            s3_region: "us-east-1".to_string(),
+        },
+        format: PayloadFormat::Gzip,
+    };
+    compress(cfg, pool.clone(), Arc::new(AtomicBool::new(false))).await?;
+
+    let format: String = sqlx::query_scalar("SELECT payload_format FROM oai_records WHERE id = $1")
+        .bind(record.id)
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(format, "gzip");
+    let versions = history::versions(&pool, record.id).await?;
+    assert_eq!(versions[0].payload_format, PayloadFormat::Gzip);
+    assert!(!data_dir.join(record.path()).exists());
+    assert!(
+        !data_dir
+            .join(record.version_path(1, PayloadFormat::Zstd))
+            .exists()
+    );
+    record.payload_format = PayloadFormat::Gzip;
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(data_dir.join(record.path()))?)
+        .read_to_string(&mut decoded)?;
+    assert_eq!(decoded, EAD_XML);
+    assert!(
+        data_dir
+            .join(record.version_path(1, PayloadFormat::Gzip))
+            .exists()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn payloads_are_stored_in_and_parsed_from_s3() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("s3-payloads")?;
+    let rules_path = create_rules_file("s3-payloads-rules")?;
+    let identifier = "record-s3";
+
+    let mut records = HashMap::new();
+    records.insert(
+        identifier.to_string(),
+        GetRecordSpec::Payload(EAD_XML.to_string()),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+        records,
+        ..Default::default()
+    })
+    .await?;
+    let s3 = start_mock_s3_server().await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir.clone());
+    config.storage = StorageConfig::S3(S3Config {
+        endpoint: s3.endpoint.clone(),
+        bucket: "payloads".to_string(),
+        prefix: "ead/".to_string(),
+        region: "us-east-1".to_string(),
+        access_key_id: "minioadmin".to_string(),
+        secret_access_key: "minioadmin".to_string(),
+    });
+    config.payload_format = PayloadFormat::Gzip;
+    config.keep_versions = 1;
+    run_harvest_with(&pool, config, Some(rules_path)).await?;
+
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "parsed", "{}", snapshot.message);
+    assert_eq!(
+        snapshot.metadata["title"],
+        serde_json::json!(["Integration Title"])
+    );
+
+    let record = OaiRecord {
+        id: fetch_record_id(&pool, &server.endpoint, identifier).await?,
+        identifier: identifier.to_string(),
+        fingerprint: fetch_fingerprint(&pool, &server.endpoint, identifier).await?,
+        status: OaiRecordStatus::Parsed,
+        payload_format: PayloadFormat::Gzip,
+        payload_bytes: None,
+    };
+    let key = |path: std::path::PathBuf| format!("/payloads/ead/{}", path.display());
+    assert_eq!(
+        s3.keys(),
+        vec![
+            key(record.version_path(1, PayloadFormat::Gzip)),
+            key(record.path()),
+        ]
+    );
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(s3.object(&key(record.path())).unwrap().as_slice())
+        .read_to_string(&mut decoded)?;
+    assert_eq!(decoded, EAD_XML);
+    assert_eq!(fs::read_dir(&data_dir)?.count(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn gc_reports_then_removes_files_no_record_needs() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("gc")?;
+    let endpoint = "https://gc.example.org/oai";
+
+    insert_record(&pool, endpoint, "record-kept", DEFAULT_DATESTAMP, "parsed").await?;
+    insert_record(
+        &pool,
+        endpoint,
+        "record-deleted",
+        DEFAULT_DATESTAMP,
+        "deleted",
+    )
+    .await?;
+    let record = |identifier: &str, fingerprint: String| OaiRecord {
+        id: 0,
+        identifier: identifier.to_string(),
+        fingerprint,
+        status: OaiRecordStatus::Parsed,
+        payload_format: PayloadFormat::Xml,
+        payload_bytes: None,
+    };
+    let kept = record(
+        "record-kept",
+        fetch_fingerprint(&pool, endpoint, "record-kept").await?,
+    );
+    let deleted = record(
+        "record-deleted",
+        fetch_fingerprint(&pool, endpoint, "record-deleted").await?,
+    );
+    let orphan = record("record-gone", "ffffffffffffffffffffffff".to_string());
+
+    let kept_path = data_dir.join(kept.path());
+    let garbage = [
+        data_dir.join(deleted.path()),
+        data_dir.join(orphan.path()),
+        data_dir.join(kept.path_as(PayloadFormat::Zstd)),
+        data_dir.join(kept.version_path(1, PayloadFormat::Xml)),
+        kept_path.with_file_name(format!(".{}.xml.1-0.tmp", kept.fingerprint)),
+    ];
+    let unrecognized = data_dir.join("README.txt");
+    for path in garbage.iter().chain([&kept_path, &unrecognized]) {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, EAD_XML)?;
+    }
+
+    let cfg = |apply, storage_url: Option<&str>| GcArgs {
+        dir: data_dir.clone(),
+        storage: StorageArgs {
+            storage_url: storage_url.map(str::to_string),
+            s3_endpoint: None,
+            s3_region: "us-east-1".to_string(),
+        },
+        apply,
+    };
+    let error = gc(
+        cfg(true, Some("s3://payloads")),
+        pool.clone(),
+        Arc::new(AtomicBool::new(false)),
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("s3://payloads"), "{error}");
+
+    gc(
+        cfg(false, None),
+        pool.clone(),
+        Arc::new(AtomicBool::new(false)),
+    )
+    .await?;
+    for path in garbage.iter().chain([&kept_path, &unrecognized]) {
+        assert!(path.exists(), "{} is only reported", path.display());
+    }
+
+    gc(
+        cfg(true, None),
+        pool.clone(),
+        Arc::new(AtomicBool::new(false)),
+    )
+    .await?;
+    for path in &garbage {
+        assert!(!path.exists(), "{} is removed", path.display());
+    }
+    assert!(kept_path.exists());
+    assert!(unrecognized.exists());
+    assert!(!data_dir.join(&orphan.fingerprint[0..2]).exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_requeues_records_whose_payload_is_missing_or_corrupt() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("verify")?;
+    let endpoint = "https://verify.example.org/oai";
+
+    let hash = format!("{:x}", Sha256::digest(EAD_XML.as_bytes()));
+    let mut paths = HashMap::new();
+    for (identifier, status, format) in [
+        ("record-intact", "parsed", PayloadFormat::Xml),
+        ("record-unhashed", "available", PayloadFormat::Xml),
+        ("record-missing", "parsed", PayloadFormat::Xml),
+        ("record-altered", "available", PayloadFormat::Xml),
+        ("record-undecodable", "parsed", PayloadFormat::Gzip),
+    ] {
+        insert_record(&pool, endpoint, identifier, DEFAULT_DATESTAMP, status).await?;
+        sqlx::query(
+            "UPDATE oai_records SET content_hash = $2, payload_format = $3, prior_status = 'parsed' \
+             WHERE identifier = $1",
+        )
+        .bind(identifier)
+        .bind((identifier != "record-unhashed").then_some(hash.as_str()))
+        .bind(format.as_str())
+        .execute(&pool)
+        .await?;
+        let record = OaiRecord {
+            id: 0,
+            identifier: identifier.to_string(),
+            fingerprint: fetch_fingerprint(&pool, endpoint, identifier).await?,
+            status: OaiRecordStatus::Parsed,
+            payload_format: format,
+            payload_bytes: None,
+        };
+        paths.insert(identifier, data_dir.join(record.path()));
+    }
+    for (identifier, contents) in [
+        ("record-intact", EAD_XML),
+        ("record-unhashed", "<ead/>"),
+        ("record-altered", "<ead/>"),
+        ("record-undecodable", EAD_XML),
+    ] {
+        fs::create_dir_all(paths[identifier].parent().unwrap())?;
+        fs::write(&paths[identifier], contents)?;
+    }
+
+    let cfg = |dry_run| VerifyArgs {
+        dir: data_dir.clone(),
+        storage: StorageArgs {
+            storage_url: None,
+            s3_endpoint: None,
+            s3_region: "us-east-1".to_string(),
+        },
+        dry_run,
+    };
+    verify(cfg(true), pool.clone(), Arc::new(AtomicBool::new(false))).await?;
+    let snapshot = fetch_record_snapshot(&pool, endpoint, "record-missing").await?;
+    assert_eq!(snapshot.status, "parsed", "a dry run only reports");
+
+    verify(cfg(false), pool.clone(), Arc::new(AtomicBool::new(false))).await?;
+    for (identifier, status) in [
+        ("record-intact", "parsed"),
+        ("record-unhashed", "available"),
+    ] {
+        let snapshot = fetch_record_snapshot(&pool, endpoint, identifier).await?;
+        assert_eq!(snapshot.status, status, "{identifier}");
+    }
+    for (identifier, message) in [
+        ("record-missing", "payload is missing"),
+        ("record-altered", "does not match its checksum"),
+        ("record-undecodable", "does not decode"),
+    ] {
+        let snapshot = fetch_record_snapshot(&pool, endpoint, identifier).await?;
+        assert_eq!(snapshot.status, "pending", "{identifier}");
+        assert!(snapshot.message.contains(message), "{}", snapshot.message);
+    }
+    let prior_status: Option<String> = sqlx::query_scalar(
+        "SELECT prior_status FROM oai_records WHERE identifier = 'record-altered'",
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(prior_status, None, "the re-download is parsed afresh");
+    Ok(())
+}
+
+#[tokio::test]
+async fn harvest_lists_incrementally_after_a_completed_run() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("incremental")?;
+    let identifier = "record-incremental";
+
+    let mut records = HashMap::new();
+    records.insert(
+        identifier.to_string(),
+        GetRecordSpec::Payload(EAD_XML.to_string()),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+        records,
+        ..Default::default()
+    })
+    .await?;
+
+    // No completed run yet: a complete listing.
+    run_harvest(&pool, &server.endpoint, data_dir.clone(), None).await?;
+    // Completed run on record: only changes since then (at day granularity).
+    run_harvest(&pool, &server.endpoint, data_dir.clone(), None).await?;
+    // --full ignores the run history.
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.full = true;
+    run_harvest_with(&pool, config, None).await?;
+
+    let requests = server.requests("ListIdentifiers");
+    assert_eq!(requests.len(), 3);
+    assert!(!requests[0].contains_key("from"));
+    let from = requests[1].get("from").expect("incremental run sends from");
+    assert_eq!(from.len(), "YYYY-MM-DD".len(), "day granularity: {from}");
+    assert!(!requests[2].contains_key("from"));
+
+    // The incremental listing saw nothing older than `from`.
+    let run = fetch_latest_run(&pool, &server.endpoint).await?;
+    assert_eq!(run.outcome, "completed");
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "available");
+    assert_eq!(snapshot.version, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn harvest_ignores_failed_runs_when_listing_incrementally() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("incremental-failed-run")?;
+
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec("record-a", DEFAULT_DATESTAMP, Some("deleted"))],
+        records: HashMap::new(),
+        ..Default::default()
+    })
+    .await?;
+
+    for outcome in ["failed", "interrupted", "running"] {
+        sqlx::query(
+            "INSERT INTO runs (kind, endpoint, metadata_prefix, outcome) VALUES ('harvest', $1, $2, $3)",
+        )
+        .bind(&server.endpoint)
+        .bind(support::METADATA_PREFIX)
+        .bind(outcome)
+        .execute(&pool)
+        .await?;
+    }
+
+    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+
+    let requests = server.requests("ListIdentifiers");
+    assert_eq!(requests.len(), 1);
+    assert!(!requests[0].contains_key("from"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn set_scoped_harvest_lists_only_set_members() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("set-scoped")?;
+    let member = "record-in-set";
+    let outsider = "record-outside-set";
+
+    let mut records = HashMap::new();
+    for identifier in [member, outsider] {
+        records.insert(
+            identifier.to_string(),
+            GetRecordSpec::Payload(EAD_XML.to_string()),
+        );
+    }
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![
+            header_spec(member, DEFAULT_DATESTAMP, None).in_sets(&["manuscripts", "all"]),
+            header_spec(outsider, DEFAULT_DATESTAMP, None).in_sets(&["photographs", "all"]),
+        ],
+        records,
+        ..Default::default()
+    })
+    .await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.scope = config.scope.with_set(Some("manuscripts".to_string()));
+    run_harvest_with(&pool, config, None).await?;
+
+    let requests = server.requests("ListIdentifiers");
+    assert_eq!(
+        requests[0].get("set").map(String::as_str),
+        Some("manuscripts")
+    );
+
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, member).await?;
+    assert_eq!(snapshot.status, "available");
+    assert_eq!(snapshot.set_specs, vec!["manuscripts", "all"]);
+    assert_eq!(
+        count_records_for_identifier(&pool, &server.endpoint, outsider).await?,
+        0
+    );
+
+    let run = sqlx::query_scalar::<_, String>("SELECT set_spec FROM runs ORDER BY id DESC LIMIT 1")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(run, "manuscripts");
+    Ok(())
+}
+
+#[tokio::test]
+async fn deleted_or_setless_headers_keep_their_set_membership() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("set-membership")?;
+    let deleted = "record-deleted-without-sets";
+    let setless = "record-listed-without-sets";
+    let moved = "record-moved-set";
+
+    let mut records = HashMap::new();
+    for identifier in [setless, moved] {
+        records.insert(
+            identifier.to_string(),
+            GetRecordSpec::Payload(EAD_XML.to_string()),
+        );
+    }
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![
+            header_spec(deleted, DEFAULT_DATESTAMP, Some("deleted")),
+            header_spec(setless, DEFAULT_DATESTAMP, None),
+            header_spec(moved, DEFAULT_DATESTAMP, None).in_sets(&["photographs"]),
+        ],
+        records,
+        ..Default::default()
+    })
+    .await?;
+
+    for identifier in [deleted, setless, moved] {
+        insert_record(
+            &pool,
+            &server.endpoint,
+            identifier,
+            "2000-01-01",
+            "available",
+        )
+        .await?;
+    }
+    sqlx::query("UPDATE oai_records SET set_specs = '{manuscripts}' WHERE endpoint = $1")
+        .bind(&server.endpoint)
+        .execute(&pool)
+        .await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.full = true;
+    run_harvest_with(&pool, config, None).await?;
+
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, deleted).await?;
+    assert_eq!(snapshot.status, "deleted");
+    assert_eq!(snapshot.set_specs, vec!["manuscripts"]);
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, setless).await?;
+    assert_eq!(snapshot.set_specs, vec!["manuscripts"]);
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, moved).await?;
+    assert_eq!(snapshot.set_specs, vec!["photographs"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_records_mode_stores_payloads_without_get_record() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("list-records")?;
+    let identifier = "record-inline";
+    let deleted = "record-inline-deleted";
+
+    let mut records = HashMap::new();
+    records.insert(
+        identifier.to_string(),
+        GetRecordSpec::Payload(EAD_XML.to_string()),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![
+            header_spec(identifier, DEFAULT_DATESTAMP, None),
+            header_spec(deleted, DEFAULT_DATESTAMP, Some("deleted")),
+        ],
+        records,
+        ..Default::default()
+    })
+    .await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir.clone());
+    config.mode = HarvestMode::ListRecords;
+    run_harvest_with(&pool, config, None).await?;
+
+    assert_eq!(server.requests("ListRecords").len(), 1);
+    assert!(server.requests("ListIdentifiers").is_empty());
+    assert!(server.requests("GetRecord").is_empty());
+
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "available");
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, deleted).await?;
+    assert_eq!(snapshot.status, "deleted");
+
+    let fingerprint = fetch_fingerprint(&pool, &server.endpoint, identifier).await?;
+    let record = OaiRecord {
+        id: 0,
+        identifier: identifier.to_string(),
+        fingerprint,
+        status: OaiRecordStatus::Available,
+        payload_format: PayloadFormat::Xml,
+        payload_bytes: None,
+    };
+    let payload = fs::read_to_string(data_dir.join(record.path()))?;
+    assert_eq!(payload, EAD_XML);
+
+    let run = fetch_latest_run(&pool, &server.endpoint).await?;
+    assert_eq!(run.outcome, "completed");
+    assert_eq!(run.processed, 2);
+    assert_eq!(run.imported, 1);
+    assert_eq!(run.deleted, 1);
+    assert_eq!(run.failed, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn records_keep_their_header_and_about_provenance() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let identifier = "record-with-about";
+    let deleted = "record-with-about-deleted";
+    let provenance = "<provenance xmlns=\"http://www.openarchives.org/OAI/2.0/provenance\">\
+                      <originDescription harvestDate=\"2026-01-01\" altered=\"false\">\
+                      <baseURL>https://example.org/oai</baseURL></originDescription></provenance>";
+    let rights = "<rights>CC BY 4.0</rights>";
+
+    for mode in [HarvestMode::ListIdentifiers, HarvestMode::ListRecords] {
+        let data_dir = create_temp_dir("about")?;
+        let mut records = HashMap::new();
+        records.insert(
+            identifier.to_string(),
+            GetRecordSpec::Payload(EAD_XML.to_string()),
+        );
+        let server = start_mock_oai_server(MockOaiConfig {
+            headers: vec![
+                header_spec(identifier, DEFAULT_DATESTAMP, None).in_sets(&["manuscripts"]),
+                header_spec(deleted, DEFAULT_DATESTAMP, Some("deleted")),
+            ],
+            records,
+            about: HashMap::from([(
+                identifier.to_string(),
+                vec![provenance.to_string(), rights.to_string()],
+            )]),
+            ..Default::default()
+        })
+        .await?;
+
+        let mut config = harvest_config(&server.endpoint, data_dir);
+        config.mode = mode;
+        run_harvest_with(&pool, config, None).await?;
+
+        let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+        assert_eq!(snapshot.status, "available", "{mode:?}");
+        assert_eq!(snapshot.about, vec![provenance, rights], "{mode:?}");
+        assert_eq!(snapshot.header_status, None);
+        assert_eq!(snapshot.set_specs, vec!["manuscripts"]);
+        assert_eq!(
+            snapshot.last_seen_response_date.as_deref(),
+            Some("2026-02-07T00:00:00Z")
+        );
+
+        let snapshot = fetch_record_snapshot(&pool, &server.endpoint, deleted).await?;
+        assert_eq!(snapshot.header_status.as_deref(), Some("deleted"));
+        assert!(snapshot.about.is_empty());
+        assert_eq!(
+            snapshot.last_seen_response_date.as_deref(),
+            Some("2026-02-07T00:00:00Z")
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_records_mode_falls_back_to_get_record_without_inline_metadata() -> anyhow::Result<()>
+{
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("list-records-fallback")?;
+    let identifier = "record-no-inline-metadata";
+
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+        records: HashMap::new(),
+        ..Default::default()
+    })
+    .await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.mode = HarvestMode::ListRecords;
+    run_harvest_with(&pool, config, None).await?;
+
+    let requests = server.requests("GetRecord");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].get("identifier").map(String::as_str),
+        Some(identifier)
+    );
+    Ok(())
+}
+
+fn paged_config(count: usize) -> MockOaiConfig {
+    let mut headers = Vec::new();
+    let mut records = HashMap::new();
+    for n in 1..=count {
+        let identifier = format!("record-page-{n}");
+        headers.push(header_spec(&identifier, DEFAULT_DATESTAMP, None));
+        records.insert(identifier, GetRecordSpec::Payload(EAD_XML.to_string()));
+    }
+    MockOaiConfig {
+        headers,
+        records,
+        page_size: Some(2),
+        ..Default::default()
+    }
+}
+
+async fn imported_page_records(pool: &sqlx::PgPool, endpoint: &str) -> anyhow::Result<Vec<String>> {
+    Ok(sqlx::query_scalar::<_, String>(
+        "SELECT identifier FROM oai_records WHERE endpoint = $1 ORDER BY identifier",
+    )
+    .bind(endpoint)
+    .fetch_all(pool)
+    .await?)
+}
+
+#[tokio::test]
+async fn failed_page_fetch_leaves_resumption_token_saved() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("resumption-saved")?;
+
+    let server = start_mock_oai_server(MockOaiConfig {
+        fail_token: Some("4~~".to_string()),
+        ..paged_config(5)
+    })
+    .await?;
+
+    let result = run_harvest(&pool, &server.endpoint, data_dir, None).await;
+    assert!(result.is_err(), "third page fetch fails");
+
+    let run = fetch_latest_run(&pool, &server.endpoint).await?;
+    assert_eq!(run.outcome, "failed");
+    assert_eq!(
+        imported_page_records(&pool, &server.endpoint).await?.len(),
+        4,
+        "first two pages were imported"
+    );
+
+    let saved = resumption::load(&pool, &OaiScope::new(&server.endpoint, METADATA_PREFIX))
+        .await?
+        .expect("token saved for the unfetched page");
+    assert_eq!(saved.verb, "ListIdentifiers");
+    assert_eq!(saved.token, "4~~");
+    assert_eq!(saved.listing_from, None);
+    assert_eq!(
+        saved.expires_at,
+        Some(Utc.with_ymd_and_hms(2999, 12, 31, 0, 0, 0).unwrap())
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn harvest_resumes_listing_from_saved_token() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("resumption-resume")?;
+    let server = start_mock_oai_server(paged_config(5)).await?;
+    let scope = OaiScope::new(&server.endpoint, METADATA_PREFIX);
+    let listing_started_at = Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap();
+
+    resumption::save(
+        &pool,
+        &scope,
+        &SavedListing {
+            verb: "ListIdentifiers".to_string(),
+            token: "2~~".to_string(),
+            expires_at: None,
+            listing_from: None,
+            listing_started_at,
+        },
+    )
+    .await?;
+
+    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+
+    let requests = server.requests("ListIdentifiers");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0].get("resumptionToken").map(String::as_str),
+        Some("2~~")
+    );
+    assert!(!requests[0].contains_key("metadataPrefix"));
+    assert_eq!(
+        imported_page_records(&pool, &server.endpoint).await?,
+        vec!["record-page-3", "record-page-4", "record-page-5"]
+    );
+    assert!(
+        resumption::load(&pool, &scope).await?.is_none(),
+        "completed listing clears the token"
+    );
+
+    let recorded = sqlx::query_scalar::<_, Option<chrono::DateTime<Utc>>>(
+        "SELECT listing_started_at FROM runs ORDER BY id DESC LIMIT 1",
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(recorded, Some(listing_started_at));
+    Ok(())
+}
+
+#[tokio::test]
+async fn harvest_lists_from_start_when_saved_token_is_rejected() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("resumption-rejected")?;
+    let server = start_mock_oai_server(paged_config(3)).await?;
+    let scope = OaiScope::new(&server.endpoint, METADATA_PREFIX);
+
+    resumption::save(
+        &pool,
+        &scope,
+        &SavedListing {
+            verb: "ListIdentifiers".to_string(),
+            token: "stale".to_string(),
+            expires_at: None,
+            listing_from: None,
+            listing_started_at: Utc::now(),
+        },
+    )
+    .await?;
+
+    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+
+    let requests = server.requests("ListIdentifiers");
+    assert_eq!(
+        requests[0].get("resumptionToken").map(String::as_str),
+        Some("stale")
+    );
+    assert_eq!(
+        requests[1].get("metadataPrefix").map(String::as_str),
+        Some(METADATA_PREFIX)
+    );
+    assert_eq!(
+        imported_page_records(&pool, &server.endpoint).await?.len(),
+        3
+    );
+    assert!(resumption::load(&pool, &scope).await?.is_none());
+
+    let run = fetch_latest_run(&pool, &server.endpoint).await?;
+    assert_eq!(run.outcome, "completed");
+    Ok(())
+}
+
+#[tokio::test]
+async fn token_rejected_mid_listing_restarts_the_listing_once() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("resumption-rejected-mid-listing")?;
+    let server = start_mock_oai_server(MockOaiConfig {
+        reject_token_once: Some("2~~".to_string()),
+        ..paged_config(3)
+    })
+    .await?;
+
+    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+
+    let tokens: Vec<_> = server
+        .requests("ListIdentifiers")
+        .iter()
+        .map(|params| params.get("resumptionToken").cloned())
+        .collect();
+    assert_eq!(
+        tokens,
+        [None, Some("2~~".to_string()), None, Some("2~~".to_string())]
+    );
+    assert_eq!(
+        imported_page_records(&pool, &server.endpoint).await?.len(),
+        3
+    );
+    assert_eq!(
+        fetch_latest_run(&pool, &server.endpoint).await?.outcome,
+        "completed"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_records_match_completes_with_zero_changes() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("no-records-match")?;
+    let server = start_mock_oai_server(MockOaiConfig::default()).await?;
+
+    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+
+    let run = fetch_latest_run(&pool, &server.endpoint).await?;
+    assert_eq!(run.outcome, "completed");
+    assert_eq!(run.processed, 0);
+    assert!(run.error_sample.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_disseminate_format_fails_the_run_with_its_code() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("cannot-disseminate")?;
+    let server = start_mock_oai_server(paged_config(1)).await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.scope = OaiScope::new(&server.endpoint, "oai_dc");
+    let error = run_harvest_with(&pool, config, None)
+        .await
+        .expect_err("an unsupported prefix aborts the harvest");
+    assert!(error.to_string().contains("cannotDisseminateFormat"));
+
+    let run = fetch_latest_run(&pool, &server.endpoint).await?;
+    assert_eq!(run.outcome, "failed");
+    assert!(
+        run.error_sample
+            .starts_with("OAI-PMH cannotDisseminateFormat: Unsupported metadata prefix"),
+        "{}",
+        run.error_sample
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn expired_saved_token_is_discarded() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("resumption-expired")?;
+    let server = start_mock_oai_server(paged_config(3)).await?;
+    let scope = OaiScope::new(&server.endpoint, METADATA_PREFIX);
+
+    resumption::save(
+        &pool,
+        &scope,
+        &SavedListing {
+            verb: "ListIdentifiers".to_string(),
+            token: "2~~".to_string(),
+            expires_at: Some(Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()),
+            listing_from: None,
+            listing_started_at: Utc::now(),
+        },
+    )
+    .await?;
+
+    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+
+    let requests = server.requests("ListIdentifiers");
+    assert!(!requests[0].contains_key("resumptionToken"));
+    assert_eq!(
+        imported_page_records(&pool, &server.endpoint).await?.len(),
+        3
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn throttled_requests_are_retried_and_counted() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("throttled")?;
+    let identifier = "record-throttled";
+
+    let mut records = HashMap::new();
+    records.insert(
+        identifier.to_string(),
+        GetRecordSpec::Payload(EAD_XML.to_string()),
+    );
+    let server = start_mock_oai_server(MockOaiConfig {
+        headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+        records,
+        throttle_first: 2,
+        ..Default::default()
+    })
+    .await?;
+
+    run_harvest(&pool, &server.endpoint, data_dir, None).await?;
+
+    let snapshot = fetch_record_snapshot(&pool, &server.endpoint, identifier).await?;
+    assert_eq!(snapshot.status, "available");
+    assert_eq!(server.requests("Identify").len(), 3);
+
+    let run = fetch_latest_run(&pool, &server.endpoint).await?;
+    assert_eq!(run.outcome, "completed");
+    assert_eq!(run.failed, 0);
+    assert_eq!(run.throttled, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_per_second_spaces_out_requests() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("rate-limited")?;
+    let server = start_mock_oai_server(paged_config(3)).await?;
+
+    let mut config = harvest_config(&server.endpoint, data_dir);
+    config.requests_per_second = Some(20.0);
+    let started = std::time::Instant::now();
+    run_harvest_with(&pool, config, None).await?;
+
+    // Identify, two list pages and three GetRecords: five 50ms gaps.
+    assert_eq!(server.requests("GetRecord").len(), 3);
+    assert!(started.elapsed() >= std::time::Duration::from_millis(250));
+    Ok(())
+}
+
+#[derive(Parser)]
+struct HarvestCli {
+    #[command(flatten)]
+    args: HarvesterArgs,
+}
+
+#[tokio::test]
+async fn sources_file_harvests_each_source_as_its_own_run() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("sources")?;
+    let rules_path = create_rules_file("sources-rules")?;
+
+    let mut servers = Vec::new();
+    for identifier in ["record-source-a", "record-source-b"] {
+        let mut records = HashMap::new();
+        records.insert(
+            identifier.to_string(),
+            GetRecordSpec::Payload(EAD_XML.to_string()),
+        );
+        servers.push(
+            start_mock_oai_server(MockOaiConfig {
+                headers: vec![header_spec(identifier, DEFAULT_DATESTAMP, None)],
+                records,
+                ..Default::default()
+            })
+            .await?,
+        );
+    }
+    // Nothing listens on the discard port: this source fails to connect.
+    let unreachable = "http://127.0.0.1:9";
+
+    let sources_path = data_dir.join("sources.toml");
+    fs::write(
+        &sources_path,
+        format!(
+            r#"
+[[source]]
+endpoint = "{}"
+rules = "{}"
+oai_timeout = 10
+
+[[source]]
+endpoint = "{}"
+metadata_prefix = "{METADATA_PREFIX}"
+concurrency = 2
+
+[[source]]
+endpoint = "{unreachable}"
+oai_timeout = 5
+"#,
+            servers[0].endpoint,
+            rules_path.display(),
+            servers[1].endpoint,
+        ),
+    )?;
+
+    let cli = HarvestCli::try_parse_from([
+        "harvest",
+        "--sources",
+        sources_path.to_str().unwrap(),
+        "--dir",
+        data_dir.to_str().unwrap(),
+        "-m",
+        METADATA_PREFIX,
+    ])?;
+    let result = harvester::harvest(cli.args, pool.clone(), Arc::new(AtomicBool::new(false))).await;
+    let error = result.expect_err("the unreachable source fails the process");
+    assert_eq!(error.to_string(), "1 of 3 source(s) failed");
+
+    let snapshot = fetch_record_snapshot(&pool, &servers[0].endpoint, "record-source-a").await?;
+    assert_eq!(snapshot.status, "parsed", "first source applies its rules");
+    let snapshot = fetch_record_snapshot(&pool, &servers[1].endpoint, "record-source-b").await?;
+    assert_eq!(snapshot.status, "available");
+
+    for server in &servers {
+        let run = fetch_latest_run(&pool, &server.endpoint).await?;
+        assert_eq!(run.outcome, "completed");
+        assert_eq!(run.processed, 1);
+    }
+    let run = fetch_latest_run(&pool, unreachable).await?;
+    assert_eq!(run.outcome, "failed");
+    assert!(!run.error_sample.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn sources_share_the_cross_source_download_cap() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+    let data_dir = create_temp_dir("sources-download-cap")?;
+    let in_flight = Arc::new(InFlight::default());
+
+    let mut servers = Vec::new();
+    for source in ["a", "b"] {
+        let identifiers: Vec<_> = (0..3).map(|i| format!("record-{source}-{i}")).collect();
+        servers.push(
+            start_mock_oai_server(MockOaiConfig {
+                headers: identifiers
+                    .iter()
+                    .map(|identifier| header_spec(identifier, DEFAULT_DATESTAMP, None))
+                    .collect(),
+                records: identifiers
+                    .iter()
+                    .map(|identifier| {
+                        (
+                            identifier.clone(),
+                            GetRecordSpec::Payload(EAD_XML.to_string()),
+                        )
+                    })
+                    .collect(),
+                record_delay: std::time::Duration::from_millis(50),
+                in_flight: Some(in_flight.clone()),
+                ..Default::default()
+            })
+            .await?,
+        );
+    }
+
+    let sources_path = data_dir.join("sources.toml");
+    fs::write(
+        &sources_path,
+        format!(
+            "[[source]]\nendpoint = \"{}\"\n\n[[source]]\nendpoint = \"{}\"\n",
+            servers[0].endpoint, servers[1].endpoint,
+        ),
+    )?;
+
+    let cli = HarvestCli::try_parse_from([
+        "harvest",
+        "--sources",
+        sources_path.to_str().unwrap(),
+        "--dir",
+        data_dir.to_str().unwrap(),
+        "-m",
+        METADATA_PREFIX,
+        "--concurrency",
+        "4",
+        "--max-concurrent-downloads",
+        "1",
+    ])?;
+    harvester::harvest(cli.args, pool.clone(), Arc::new(AtomicBool::new(false))).await?;
+
+    for server in &servers {
+        assert_eq!(server.requests("GetRecord").len(), 3);
+    }
+    assert_eq!(
+        in_flight.peak(),
+        1,
+        "one GetRecord at a time across sources"
+    );
+    Ok(())
+}
+
+#[test]
+fn harvest_requires_an_endpoint_or_sources_file() {
+    assert!(HarvestCli::try_parse_from(["harvest", "-m", METADATA_PREFIX]).is_err());
+    assert!(
+        HarvestCli::try_parse_from([
+            "harvest",
+            "--sources",
+            "sources.toml",
+            "https://example.org/oai"
+        ])
+        .is_err()
+    );
+}
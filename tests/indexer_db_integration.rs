@@ -129,6 +129,61 @@ async fn standard_candidate_queries_select_pending_and_retryable_records() -> an
     Ok(())
 }
 
+#[tokio::test]
+async fn set_scoped_candidate_queries_select_only_set_members() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+
+    for identifier in ["a-in-set", "b-outside-set"] {
+        insert_record_with_index(
+            &pool,
+            ENDPOINT,
+            identifier,
+            DEFAULT_DATESTAMP,
+            "parsed",
+            "pending",
+            "",
+            0,
+            metadata(REPOSITORY),
+        )
+        .await?;
+    }
+    sqlx::query("UPDATE oai_records SET set_specs = $2 WHERE identifier = $1")
+        .bind("a-in-set")
+        .bind(vec!["manuscripts".to_string(), "all".to_string()])
+        .execute(&pool)
+        .await?;
+
+    let s = scope(ENDPOINT).with_set(Some("manuscripts".to_string()));
+    let params = FetchIndexCandidatesParams {
+        scope: &s,
+        source_repository: REPOSITORY,
+        selection_mode: IndexSelectionMode::Standard,
+        max_attempts: Some(5),
+        message_filter: None,
+        last_identifier: None,
+    };
+    let candidates = fetch(&pool, params).await?;
+    assert_eq!(
+        records_with_status(candidates),
+        vec![("a-in-set".to_string(), OaiRecordStatus::Parsed)]
+    );
+
+    // Without a set, every record in the repository is a candidate.
+    let s = scope(ENDPOINT);
+    let params = FetchIndexCandidatesParams {
+        scope: &s,
+        source_repository: REPOSITORY,
+        selection_mode: IndexSelectionMode::Standard,
+        max_attempts: Some(5),
+        message_filter: None,
+        last_identifier: None,
+    };
+    assert_eq!(fetch(&pool, params).await?.len(), 2);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn failed_candidate_queries_apply_attempt_and_message_filters() -> anyhow::Result<()> {
     let _guard = acquire_test_lock().await;
@@ -12,11 +12,13 @@ use sqlx::PgPool;
 use harvester::{
     ARCLIGHT_METADATA_PREFIX, ArcLightIndexer, ArcLightIndexerConfig, IndexRunOptions, IndexRunner,
     IndexRunnerConfig,
+    storage::{S3Config, StorageConfig},
 };
 use support::{
-    DEFAULT_DATESTAMP, acquire_test_lock, create_temp_dir, create_temp_file, create_traject_shim,
-    fetch_fingerprint, fetch_latest_run, fetch_record_snapshot, insert_record_with_index, metadata,
-    setup_test_pool, start_mock_solr_server,
+    DEFAULT_DATESTAMP, EAD_XML, acquire_test_lock, create_temp_dir, create_temp_file,
+    create_traject_shim, fetch_fingerprint, fetch_latest_run, fetch_record_snapshot,
+    insert_record_with_index, metadata, setup_test_pool, start_mock_s3_server,
+    start_mock_solr_server,
 };
 
 const ENDPOINT: &str = "https://indexer.example.org/oai";
@@ -65,10 +67,11 @@ fn build_config(
 ) -> ArcLightIndexerConfig {
     ArcLightIndexerConfig {
         configuration,
-        dir: data_dir,
+        storage: StorageConfig::Local(data_dir),
         repository: REPOSITORY_ID.to_string(),
         repository_file,
         record_timeout_seconds: 5,
+        record_timeout_seconds_per_mib: None,
         solr_url,
         solr_commit_within_ms: 1000,
         solr_no_commit: false,
@@ -143,6 +146,135 @@ async fn index_success_marks_record_indexed() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn index_feeds_traject_a_decompressed_payload() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+
+    insert_record_with_index(
+        &pool,
+        ENDPOINT,
+        "index-compressed",
+        DEFAULT_DATESTAMP,
+        "parsed",
+        "pending",
+        "",
+        0,
+        metadata(REPOSITORY),
+    )
+    .await?;
+    sqlx::query("UPDATE oai_records SET payload_format = 'zstd'")
+        .execute(&pool)
+        .await?;
+    let fingerprint = fetch_fingerprint(&pool, ENDPOINT, "index-compressed").await?;
+    let data_dir = create_temp_dir("index-compressed-data")?;
+    let payload_dir = data_dir.join(&fingerprint[0..2]).join(&fingerprint[2..4]);
+    std::fs::create_dir_all(&payload_dir)?;
+    std::fs::write(
+        payload_dir.join(format!("{fingerprint}.xml.zst")),
+        zstd::encode_all(EAD_XML.as_bytes(), 3)?,
+    )?;
+
+    let configuration = create_temp_file("index-compressed-config")?;
+    let repository_file = create_temp_file("index-compressed-repo-file")?;
+    let capture = create_temp_file("index-compressed-capture")?;
+    let shim = create_traject_shim("index-compressed-traject")?;
+    let _path_guard = prepend_path(shim.parent().unwrap());
+    let _mode_guard = EnvVarGuard::set("TRAJECT_SHIM_MODE", "capture".to_string());
+    let _capture_guard = EnvVarGuard::set(
+        "TRAJECT_SHIM_CAPTURE",
+        capture.to_string_lossy().into_owned(),
+    );
+    let solr = start_mock_solr_server(200, r#"{"responseHeader":{"status":0}}"#).await?;
+
+    let config = build_config(
+        configuration,
+        data_dir,
+        repository_file,
+        solr.solr_url.clone(),
+    );
+    let runner = build_runner(
+        ArcLightIndexer::new(config),
+        pool.clone(),
+        IndexRunOptions::standard(Some(5)),
+        false,
+    );
+    runner.run().await?;
+
+    let snapshot = fetch_record_snapshot(&pool, ENDPOINT, "index-compressed").await?;
+    assert_eq!(snapshot.index_status.as_deref(), Some("indexed"));
+    assert_eq!(std::fs::read_to_string(&capture)?, EAD_XML);
+    Ok(())
+}
+
+#[tokio::test]
+async fn index_feeds_traject_a_payload_from_s3() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+
+    insert_record_with_index(
+        &pool,
+        ENDPOINT,
+        "index-s3",
+        DEFAULT_DATESTAMP,
+        "parsed",
+        "pending",
+        "",
+        0,
+        metadata(REPOSITORY),
+    )
+    .await?;
+    let fingerprint = fetch_fingerprint(&pool, ENDPOINT, "index-s3").await?;
+    let s3 = start_mock_s3_server().await?;
+    let storage = StorageConfig::S3(S3Config {
+        endpoint: s3.endpoint.clone(),
+        bucket: "payloads".to_string(),
+        prefix: String::new(),
+        region: "us-east-1".to_string(),
+        access_key_id: "minioadmin".to_string(),
+        secret_access_key: "minioadmin".to_string(),
+    });
+    let key = PathBuf::from(&fingerprint[0..2])
+        .join(&fingerprint[2..4])
+        .join(format!("{fingerprint}.xml"));
+    storage
+        .open()
+        .put(&key, EAD_XML.as_bytes().to_vec())
+        .await?;
+
+    let configuration = create_temp_file("index-s3-config")?;
+    let repository_file = create_temp_file("index-s3-repo-file")?;
+    let capture = create_temp_file("index-s3-capture")?;
+    let shim = create_traject_shim("index-s3-traject")?;
+    let _path_guard = prepend_path(shim.parent().unwrap());
+    let _mode_guard = EnvVarGuard::set("TRAJECT_SHIM_MODE", "capture".to_string());
+    let _capture_guard = EnvVarGuard::set(
+        "TRAJECT_SHIM_CAPTURE",
+        capture.to_string_lossy().into_owned(),
+    );
+    let solr = start_mock_solr_server(200, r#"{"responseHeader":{"status":0}}"#).await?;
+
+    let mut config = build_config(
+        configuration,
+        PathBuf::new(),
+        repository_file,
+        solr.solr_url.clone(),
+    );
+    config.storage = storage;
+    let runner = build_runner(
+        ArcLightIndexer::new(config),
+        pool.clone(),
+        IndexRunOptions::standard(Some(5)),
+        false,
+    );
+    runner.run().await?;
+
+    let snapshot = fetch_record_snapshot(&pool, ENDPOINT, "index-s3").await?;
+    assert_eq!(snapshot.index_status.as_deref(), Some("indexed"));
+    assert_eq!(std::fs::read_to_string(&capture)?, EAD_XML);
+    Ok(())
+}
+
 #[tokio::test]
 async fn index_failure_marks_record_index_failed() -> anyhow::Result<()> {
     let _guard = acquire_test_lock().await;
@@ -1,6 +1,6 @@
 mod support;
 
-use harvester::db::report::{not_seen_since, stale_in_index};
+use harvester::db::report::{PayloadSizeRow, largest_payloads, not_seen_since, stale_in_index};
 use support::{
     DEFAULT_DATESTAMP, acquire_test_lock, fetch_record_id, insert_record, insert_record_with_index,
     metadata, scope, setup_test_pool,
@@ -86,3 +86,51 @@ async fn not_seen_since_reports_records_absent_from_the_feed() -> anyhow::Result
 
     Ok(())
 }
+
+#[tokio::test]
+async fn largest_payloads_lists_the_biggest_stored_records_first() -> anyhow::Result<()> {
+    let _guard = acquire_test_lock().await;
+    let pool = setup_test_pool().await?;
+
+    for (identifier, status, bytes, components) in [
+        ("small", "parsed", Some(100), Some(2)),
+        ("big", "available", Some(5_000), Some(40)),
+        ("medium", "parsed", Some(1_000), None),
+        ("unmeasured", "parsed", None, None),
+        ("was-deleted", "deleted", Some(9_000), Some(90)),
+    ] {
+        insert_record(&pool, ENDPOINT, identifier, DEFAULT_DATESTAMP, status).await?;
+        sqlx::query(
+            "UPDATE oai_records SET payload_bytes = $2, component_count = $3 \
+             WHERE identifier = $1",
+        )
+        .bind(identifier)
+        .bind(bytes.map(i64::from))
+        .bind(components)
+        .execute(&pool)
+        .await?;
+    }
+
+    let largest = largest_payloads(&pool, &scope(ENDPOINT), 2).await?;
+    assert_eq!(
+        largest,
+        vec![
+            PayloadSizeRow {
+                identifier: "big".to_string(),
+                payload_bytes: 5_000,
+                component_count: Some(40),
+            },
+            PayloadSizeRow {
+                identifier: "medium".to_string(),
+                payload_bytes: 1_000,
+                component_count: None,
+            },
+        ]
+    );
+    assert_eq!(
+        largest_payloads(&pool, &scope(ENDPOINT), 10).await?.len(),
+        3
+    );
+
+    Ok(())
+}
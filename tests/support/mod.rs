@@ -8,10 +8,14 @@ use std::{
         Arc, Once, OnceLock,
         atomic::{AtomicBool, AtomicUsize, Ordering},
     },
+    time::Duration,
 };
 
 use anyhow::Context;
-use harvester::{ARCLIGHT_METADATA_PREFIX, Harvester, OaiConfig, OaiScope};
+use harvester::{
+    ARCLIGHT_METADATA_PREFIX, HarvestMode, Harvester, OaiConfig, OaiScope, oai::PayloadFormat,
+    storage::StorageConfig,
+};
 use sqlx::{
     PgPool, Row,
     migrate::Migrator,
@@ -42,6 +46,14 @@ pub struct RecordSnapshot {
     pub version: i32,
     pub metadata: serde_json::Value,
     pub last_seen_at_set: bool,
+    pub last_seen_response_date: Option<String>,
+    pub set_specs: Vec<String>,
+    pub header_status: Option<String>,
+    pub about: Vec<String>,
+    pub payload_bytes: Option<i64>,
+    pub component_count: Option<i32>,
+    pub failure_category: Option<String>,
+    pub attempts: i32,
     pub index_status: Option<String>,
     pub index_message: Option<String>,
     pub index_attempts: Option<i32>,
@@ -56,6 +68,8 @@ pub struct RunSnapshot {
     pub imported: i32,
     pub deleted: i32,
     pub failed: i32,
+    pub throttled: i32,
+    pub unchanged: i32,
     pub error_sample: String,
     pub finished_at_set: bool,
 }
@@ -65,6 +79,14 @@ pub struct HeaderSpec {
     identifier: String,
     datestamp: String,
     status: Option<String>,
+    sets: Vec<String>,
+}
+
+impl HeaderSpec {
+    pub fn in_sets(mut self, sets: &[&str]) -> Self {
+        self.sets = sets.iter().map(ToString::to_string).collect();
+        self
+    }
 }
 
 #[derive(Clone)]
@@ -73,17 +95,75 @@ pub enum GetRecordSpec {
     MissingPayload,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct MockOaiConfig {
     pub headers: Vec<HeaderSpec>,
     pub records: HashMap<String, GetRecordSpec>,
+    /// Headers per list page; `None` returns every header in one page.
+    pub page_size: Option<usize>,
+    /// Answer requests for this resumption token with an HTTP 500.
+    pub fail_token: Option<String>,
+    /// setSpecs returned by ListSets; empty answers `noSetHierarchy`.
+    pub sets: Vec<String>,
+    /// Answer this many requests (of any verb), from the first, with an HTTP
+    /// 503 and `Retry-After: 0`.
+    pub throttle_first: usize,
+    /// Answer the first N GetRecord requests for each identifier with an
+    /// HTTP 502.
+    pub flaky_records: HashMap<String, usize>,
+    /// Answer the first request for this resumption token with
+    /// `badResumptionToken`, as if it expired mid-listing.
+    pub reject_token_once: Option<String>,
+    /// `<about>` container contents per identifier, sent after the metadata
+    /// by GetRecord and ListRecords.
+    pub about: HashMap<String, Vec<String>>,
+    /// Hold each GetRecord answer this long, so requests overlap.
+    pub record_delay: Duration,
+    /// Count GetRecord requests in flight, possibly across several servers.
+    pub in_flight: Option<Arc<InFlight>>,
+}
+
+/// GetRecord requests being answered right now, and the most seen at once.
+#[derive(Default)]
+pub struct InFlight {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl InFlight {
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+
+    fn enter(&self) {
+        let current = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(current, Ordering::SeqCst);
+    }
+
+    fn leave(&self) {
+        self.current.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 pub struct MockOaiServer {
     pub endpoint: String,
+    requests: Arc<std::sync::Mutex<Vec<HashMap<String, String>>>>,
     handle: JoinHandle<()>,
 }
 
+impl MockOaiServer {
+    /// Query params of every request received for `verb`, in arrival order.
+    pub fn requests(&self, verb: &str) -> Vec<HashMap<String, String>> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|params| params.get("verb").map(String::as_str) == Some(verb))
+            .cloned()
+            .collect()
+    }
+}
+
 pub struct MockSolrServer {
     pub solr_url: String,
     handle: JoinHandle<()>,
@@ -95,6 +175,33 @@ impl Drop for MockSolrServer {
     }
 }
 
+/// An in-memory S3 bucket store: objects keyed by request path
+/// (`/bucket/key`). Requests without a SigV4 `Authorization` header get 403.
+pub struct MockS3Server {
+    pub endpoint: String,
+    objects: Arc<std::sync::Mutex<HashMap<String, Vec<u8>>>>,
+    handle: JoinHandle<()>,
+}
+
+impl MockS3Server {
+    /// Stored object paths, sorted.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<_> = self.objects.lock().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn object(&self, path: &str) -> Option<Vec<u8>> {
+        self.objects.lock().unwrap().get(path).cloned()
+    }
+}
+
+impl Drop for MockS3Server {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
 impl Drop for MockOaiServer {
     fn drop(&mut self) {
         self.handle.abort();
@@ -114,6 +221,7 @@ pub fn header_spec(identifier: &str, datestamp: &str, status: Option<&str>) -> H
         identifier: identifier.to_string(),
         datestamp: datestamp.to_string(),
         status: status.map(ToString::to_string),
+        sets: Vec::new(),
     }
 }
 
@@ -147,20 +255,41 @@ pub async fn setup_test_pool() -> anyhow::Result<PgPool> {
     Ok(pool)
 }
 
+pub fn harvest_config(endpoint: &str, data_dir: PathBuf) -> OaiConfig {
+    OaiConfig {
+        storage: StorageConfig::Local(data_dir),
+        scope: harvester::OaiScope::new(endpoint, METADATA_PREFIX),
+        oai_timeout: 10,
+        oai_retries: 0,
+        full: false,
+        mode: HarvestMode::ListIdentifiers,
+        concurrency: 10,
+        adaptive_concurrency: false,
+        requests_per_second: None,
+        keep_versions: 0,
+        payload_format: PayloadFormat::Xml,
+        schema: None,
+        max_payload_bytes: None,
+    }
+}
+
 pub async fn run_harvest(
     pool: &PgPool,
     endpoint: &str,
     data_dir: PathBuf,
     rules: Option<PathBuf>,
 ) -> anyhow::Result<()> {
-    let config = OaiConfig {
-        data_dir,
-        scope: harvester::OaiScope::new(endpoint, METADATA_PREFIX),
-        oai_timeout: 10,
-        oai_retries: 0,
-    };
-    let harvester = Harvester::new(config, pool.clone(), Arc::new(AtomicBool::new(false)));
-    harvester::perform(&harvester, rules).await
+    run_harvest_with(pool, harvest_config(endpoint, data_dir), rules).await
+}
+
+pub async fn run_harvest_with(
+    pool: &PgPool,
+    config: OaiConfig,
+    rules: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let harvester = Harvester::new(config, pool.clone(), Arc::new(AtomicBool::new(false)))?;
+    harvester::perform(&harvester, rules).await?;
+    Ok(())
 }
 
 pub async fn insert_record(
@@ -281,7 +410,9 @@ pub async fn fetch_record_snapshot(
     let row = sqlx::query(
         r#"
         SELECT r.status, r.message, r.datestamp, r.version, r.metadata,
-               r.last_seen_at IS NOT NULL AS last_seen_at_set,
+               r.last_seen_at IS NOT NULL AS last_seen_at_set, r.last_seen_response_date,
+               r.set_specs, r.header_status, r.about, r.payload_bytes, r.component_count,
+               r.failure_category, r.attempts,
                i.status AS index_status,
                i.message AS index_message,
                i.attempts AS index_attempts,
@@ -305,6 +436,14 @@ pub async fn fetch_record_snapshot(
         version: row.try_get("version")?,
         metadata: row.try_get("metadata")?,
         last_seen_at_set: row.try_get("last_seen_at_set")?,
+        last_seen_response_date: row.try_get("last_seen_response_date")?,
+        set_specs: row.try_get("set_specs")?,
+        header_status: row.try_get("header_status")?,
+        about: row.try_get("about")?,
+        payload_bytes: row.try_get("payload_bytes")?,
+        component_count: row.try_get("component_count")?,
+        failure_category: row.try_get("failure_category")?,
+        attempts: row.try_get("attempts")?,
         index_status: row.try_get("index_status")?,
         index_message: row.try_get("index_message")?,
         index_attempts: row.try_get("index_attempts")?,
@@ -316,7 +455,8 @@ pub async fn fetch_record_snapshot(
 pub async fn fetch_latest_run(pool: &PgPool, endpoint: &str) -> anyhow::Result<RunSnapshot> {
     let row = sqlx::query(
         r#"
-        SELECT kind, outcome, processed, imported, deleted, failed, error_sample,
+        SELECT kind, outcome, processed, imported, deleted, failed, throttled, unchanged,
+               error_sample,
                finished_at IS NOT NULL AS finished_at_set
         FROM runs
         WHERE endpoint = $1
@@ -335,6 +475,8 @@ pub async fn fetch_latest_run(pool: &PgPool, endpoint: &str) -> anyhow::Result<R
         imported: row.try_get("imported")?,
         deleted: row.try_get("deleted")?,
         failed: row.try_get("failed")?,
+        throttled: row.try_get("throttled")?,
+        unchanged: row.try_get("unchanged")?,
         error_sample: row.try_get("error_sample")?,
         finished_at_set: row.try_get("finished_at_set")?,
     })
@@ -362,8 +504,12 @@ pub async fn count_records_for_identifier(
 }
 
 pub fn create_rules_file(name: &str) -> anyhow::Result<PathBuf> {
+    create_rules_file_with(name, RULES_CSV)
+}
+
+pub fn create_rules_file_with(name: &str, rules: &str) -> anyhow::Result<PathBuf> {
     let path = unique_path(name).with_extension("csv");
-    fs::write(&path, RULES_CSV)?;
+    fs::write(&path, rules)?;
     Ok(path)
 }
 
@@ -419,6 +565,10 @@ case "$mode" in
     sleep "${TRAJECT_SHIM_SLEEP_SECONDS:-1}"
     exit 0
     ;;
+  capture)
+    cp "${@: -1}" "${TRAJECT_SHIM_CAPTURE}"
+    exit 0
+    ;;
   *)
     echo "unknown traject shim mode: ${mode}" >&2
     exit 2
@@ -469,6 +619,8 @@ pub async fn start_mock_oai_server(config: MockOaiConfig) -> anyhow::Result<Mock
     let endpoint = format!("http://{}", address);
     let endpoint_for_task = endpoint.clone();
     let shared_config = Arc::new(config);
+    let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let requests_for_task = requests.clone();
 
     let handle = tokio::spawn(async move {
         loop {
@@ -478,15 +630,50 @@ pub async fn start_mock_oai_server(config: MockOaiConfig) -> anyhow::Result<Mock
             };
             let endpoint = endpoint_for_task.clone();
             let config = shared_config.clone();
+            let requests = requests_for_task.clone();
             tokio::spawn(async move {
-                if let Err(error) = handle_connection(&mut socket, &endpoint, &config).await {
+                if let Err(error) =
+                    handle_connection(&mut socket, &endpoint, &config, &requests).await
+                {
                     eprintln!("mock OAI server request handling failed: {}", error);
                 }
             });
         }
     });
 
-    Ok(MockOaiServer { endpoint, handle })
+    Ok(MockOaiServer {
+        endpoint,
+        requests,
+        handle,
+    })
+}
+
+pub async fn start_mock_s3_server() -> anyhow::Result<MockS3Server> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let endpoint = format!("http://{}", listener.local_addr()?);
+    let objects = Arc::new(std::sync::Mutex::new(HashMap::new()));
+    let objects_for_task = objects.clone();
+
+    let handle = tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = match listener.accept().await {
+                Ok(value) => value,
+                Err(_) => break,
+            };
+            let objects = objects_for_task.clone();
+            tokio::spawn(async move {
+                if let Err(error) = handle_s3_connection(&mut socket, &objects).await {
+                    eprintln!("mock S3 request handling failed: {}", error);
+                }
+            });
+        }
+    });
+
+    Ok(MockS3Server {
+        endpoint,
+        objects,
+        handle,
+    })
 }
 
 fn load_test_env() {
@@ -542,6 +729,7 @@ async fn handle_connection(
     socket: &mut TcpStream,
     endpoint: &str,
     config: &MockOaiConfig,
+    requests: &std::sync::Mutex<Vec<HashMap<String, String>>>,
 ) -> anyhow::Result<()> {
     let mut buf = vec![0u8; 8192];
     let mut total = 0usize;
@@ -564,13 +752,71 @@ async fn handle_connection(
     let request_line = request.lines().next().unwrap_or_default();
     let path = request_line.split_whitespace().nth(1).unwrap_or("/");
     let params = parse_query_params(path);
-    let body = build_oai_response(endpoint, config, &params);
+    let (received, record_requests, token_requests) = {
+        let mut requests = requests.lock().unwrap();
+        requests.push(params.clone());
+        let record_requests = requests
+            .iter()
+            .filter(|logged| {
+                logged.get("verb").map(String::as_str) == Some("GetRecord")
+                    && logged.get("identifier") == params.get("identifier")
+            })
+            .count();
+        let token_requests = requests
+            .iter()
+            .filter(|logged| {
+                logged.contains_key("resumptionToken")
+                    && logged.get("resumptionToken") == params.get("resumptionToken")
+            })
+            .count();
+        (requests.len(), record_requests, token_requests)
+    };
+    let get_record = params.get("verb").map(String::as_str) == Some("GetRecord");
+    let in_flight = config.in_flight.as_ref().filter(|_| get_record);
+    if let Some(in_flight) = in_flight {
+        in_flight.enter();
+    }
+    if get_record {
+        tokio::time::sleep(config.record_delay).await;
+    }
+    let flaky = get_record
+        && params
+            .get("identifier")
+            .and_then(|identifier| config.flaky_records.get(identifier))
+            .is_some_and(|failures| record_requests <= *failures);
+    let (status, body) = if received <= config.throttle_first {
+        (
+            "503 Service Unavailable\r\nRetry-After: 0",
+            "slow down".to_string(),
+        )
+    } else if flaky {
+        ("502 Bad Gateway", "bad gateway".to_string())
+    } else if config.reject_token_once.is_some()
+        && params.get("resumptionToken") == config.reject_token_once.as_ref()
+        && token_requests == 1
+    {
+        (
+            "200 OK",
+            error_response(endpoint, &params, "badResumptionToken", "Expired token"),
+        )
+    } else if config.fail_token.is_some()
+        && params.get("resumptionToken") == config.fail_token.as_ref()
+    {
+        ("500 Internal Server Error", "upstream failure".to_string())
+    } else {
+        ("200 OK", build_oai_response(endpoint, config, &params))
+    };
     let response = format!(
-        "HTTP/1.1 200 OK\r\nContent-Type: text/xml; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        "HTTP/1.1 {status}\r\nContent-Type: text/xml; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
         body.len(),
         body
     );
 
+    // Left before answering: the client may send its next request as soon as
+    // it has this response.
+    if let Some(in_flight) = in_flight {
+        in_flight.leave();
+    }
     socket.write_all(response.as_bytes()).await?;
     Ok(())
 }
@@ -610,6 +856,91 @@ async fn handle_solr_connection(
     Ok(())
 }
 
+/// Serve keep-alive requests on one connection until the client closes it.
+async fn handle_s3_connection(
+    socket: &mut TcpStream,
+    objects: &std::sync::Mutex<HashMap<String, Vec<u8>>>,
+) -> anyhow::Result<()> {
+    let mut buf = Vec::new();
+    loop {
+        let header_end = loop {
+            if let Some(end) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+                break end + 4;
+            }
+            let mut chunk = [0u8; 8192];
+            let bytes_read = socket.read(&mut chunk).await?;
+            if bytes_read == 0 {
+                return Ok(());
+            }
+            buf.extend_from_slice(&chunk[..bytes_read]);
+        };
+
+        let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+        let method = request_line.next().unwrap_or_default().to_string();
+        let path = request_line.next().unwrap_or_default().to_string();
+        let headers: HashMap<String, String> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        let content_length: usize = headers
+            .get("content-length")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        while buf.len() < header_end + content_length {
+            let mut chunk = [0u8; 8192];
+            let bytes_read = socket.read(&mut chunk).await?;
+            if bytes_read == 0 {
+                return Ok(());
+            }
+            buf.extend_from_slice(&chunk[..bytes_read]);
+        }
+        let body: Vec<u8> = buf
+            .drain(..header_end + content_length)
+            .skip(header_end)
+            .collect();
+
+        let signed = headers
+            .get("authorization")
+            .is_some_and(|value| value.starts_with("AWS4-HMAC-SHA256 Credential="));
+        let (status, response_body) = if !signed {
+            (
+                "403 Forbidden",
+                b"<Error><Code>AccessDenied</Code></Error>".to_vec(),
+            )
+        } else {
+            let mut objects = objects.lock().unwrap();
+            match method.as_str() {
+                "PUT" => {
+                    objects.insert(path, body);
+                    ("200 OK", Vec::new())
+                }
+                "GET" => match objects.get(&path) {
+                    Some(object) => ("200 OK", object.clone()),
+                    None => (
+                        "404 Not Found",
+                        b"<Error><Code>NoSuchKey</Code></Error>".to_vec(),
+                    ),
+                },
+                "DELETE" => {
+                    objects.remove(&path);
+                    ("204 No Content", Vec::new())
+                }
+                _ => ("405 Method Not Allowed", Vec::new()),
+            }
+        };
+
+        let head = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n",
+            status,
+            response_body.len()
+        );
+        socket.write_all(head.as_bytes()).await?;
+        socket.write_all(&response_body).await?;
+    }
+}
+
 fn parse_query_params(path: &str) -> HashMap<String, String> {
     let mut params = HashMap::new();
     let query = path.split_once('?').map(|(_, query)| query).unwrap_or("");
@@ -618,11 +949,36 @@ fn parse_query_params(path: &str) -> HashMap<String, String> {
             continue;
         }
         let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
-        params.insert(key.to_string(), value.to_string());
+        params.insert(percent_decode(key), percent_decode(value));
     }
     params
 }
 
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
 fn build_oai_response(
     endpoint: &str,
     config: &MockOaiConfig,
@@ -630,7 +986,10 @@ fn build_oai_response(
 ) -> String {
     match params.get("verb").map(|value| value.as_str()) {
         Some("Identify") => identify_response(endpoint),
-        Some("ListIdentifiers") => list_identifiers_response(endpoint, params, &config.headers),
+        Some("ListIdentifiers") => list_identifiers_response(endpoint, params, config),
+        Some("ListRecords") => list_records_response(endpoint, params, config),
+        Some("ListMetadataFormats") => list_metadata_formats_response(endpoint),
+        Some("ListSets") => list_sets_response(endpoint, params, config),
         Some("GetRecord") => get_record_response(endpoint, params, config),
         _ => error_response(endpoint, params, "badVerb", "Unknown or missing verb"),
     }
@@ -655,10 +1014,56 @@ fn identify_response(endpoint: &str) -> String {
     )
 }
 
+fn list_metadata_formats_response(endpoint: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<OAI-PMH xmlns="http://www.openarchives.org/OAI/2.0/">
+  <responseDate>2026-02-07T00:00:00Z</responseDate>
+  <request verb="ListMetadataFormats">{endpoint}</request>
+  <ListMetadataFormats>
+    <metadataFormat>
+      <metadataPrefix>{METADATA_PREFIX}</metadataPrefix>
+      <schema>https://www.loc.gov/ead/ead.xsd</schema>
+      <metadataNamespace>urn:isbn:1-931666-22-9</metadataNamespace>
+    </metadataFormat>
+  </ListMetadataFormats>
+</OAI-PMH>"#
+    )
+}
+
+fn list_sets_response(
+    endpoint: &str,
+    params: &HashMap<String, String>,
+    config: &MockOaiConfig,
+) -> String {
+    if config.sets.is_empty() {
+        return error_response(
+            endpoint,
+            params,
+            "noSetHierarchy",
+            "This repository does not support sets",
+        );
+    }
+    let set_xml: String = config
+        .sets
+        .iter()
+        .map(|set| format!("<set><setSpec>{set}</setSpec><setName>Set {set}</setName></set>"))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<OAI-PMH xmlns="http://www.openarchives.org/OAI/2.0/">
+  <responseDate>2026-02-07T00:00:00Z</responseDate>
+  <request verb="ListSets">{endpoint}</request>
+  <ListSets>{set_xml}</ListSets>
+</OAI-PMH>"#
+    )
+}
+
 fn list_identifiers_response(
     endpoint: &str,
     params: &HashMap<String, String>,
-    headers: &[HeaderSpec],
+    config: &MockOaiConfig,
 ) -> String {
     let verb = params
         .get("verb")
@@ -668,18 +1073,13 @@ fn list_identifiers_response(
         .get("metadataPrefix")
         .map(String::as_str)
         .unwrap_or(METADATA_PREFIX);
+    let (headers, token_xml) = match list_page(params, config) {
+        Ok(page) => page,
+        Err((code, message)) => return error_response(endpoint, params, code, message),
+    };
     let header_xml = headers
         .iter()
-        .map(|header| match header.status.as_deref() {
-            Some(status) => format!(
-                "<header status=\"{status}\"><identifier>{}</identifier><datestamp>{}</datestamp></header>",
-                header.identifier, header.datestamp
-            ),
-            None => format!(
-                "<header><identifier>{}</identifier><datestamp>{}</datestamp></header>",
-                header.identifier, header.datestamp
-            ),
-        })
+        .map(|header| header_xml(header))
         .collect::<Vec<_>>()
         .join("");
 
@@ -688,11 +1088,138 @@ fn list_identifiers_response(
 <OAI-PMH xmlns="http://www.openarchives.org/OAI/2.0/">
   <responseDate>2026-02-07T00:00:00Z</responseDate>
   <request verb="{verb}" metadataPrefix="{metadata_prefix}">{endpoint}</request>
-  <ListIdentifiers>{header_xml}</ListIdentifiers>
+  <ListIdentifiers>{header_xml}{token_xml}</ListIdentifiers>
+</OAI-PMH>"#
+    )
+}
+
+/// Headers come from `config.headers`; each record carries its `Payload`
+/// spec as inline metadata (none for deleted or `MissingPayload` records).
+fn list_records_response(
+    endpoint: &str,
+    params: &HashMap<String, String>,
+    config: &MockOaiConfig,
+) -> String {
+    let metadata_prefix = params
+        .get("metadataPrefix")
+        .map(String::as_str)
+        .unwrap_or(METADATA_PREFIX);
+    let (headers, token_xml) = match list_page(params, config) {
+        Ok(page) => page,
+        Err((code, message)) => return error_response(endpoint, params, code, message),
+    };
+    let record_xml = headers
+        .iter()
+        .map(|header| {
+            let metadata_xml = match config.records.get(&header.identifier) {
+                Some(GetRecordSpec::Payload(metadata)) if header.status.is_none() => {
+                    format!("<metadata>{metadata}</metadata>")
+                }
+                _ => String::new(),
+            };
+            format!(
+                "<record>{}{metadata_xml}{}</record>",
+                header_xml(header),
+                about_xml(config, &header.identifier)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("");
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<OAI-PMH xmlns="http://www.openarchives.org/OAI/2.0/">
+  <responseDate>2026-02-07T00:00:00Z</responseDate>
+  <request verb="ListRecords" metadataPrefix="{metadata_prefix}">{endpoint}</request>
+  <ListRecords>{record_xml}{token_xml}</ListRecords>
 </OAI-PMH>"#
     )
 }
 
+/// The headers a list request selects (by `from` and `set`) and the
+/// `<resumptionToken>` element for the page after them. Tokens are
+/// `offset~from~set`, carrying the original arguments like a real provider's
+/// opaque state; anything else is a `badResumptionToken`.
+fn list_page<'a>(
+    params: &HashMap<String, String>,
+    config: &'a MockOaiConfig,
+) -> Result<(Vec<&'a HeaderSpec>, String), (&'static str, &'static str)> {
+    if params
+        .get("metadataPrefix")
+        .is_some_and(|prefix| prefix != METADATA_PREFIX)
+    {
+        return Err(("cannotDisseminateFormat", "Unsupported metadata prefix"));
+    }
+
+    let (offset, from, set) = match params.get("resumptionToken") {
+        Some(token) => {
+            let mut parts = token.splitn(3, '~');
+            let offset = parts.next().and_then(|offset| offset.parse().ok());
+            match (offset, parts.next(), parts.next()) {
+                (Some(offset), Some(from), Some(set)) => (offset, from, set),
+                _ => return Err(("badResumptionToken", "Unknown resumption token")),
+            }
+        }
+        None => (
+            0,
+            params.get("from").map(String::as_str).unwrap_or_default(),
+            params.get("set").map(String::as_str).unwrap_or_default(),
+        ),
+    };
+
+    // Datestamps share the day granularity advertised by Identify, so string
+    // comparison orders them correctly.
+    let matching: Vec<_> = config
+        .headers
+        .iter()
+        .filter(|header| header.datestamp.as_str() >= from)
+        .filter(|header| set.is_empty() || header.sets.iter().any(|s| s == set))
+        .collect();
+    if matching.is_empty() {
+        return Err(("noRecordsMatch", "No matching records"));
+    }
+
+    let page_size = config.page_size.unwrap_or(matching.len());
+    let end = (offset + page_size).min(matching.len());
+    let token_xml = if end < matching.len() {
+        format!(
+            r#"<resumptionToken expirationDate="2999-12-31T00:00:00Z" completeListSize="{}" cursor="{offset}">{end}~{from}~{set}</resumptionToken>"#,
+            matching.len()
+        )
+    } else {
+        String::new()
+    };
+    Ok((matching[offset.min(end)..end].to_vec(), token_xml))
+}
+
+fn header_xml(header: &HeaderSpec) -> String {
+    let set_xml: String = header
+        .sets
+        .iter()
+        .map(|set| format!("<setSpec>{set}</setSpec>"))
+        .collect();
+    match header.status.as_deref() {
+        Some(status) => format!(
+            "<header status=\"{status}\"><identifier>{}</identifier><datestamp>{}</datestamp>{set_xml}</header>",
+            header.identifier, header.datestamp
+        ),
+        None => format!(
+            "<header><identifier>{}</identifier><datestamp>{}</datestamp>{set_xml}</header>",
+            header.identifier, header.datestamp
+        ),
+    }
+}
+
+fn about_xml(config: &MockOaiConfig, identifier: &str) -> String {
+    config
+        .about
+        .get(identifier)
+        .into_iter()
+        .flatten()
+        .map(|about| format!("<about>{about}</about>"))
+        .collect()
+}
+
 fn get_record_response(
     endpoint: &str,
     params: &HashMap<String, String>,
@@ -712,6 +1239,7 @@ fn get_record_response(
         .find(|header| header.identifier == identifier)
         .map(|header| header.datestamp.as_str())
         .unwrap_or(DEFAULT_DATESTAMP);
+    let about_xml = about_xml(config, identifier);
 
     match config.records.get(identifier) {
         Some(GetRecordSpec::Payload(metadata)) => format!(
@@ -725,7 +1253,7 @@ fn get_record_response(
         <identifier>{identifier}</identifier>
         <datestamp>{datestamp}</datestamp>
       </header>
-      <metadata>{metadata}</metadata>
+      <metadata>{metadata}</metadata>{about_xml}
     </record>
   </GetRecord>
 </OAI-PMH>"#
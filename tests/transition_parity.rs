@@ -2,7 +2,7 @@ mod support;
 
 use harvester::{
     db::{harvester as harvest_db, indexer as index_db},
-    oai::{HarvestEvent, IndexEvent},
+    oai::{FailureCategory, HarvestEvent, IndexEvent, PayloadFormat, PayloadSize},
 };
 use support::{
     DEFAULT_DATESTAMP, acquire_test_lock, fetch_record_id, fetch_record_snapshot, insert_record,
@@ -27,19 +27,64 @@ async fn harvest_events_produce_legal_transitions() -> anyhow::Result<()> {
         &pool,
         &scope(ENDPOINT),
         "dl-ok",
-        &HarvestEvent::DownloadSucceeded,
+        &HarvestEvent::DownloadSucceeded {
+            content_hash: "hash-1",
+            payload_format: PayloadFormat::Xml,
+            about: &[],
+            size: PayloadSize::default(),
+        },
     )
     .await?;
     let snap = fetch_record_snapshot(&pool, ENDPOINT, "dl-ok").await?;
     assert_eq!(snap.status, "available");
 
+    // DownloadUnchanged: pending -> parsed (the prior status), only when the
+    // hash matches
+    insert_record(&pool, ENDPOINT, "dl-same", DEFAULT_DATESTAMP, "pending").await?;
+    sqlx::query(
+        "UPDATE oai_records SET content_hash = 'hash-1', prior_status = 'parsed' \
+         WHERE identifier = 'dl-same'",
+    )
+    .execute(&pool)
+    .await?;
+    let changed = harvest_db::transition(
+        &pool,
+        &scope(ENDPOINT),
+        "dl-same",
+        &HarvestEvent::DownloadUnchanged {
+            content_hash: "hash-2",
+            payload_format: PayloadFormat::Xml,
+            about: &[],
+            size: PayloadSize::default(),
+        },
+    )
+    .await?;
+    assert_eq!(changed, 0);
+    harvest_db::transition(
+        &pool,
+        &scope(ENDPOINT),
+        "dl-same",
+        &HarvestEvent::DownloadUnchanged {
+            content_hash: "hash-1",
+            payload_format: PayloadFormat::Xml,
+            about: &[],
+            size: PayloadSize::default(),
+        },
+    )
+    .await?;
+    let snap = fetch_record_snapshot(&pool, ENDPOINT, "dl-same").await?;
+    assert_eq!(snap.status, "parsed");
+
     // DownloadFailed: pending -> failed
     insert_record(&pool, ENDPOINT, "dl-fail", DEFAULT_DATESTAMP, "pending").await?;
     harvest_db::transition(
         &pool,
         &scope(ENDPOINT),
         "dl-fail",
-        &HarvestEvent::DownloadFailed { message: "timeout" },
+        &HarvestEvent::DownloadFailed {
+            message: "timeout",
+            category: FailureCategory::Transient,
+        },
     )
     .await?;
     let snap = fetch_record_snapshot(&pool, ENDPOINT, "dl-fail").await?;
@@ -75,7 +120,7 @@ async fn harvest_events_produce_legal_transitions() -> anyhow::Result<()> {
 
     // HarvestRetry: failed -> pending (batch)
     insert_record(&pool, ENDPOINT, "retry-me", DEFAULT_DATESTAMP, "failed").await?;
-    harvest_db::retry(&pool, &scope(ENDPOINT)).await?;
+    harvest_db::retry(&pool, &scope(ENDPOINT), Default::default()).await?;
     let snap = fetch_record_snapshot(&pool, ENDPOINT, "retry-me").await?;
     assert_eq!(snap.status, "pending");
 
@@ -350,7 +395,8 @@ async fn illegal_status_transitions_are_rejected() -> anyhow::Result<()> {
     let _guard = acquire_test_lock().await;
     let pool = setup_test_pool().await?;
 
-    // pending -> parsed (skips available)
+    // pending -> parsed (skips available; only legal when restoring an
+    // unchanged parsed record)
     insert_record(
         &pool,
         ENDPOINT,